@@ -0,0 +1,490 @@
+"""
+Differential fuzzer for the Python and JS implementations of MistQL.
+
+Generates random queries following py/mistql/grammar.lark, along with random
+data, and runs both implementations on them. Every input they disagree on is
+minimized, then printed as a case ready to paste into shared/testdata.json.
+
+Usage: python fuzz/fuzz.py [--runs N] [--seed S] [--expect js|py]
+
+The JS implementation has to be built first, with `npm run build` in js/.
+"""
+
+import argparse
+import json
+import os
+import queue
+import random
+import re
+import subprocess
+import sys
+import threading
+from typing import Any, Dict, Iterator, List, Optional, Tuple
+
+ROOT = os.path.join(os.path.dirname(os.path.abspath(__file__)), "..")
+GRAMMAR = os.path.join(ROOT, "py", "mistql", "grammar.lark")
+
+sys.path.insert(0, os.path.join(ROOT, "py"))
+
+from mistql.builtins import builtin_arities  # noqa: E402
+
+Outcome = Dict[str, Any]
+
+KEYS = ["a", "b", "name", "items", "x"]
+NUMBERS = ["0", "1", "2", "3", "10", "0.5", "1.5", "1e3"]
+STRINGS = ["", "a", "abc", "a b", "A", "1", "é", "😀", "a,b", "[a-c]+", "\n"]
+FUNCTIONS = {
+    name: arity
+    for name, arity in builtin_arities.items()
+    if re.fullmatch(r"[a-z]+", name) and name != "log"
+}
+
+
+def operator_levels(grammar: str) -> Tuple[List[List[str]], List[str]]:
+    """
+    Reads the binary operators of each precedence level, lowest first, and
+    the unary operators, from the op_* rules of the grammar.
+    """
+    binary: List[List[str]] = []
+    unary: List[str] = []
+    for body in re.findall(r"^\?op_\w+:(.*?)(?=^\S|\Z)", grammar, re.M | re.S):
+        level = re.findall(r'op_\w+ _wslr\{"(.+?)"\} op_\w+', body)
+        if level:
+            binary.append(level)
+        unary += re.findall(r'_wsr\{"(.+?)"\} op_\w+', body)
+    return binary, unary
+
+
+class Node:
+    """
+    A generated query. Kept as a tree rather than text, so failures can be
+    minimized by replacing nodes with their children.
+    """
+
+    def __init__(self, kind: str, text: str = "", children=()):
+        self.kind = kind
+        self.text = text
+        self.children: List[Optional[Node]] = list(children)
+
+    def size(self) -> int:
+        return 1 + sum(child.size() for child in self.children if child)
+
+
+class Renderer:
+    """
+    Renders nodes into query text, parenthesizing only where precedence
+    requires it.
+    """
+
+    def __init__(self, binary: List[List[str]]):
+        self.levels = {op: i + 2 for i, level in enumerate(binary) for op in level}
+        self.unary_level = len(binary) + 2
+        self.atom_level = len(binary) + 3
+
+    def level(self, node: Node) -> int:
+        if node.kind in ("pipe", "let", "def"):
+            return 0
+        if node.kind == "call":
+            return 1
+        if node.kind == "binary":
+            return self.levels[node.text]
+        if node.kind == "unary":
+            return self.unary_level
+        return self.atom_level
+
+    def render(self, node: Node, min_level: int = 0) -> str:
+        text = self.render_bare(node)
+        return f"({text})" if self.level(node) < min_level else text
+
+    def render_bare(self, node: Node) -> str:
+        kind, text, children = node.kind, node.text, node.children
+        if kind in ("literal", "ref"):
+            return text
+        if kind == "array":
+            return "[" + ", ".join(self.render(item) for item in children) + "]"
+        if kind == "object":
+            return "{" + ", ".join(self.render_bare(entry) for entry in children) + "}"
+        if kind == "entry":
+            return f"{text}: {self.render(children[0])}"
+        if kind == "binary":
+            level = self.levels[text]
+            left = self.render(children[0], level)
+            return f"{left} {text} {self.render(children[1], level + 1)}"
+        if kind == "unary":
+            return text + self.render(children[0], self.unary_level)
+        if kind == "dot":
+            return self.render(children[0], self.atom_level) + "." + text
+        if kind == "index":
+            slots = [self.render(slot) if slot else "" for slot in children[1:]]
+            return self.render(children[0], self.atom_level) + f"[{':'.join(slots)}]"
+        if kind == "call":
+            return " ".join(self.render(part, 2) for part in children)
+        if kind == "pipe":
+            return " | ".join(self.render(stage, 1) for stage in children)
+        if kind == "let":
+            value, body = (self.render(child) for child in children)
+            return f"let {text} = {value} in {body}"
+        if kind == "def":
+            value, body = (self.render(child) for child in children)
+            return f"def {text} = {value} in {body}"
+        raise ValueError(f"Unknown node kind {kind}")
+
+
+class Generator:
+    """
+    Generates random queries, one method per rule of grammar.lark, and
+    random data for them to run against.
+    """
+
+    def __init__(self, rng: random.Random, binary: List[List[str]], unary: List[str]):
+        self.rng = rng
+        self.binary = [op for level in binary for op in level]
+        self.unary = unary
+        # Names bound by let and def, with the arity of functions
+        self.bound: List[Tuple[str, Optional[int]]] = []
+        self.names = 0
+
+    def query(self, depth: int) -> Node:
+        self.names = 0
+        return self.piped_expression(depth)
+
+    def data(self, depth: int = 3) -> Any:
+        # Mostly objects, so that references to keys resolve
+        return self.record(depth) if self.rng.random() < 0.8 else self.value(depth)
+
+    def record(self, depth: int) -> Dict[str, Any]:
+        return {key: self.value(depth - 1) for key in KEYS if self.rng.random() < 0.8}
+
+    def value(self, depth: int) -> Any:
+        choice = self.rng.random()
+        if depth > 0 and choice < 0.3:
+            return self.record(depth)
+        if depth > 0 and choice < 0.5:
+            return [self.value(depth - 1) for _ in range(self.rng.randint(0, 4))]
+        return self.rng.choice(
+            [None, True, False, self.rng.randint(-2, 5), self.rng.choice([0.5, -1.5])]
+            + [1e21, 1e-7, self.rng.choice(STRINGS)]
+        )
+
+    def piped_expression(self, depth: int) -> Node:
+        choice = self.rng.random()
+        if depth <= 0 or choice < 0.6:
+            return self.simple_expression(depth)
+        if choice < 0.8:
+            stages = [self.simple_expression(depth - 1)]
+            for _ in range(self.rng.randint(1, 3)):
+                stages.append(self.fncall(depth - 1, piped=True))
+            return Node("pipe", children=stages)
+        name = self.fresh_name()
+        if choice < 0.9:
+            value = self.piped_expression(depth - 1)
+            self.bound.append((name, None))
+            body = self.piped_expression(depth - 1)
+            self.bound.pop()
+            return Node("let", name, [value, body])
+        params = [self.fresh_name() for _ in range(self.rng.randint(1, 2))]
+        self.bound += [(param, None) for param in params]
+        value = self.piped_expression(depth - 1)
+        del self.bound[-len(params) :]
+        self.bound.append((name, len(params)))
+        body = self.piped_expression(depth - 1)
+        self.bound.pop()
+        return Node("def", " ".join([name] + params), [value, body])
+
+    def simple_expression(self, depth: int) -> Node:
+        if depth > 0 and self.rng.random() < 0.3:
+            return self.fncall(depth)
+        return self.op(depth)
+
+    def fncall(self, depth: int, piped: bool = False) -> Node:
+        functions = [(name, (n, n)) for name, n in self.bound if n is not None]
+        name, (min_args, max_args) = self.rng.choice(
+            functions + list(FUNCTIONS.items())
+        )
+        if max_args < 0:
+            max_args = min_args + 2
+        count = self.rng.randint(min_args, max_args)
+        # Occasionally get the arity wrong
+        if self.rng.random() < 0.05:
+            count += self.rng.choice([-1, 1])
+        if piped:
+            count -= 1
+        fn = Node("ref", name)
+        if self.rng.random() < 0.1:
+            fn = Node("dot", name, [Node("ref", "$")])
+        args = [self.op(depth - 1) for _ in range(max(count, 0))]
+        return Node("call", children=[fn] + args)
+
+    def op(self, depth: int) -> Node:
+        choice = self.rng.random()
+        if depth <= 0 or choice < 0.5:
+            return self.op_h(depth)
+        if choice < 0.9:
+            op = self.rng.choice(self.binary)
+            return Node("binary", op, [self.op(depth - 1), self.op(depth - 1)])
+        return Node("unary", self.rng.choice(self.unary), [self.op(depth - 1)])
+
+    def op_h(self, depth: int) -> Node:
+        choice = self.rng.random()
+        if depth <= 0 or choice < 0.7:
+            return self.simplevalue(depth)
+        if choice < 0.85:
+            name = self.rng.choice(KEYS + list(FUNCTIONS))
+            return Node("dot", name, [self.op_h(depth - 1)])
+        slots: List[Optional[Node]] = [self.piped_expression(depth - 1)]
+        if self.rng.random() < 0.4:
+            slots = [self.maybe(depth - 1), self.maybe(depth - 1)]
+        return Node("index", children=[self.op_h(depth - 1)] + slots)
+
+    def maybe(self, depth: int) -> Optional[Node]:
+        return self.piped_expression(depth) if self.rng.random() < 0.7 else None
+
+    def simplevalue(self, depth: int) -> Node:
+        choice = self.rng.random()
+        if depth > 0 and choice < 0.1:
+            return self.piped_expression(depth - 1)
+        if depth > 0 and choice < 0.2:
+            items = self.rng.randint(0, 3)
+            return Node("array", children=[self.op(depth - 1) for _ in range(items)])
+        if depth > 0 and choice < 0.3:
+            entries = [
+                Node("entry", self.key(), [self.op(depth - 1)])
+                for _ in range(self.rng.randint(0, 3))
+            ]
+            return Node("object", children=entries)
+        if choice < 0.6:
+            return self.reference()
+        return self.literal()
+
+    def reference(self) -> Node:
+        names = ["@", "$"] + KEYS + [name for name, n in self.bound if n is None]
+        return Node("ref", self.rng.choice(names))
+
+    def literal(self) -> Node:
+        choice = self.rng.random()
+        if choice < 0.4:
+            return Node("literal", self.rng.choice(NUMBERS))
+        if choice < 0.8:
+            return Node("literal", json.dumps(self.rng.choice(STRINGS)))
+        return Node("literal", self.rng.choice(["true", "false", "null"]))
+
+    def key(self) -> str:
+        key = self.rng.choice(KEYS)
+        return json.dumps(key) if self.rng.random() < 0.3 else key
+
+    def fresh_name(self) -> str:
+        self.names += 1
+        return f"v{self.names}"
+
+
+class Worker:
+    """A runner process for one implementation, restarted if it hangs."""
+
+    def __init__(self, command: List[str], timeout: float):
+        self.command = command
+        self.timeout = timeout
+        self.start()
+
+    def start(self):
+        self.process = subprocess.Popen(
+            self.command,
+            stdin=subprocess.PIPE,
+            stdout=subprocess.PIPE,
+            stderr=subprocess.DEVNULL,
+            text=True,
+            encoding="utf-8",
+        )
+        self.lines: "queue.Queue[str]" = queue.Queue()
+        threading.Thread(
+            target=self.read, args=(self.process, self.lines), daemon=True
+        ).start()
+
+    @staticmethod
+    def read(process: subprocess.Popen, lines: "queue.Queue[str]"):
+        for line in process.stdout:
+            lines.put(line)
+
+    def run(self, query: str, data: Any) -> Outcome:
+        try:
+            request = json.dumps({"query": query, "data": data})
+            self.process.stdin.write(request + "\n")
+            self.process.stdin.flush()
+            return json.loads(self.lines.get(timeout=self.timeout))
+        except (queue.Empty, BrokenPipeError):
+            self.process.kill()
+            self.start()
+            return {"kind": "timeout"}
+
+
+def same(a: Any, b: Any) -> bool:
+    """JSON equality, where booleans are never equal to numbers."""
+    if isinstance(a, bool) or isinstance(b, bool):
+        return type(a) is type(b) and a == b
+    if isinstance(a, list) and isinstance(b, list):
+        return len(a) == len(b) and all(same(x, y) for x, y in zip(a, b))
+    if isinstance(a, dict) and isinstance(b, dict):
+        return a.keys() == b.keys() and all(same(a[k], b[k]) for k in a)
+    if isinstance(a, (int, float)) and isinstance(b, (int, float)):
+        return a == b
+    return type(a) is type(b) and a == b
+
+
+def disagreement(py: Outcome, js: Outcome) -> Optional[Tuple[str, str]]:
+    """The kinds of outcome the implementations disagree with, if they do."""
+    if "timeout" in (py["kind"], js["kind"]):
+        return None
+    if py["kind"] != js["kind"]:
+        return py["kind"], js["kind"]
+    if py["kind"] == "value" and not same(py.get("value"), js.get("value")):
+        return "value", "value"
+    return None
+
+
+def node_variants(node: Node) -> Iterator[Node]:
+    """Yields simpler versions of a query, each with one thing removed."""
+    if node.kind != "entry":
+        for child in node.children:
+            if child and child.kind != "entry":
+                yield child
+        if node.kind != "literal":
+            yield Node("literal", "null")
+    droppable = {"array": 0, "object": 0, "call": 1, "pipe": 1, "index": 1}
+    for i, child in enumerate(node.children):
+        if node.kind in droppable and i >= droppable[node.kind]:
+            yield Node(node.kind, node.text, node.children[:i] + node.children[i + 1 :])
+        for variant in node_variants(child) if child else ():
+            children = node.children[:i] + [variant] + node.children[i + 1 :]
+            yield Node(node.kind, node.text, children)
+
+
+def data_variants(data: Any) -> Iterator[Any]:
+    """Yields simpler versions of some data, each with one thing removed."""
+    if isinstance(data, dict):
+        yield from data.values()
+        for key in data:
+            yield {k: v for k, v in data.items() if k != key}
+        for key, value in data.items():
+            for variant in data_variants(value):
+                yield {**data, key: variant}
+    elif isinstance(data, list):
+        yield from data
+        for i in range(len(data)):
+            yield data[:i] + data[i + 1 :]
+        for i, value in enumerate(data):
+            for variant in data_variants(value):
+                yield data[:i] + [variant] + data[i + 1 :]
+    elif isinstance(data, str) and data:
+        yield data[:-1]
+    elif isinstance(data, float) and data != int(data):
+        yield int(data)
+    if data is not None:
+        yield None
+
+
+class Fuzzer:
+    def __init__(self, py: Worker, js: Worker, renderer: Renderer):
+        self.py = py
+        self.js = js
+        self.renderer = renderer
+
+    def compare(self, query: str, data: Any):
+        py = self.py.run(query, data)
+        js = self.js.run(query, data)
+        return disagreement(py, js), py, js
+
+    def minimize(self, node: Node, data: Any, kinds: Tuple[str, str]):
+        """Greedily applies simplifications that keep the same disagreement."""
+        size = (node.size(), len(json.dumps(data)))
+        improved = True
+        while improved:
+            improved = False
+            candidates = [(v, data) for v in node_variants(node)]
+            candidates += [(node, v) for v in data_variants(data)]
+            for candidate, candidate_data in candidates:
+                candidate_size = (candidate.size(), len(json.dumps(candidate_data)))
+                if candidate_size >= size:
+                    continue
+                query = self.renderer.render(candidate)
+                if self.compare(query, candidate_data)[0] == kinds:
+                    node, data, size = candidate, candidate_data, candidate_size
+                    improved = True
+                    break
+        return node, data
+
+
+def shared_case(query: str, data: Any, outcome: Outcome) -> Dict[str, Any]:
+    """The case for shared/testdata.json expecting the given outcome."""
+    assertion: Dict[str, Any] = {"query": query, "data": data}
+    if outcome["kind"] == "value":
+        assertion["expected"] = outcome.get("value")
+    else:
+        assertion["throws"] = "parse" if outcome["kind"] == "parse" else True
+    it = f"agrees across implementations on {query}"
+    return {"it": it, "assertions": [assertion]}
+
+
+def describe(outcome: Outcome) -> str:
+    if outcome["kind"] == "value":
+        return "returned " + json.dumps(outcome.get("value"), ensure_ascii=False)
+    return f"{outcome['kind']}: " + str(outcome.get("error")).split("\n")[0]
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.split("\n\n")[0].strip())
+    parser.add_argument("--runs", type=int, default=1000, help="Queries to try")
+    parser.add_argument("--seed", type=int, help="Seed, for reproducing a run")
+    parser.add_argument("--depth", type=int, default=3, help="Max query depth")
+    parser.add_argument(
+        "--expect",
+        choices=["js", "py"],
+        default="js",
+        help="Which implementation's behavior the printed cases expect",
+    )
+    parser.add_argument(
+        "--timeout", type=float, default=2.0, help="Seconds before a query hangs"
+    )
+    parser.add_argument(
+        "--max-failures", type=int, default=10, help="Stop after this many"
+    )
+    parser.add_argument("--js", help="Path to the built JS implementation")
+    args = parser.parse_args()
+
+    seed = args.seed if args.seed is not None else random.randrange(2**32)
+    print(f"Fuzzing with seed {seed}", file=sys.stderr)
+    rng = random.Random(seed)
+    with open(GRAMMAR) as f:
+        binary, unary = operator_levels(f.read())
+    generator = Generator(rng, binary, unary)
+    here = os.path.dirname(os.path.abspath(__file__))
+    py = Worker([sys.executable, os.path.join(here, "py_runner.py")], args.timeout)
+    js_command = ["node", os.path.join(here, "js_runner.js")]
+    js = Worker(js_command + ([args.js] if args.js else []), args.timeout)
+    fuzzer = Fuzzer(py, js, Renderer(binary))
+
+    seen = set()
+    for _ in range(args.runs):
+        node = generator.query(args.depth)
+        data = generator.data()
+        kinds = fuzzer.compare(fuzzer.renderer.render(node), data)[0]
+        if not kinds:
+            continue
+        node, data = fuzzer.minimize(node, data, kinds)
+        query = fuzzer.renderer.render(node)
+        if (query, kinds) in seen:
+            continue
+        seen.add((query, kinds))
+        _, py_outcome, js_outcome = fuzzer.compare(query, data)
+        expected = js_outcome if args.expect == "js" else py_outcome
+        print(f"py {describe(py_outcome)}")
+        print(f"js {describe(js_outcome)}")
+        case = shared_case(query, data, expected)
+        print(json.dumps(case, indent=2, ensure_ascii=False) + "\n", flush=True)
+        if len(seen) >= args.max_failures:
+            break
+
+    print(f"Found {len(seen)} disagreements", file=sys.stderr)
+    sys.exit(1 if seen else 0)
+
+
+if __name__ == "__main__":
+    main()
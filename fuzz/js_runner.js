@@ -0,0 +1,41 @@
+// Reads {"query", "data"} JSON lines from stdin, and writes the outcome of
+// running each through the JS implementation as a JSON line.
+const path = require("path");
+const readline = require("readline");
+
+const mistql = require(
+  path.resolve(process.argv[2] || path.join(__dirname, "..", "js", "dist", "umd", "index.js"))
+);
+
+// MistQL's own errors aren't exported, so anything the engine throws that
+// isn't a builtin JS error is taken to be one of them.
+const crashes = [TypeError, RangeError, ReferenceError, SyntaxError];
+
+const describe = (kind, err) =>
+  crashes.some((type) => err instanceof type)
+    ? { kind: "crash", error: String(err) }
+    : { kind, error: String(err && err.message).split("\n")[0] };
+
+const outcome = (query, data) => {
+  let compiled;
+  try {
+    compiled = mistql.compile(query);
+  } catch (err) {
+    return describe("parse", err);
+  }
+  try {
+    return { kind: "value", value: compiled.run(data) };
+  } catch (err) {
+    return describe("throws", err);
+  }
+};
+
+// The log builtin prints, so keep stdout for results only
+const write = process.stdout.write.bind(process.stdout);
+console.log = console.error;
+
+readline.createInterface({ input: process.stdin }).on("line", (line) => {
+  const request = JSON.parse(line);
+  const result = outcome(request.query, request.data);
+  write(JSON.stringify(result) + "\n");
+});
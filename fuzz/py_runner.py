@@ -0,0 +1,41 @@
+"""
+Reads {"query", "data"} JSON lines from stdin, and writes the outcome of
+running each through the Python implementation as a JSON line.
+"""
+
+import json
+import os
+import sys
+
+sys.path.insert(0, os.path.join(os.path.dirname(__file__), "..", "py"))
+
+from mistql import compile, MistQLException, MistQLParseError  # noqa: E402
+
+
+def outcome(query, data):
+    try:
+        compiled = compile(query)
+    except MistQLParseError as e:
+        return {"kind": "parse", "error": e.args[0]}
+    except Exception as e:
+        return {"kind": "crash", "error": repr(e)}
+    try:
+        return {"kind": "value", "value": compiled.run(data)}
+    except MistQLException as e:
+        return {"kind": "throws", "error": e.args[0]}
+    except Exception as e:
+        return {"kind": "crash", "error": repr(e)}
+
+
+def main():
+    # The log builtin prints, so keep stdout for results only
+    out = sys.stdout
+    sys.stdout = sys.stderr
+    for line in sys.stdin:
+        request = json.loads(line)
+        out.write(json.dumps(outcome(request["query"], request["data"])) + "\n")
+        out.flush()
+
+
+if __name__ == "__main__":
+    main()
@@ -1,10 +1,40 @@
 #!/usr/bin/env node
 const fs = require("fs");
+const readline = require("readline");
 const mistql = require("../dist/umd/index");
+const { version } = require("../package.json");
 
-function helpAndExit() {
-  console.log(`Usage: ${argv[0]} <query> [file]`);
-  process.exit(0);
+// Mirrors the flags of the python CLI, mqpy
+const options = {
+  data: { short: "-d", value: true },
+  file: { short: "-f", value: true },
+  file_jsonl: { short: "-fjl", value: true },
+  jsonl: { short: "-jl" },
+  slurp: { short: "-s" },
+  output: { short: "-o", value: true },
+  pretty: { short: "-p" },
+  compact: { short: "-c" },
+  version: { short: "-v" },
+  help: { short: "-h" },
+};
+
+const usage = `Usage: mq [options] <query> [file]
+
+Options:
+  -d, --data <data>           The data to run the query on
+  -f, --file <file>           The file to read the data from. Defaults to stdin
+  -fjl, --file_jsonl <file>   The json-lines file to read the data from. Implies --jsonl
+  -jl, --jsonl                Read the data as json-lines, and write one result per line
+  -s, --slurp                 Run the query once over an array of every json-lines item
+  -o, --output <file>         The output file. Defaults to stdout
+  -p, --pretty                Pretty print the output. The default, except for json-lines
+  -c, --compact               Print the output on a single line
+  -v, --version               Print the version of MistQL
+  -h, --help                  Print this message`;
+
+function usageErrorAndExit(msg) {
+  console.error(`${msg}\n\n${usage}`);
+  process.exit(2);
 }
 
 function errorAndExit(msg) {
@@ -12,58 +42,152 @@ function errorAndExit(msg) {
   process.exit(1);
 }
 
-let argv = process.argv.slice(1);
-const flags = [];
-
-const shorthands = {
-  h: "help",
-};
-
-{
-  const next = [];
+function parseArgs(argv) {
+  const args = {};
+  const positionals = [];
   for (let i = 0; i < argv.length; i++) {
     const curr = argv[i];
-    if (curr.startsWith("--")) {
-      flags.push(curr.substring(2));
-    } else if (curr.startsWith("-")) {
-      curr
-        .substring(1)
-        .split("")
-        .forEach((item) => {
-          const longflag = shorthands[item];
-          if (!longflag) {
-            errorAndExit("Unknown flag " + item);
-          }
-          flags.push(longflag);
-        });
+    if (curr === "--") {
+      positionals.push(...argv.slice(i + 1));
+      break;
+    }
+    if (!curr.startsWith("-") || curr === "-") {
+      positionals.push(curr);
+      continue;
+    }
+    const eq = curr.indexOf("=");
+    const flag = eq > -1 ? curr.substring(0, eq) : curr;
+    const name = Object.keys(options).find(
+      (key) => flag === "--" + key || flag === options[key].short
+    );
+    if (!name) {
+      usageErrorAndExit("Unknown flag " + flag);
+    }
+    if (options[name].value) {
+      const value = eq > -1 ? curr.substring(eq + 1) : argv[++i];
+      if (value === undefined) {
+        usageErrorAndExit(`Flag ${flag} requires a value`);
+      }
+      args[name] = value;
     } else {
-      next.push(curr);
+      if (eq > -1) {
+        usageErrorAndExit(`Flag ${flag} doesn't take a value`);
+      }
+      args[name] = true;
     }
   }
-  argv = next;
+  return { args, positionals };
 }
 
-for (let i = 0; i < flags.length; i++) {
-  if (flags[i] === "help") {
-    helpAndExit();
-  } else {
-    errorAndExit("Unknown flag " + flags[i]);
+function parseJSON(text) {
+  try {
+    return JSON.parse(text);
+  } catch (e) {
+    errorAndExit("Invalid JSON data: " + e.message);
   }
 }
 
-if (argv.length < 2) {
-  helpAndExit();
+function compileOrExit(query) {
+  try {
+    return mistql.compile(query);
+  } catch (e) {
+    errorAndExit(e.message);
+  }
 }
 
-if (argv.length > 3) {
-  helpAndExit();
+function runOrExit(compiled, data) {
+  try {
+    return compiled.run(data);
+  } catch (e) {
+    errorAndExit(e.message);
+  }
 }
 
-const [, query, file] = argv;
-const data = JSON.parse(fs.readFileSync(file || 0).toString());
+function jsonlLines(args, file) {
+  if (args.data !== undefined) {
+    return args.data.split("\n");
+  }
+  const input = file ? fs.createReadStream(file) : process.stdin;
+  return readline.createInterface({ input, crlfDelay: Infinity });
+}
 
-try {
-  console.log(JSON.stringify(mistql.query(query, data), null, 2));
-} catch (e) {
-  console.error(e.message);
+async function streamJsonl(compiled, args, file) {
+  const fd = args.output ? fs.openSync(args.output, "w") : undefined;
+  const writeLine = (line) =>
+    fd === undefined
+      ? process.stdout.write(line + "\n")
+      : fs.writeSync(fd, line + "\n");
+  for await (const line of jsonlLines(args, file)) {
+    if (line.trim()) {
+      writeLine(JSON.stringify(runOrExit(compiled, parseJSON(line))));
+    }
+  }
+  if (fd !== undefined) {
+    fs.closeSync(fd);
+  }
 }
+
+async function slurpJsonl(args, file) {
+  const items = [];
+  for await (const line of jsonlLines(args, file)) {
+    if (line.trim()) {
+      items.push(parseJSON(line));
+    }
+  }
+  return items;
+}
+
+async function main() {
+  const { args, positionals } = parseArgs(process.argv.slice(2));
+  if (args.help) {
+    console.log(usage);
+    return;
+  }
+  if (args.version) {
+    console.log(version);
+    return;
+  }
+  if (positionals.length < 1 || positionals.length > 2) {
+    usageErrorAndExit("Expected a query, and optionally a file");
+  }
+  const [query, positionalFile] = positionals;
+  const sources = [args.data, args.file, args.file_jsonl, positionalFile];
+  if (sources.filter((source) => source !== undefined).length > 1) {
+    usageErrorAndExit("Only one of --data, --file, --file_jsonl, or a file may be given");
+  }
+  if (args.pretty && args.compact) {
+    usageErrorAndExit("Only one of --pretty and --compact may be given");
+  }
+  const file = args.file || args.file_jsonl || positionalFile;
+  const jsonl = args.jsonl || args.file_jsonl !== undefined;
+  if (args.slurp && !jsonl) {
+    usageErrorAndExit("--slurp requires json-lines input, from --jsonl or --file_jsonl");
+  }
+
+  const compiled = compileOrExit(query);
+  if (jsonl && !args.slurp) {
+    if (args.pretty) {
+      usageErrorAndExit("--pretty can't be used with json-lines output");
+    }
+    await streamJsonl(compiled, args, file);
+    return;
+  }
+
+  let data;
+  if (jsonl) {
+    data = await slurpJsonl(args, file);
+  } else if (args.data !== undefined) {
+    data = parseJSON(args.data);
+  } else {
+    data = parseJSON(fs.readFileSync(file || 0).toString());
+  }
+  const result = runOrExit(compiled, data);
+  const out = JSON.stringify(result, null, args.compact ? undefined : 2);
+  if (args.output) {
+    fs.writeFileSync(args.output, out);
+  } else {
+    console.log(out);
+  }
+}
+
+main().catch((e) => errorAndExit(e.message));
@@ -0,0 +1,179 @@
+import assert from "assert";
+import { analyze } from "./analyze";
+import { MistQLInstance } from "./instance";
+import { parseOrThrow } from "./parser";
+
+const schema = {
+  type: "object",
+  properties: {
+    name: { type: "string" },
+    total: { type: "number" },
+    meta: { type: "object" },
+    items: {
+      type: "array",
+      items: {
+        type: "object",
+        properties: { price: { type: "number" } },
+        additionalProperties: false,
+      },
+    },
+  },
+  additionalProperties: false,
+};
+
+const messages = (query: string, schema?: unknown) =>
+  analyze(parseOrThrow(query), schema).map((err) => err.message);
+
+describe("analyze", () => {
+  it("accepts valid queries", () => {
+    [
+      "items | map price * 2 | sum",
+      "let n = count items in total / n",
+      "def double x = x * 2 in items | map (double price)",
+      'if (count items) name "none"',
+      "$.count items",
+      'datediff "day" name now',
+    ].forEach((query) => {
+      assert.deepStrictEqual(messages(query, schema), [], query);
+    });
+  });
+
+  it("assumes any JSON data without a schema", () => {
+    assert.deepStrictEqual(messages("@ | filter x > 1 | map x + 1 | count"), []);
+    assert.deepStrictEqual(messages("missing.field"), []);
+  });
+
+  it("reports unknown functions and references", () => {
+    assert.deepStrictEqual(messages("cuont @"), ["Unknown function cuont"]);
+    assert.deepStrictEqual(messages("items | map prise", schema), [
+      "Unknown reference prise",
+    ]);
+  });
+
+  it("follows enclosing contexts", () => {
+    assert.deepStrictEqual(messages('"a" | apply (@^ | count)'), [
+      "count: expected array, got string",
+    ]);
+    assert.deepStrictEqual(messages("[1] | map @^^^"), [
+      "Unknown reference @^^^",
+    ]);
+  });
+
+  it("never resolves the root to a field", () => {
+    const schema = {
+      type: "object",
+      properties: { $: { type: "number" } },
+      required: ["$"],
+    };
+    assert.deepStrictEqual(messages("count $.@", schema), [
+      "count: expected array, got object",
+    ]);
+    assert.deepStrictEqual(messages('$.@.`$` + "a"', schema), [
+      "add: cannot add number and string",
+    ]);
+  });
+
+  it("reports wrong arities", () => {
+    assert.deepStrictEqual(messages("count 1 2"), [
+      "Expected 1 arguments, got 2",
+    ]);
+    assert.deepStrictEqual(messages("@ | map"), ["Expected 2 arguments, got 1"]);
+    assert.deepStrictEqual(messages("def add a b = a + b in add 1"), [
+      "Expected 2 arguments, got 1",
+    ]);
+    assert.deepStrictEqual(messages("cond true 1 false 2"), [
+      "cond: Expected pairs of conditions and results, followed by a default",
+    ]);
+  });
+
+  it("infers types from the schema", () => {
+    assert.deepStrictEqual(messages("count meta", schema), [
+      "count: expected array, got object",
+    ]);
+    assert.deepStrictEqual(messages("name + total", schema), [
+      "add: cannot add string and number",
+    ]);
+    assert.deepStrictEqual(messages('items | map price + "$"', schema), [
+      "add: cannot add number and string",
+    ]);
+    assert.deepStrictEqual(messages("total.value", schema), [
+      "Cannot access value on number",
+    ]);
+  });
+
+  it("infers types from literals and builtins", () => {
+    assert.deepStrictEqual(messages('1 < "a"'), [
+      "Cannot compare number and string",
+    ]);
+    assert.deepStrictEqual(messages("keys @ | sum | count"), [
+      "count: expected array, got number",
+    ]);
+    assert.deepStrictEqual(messages('formatdate "%Y" true'), [
+      "formatdate: expected number or string, got boolean",
+    ]);
+    assert.deepStrictEqual(messages("length [1]"), [
+      "length: expected string, got array",
+    ]);
+    assert.deepStrictEqual(messages('max "a" 1'), [
+      "max: expected number, got string",
+    ]);
+    assert.deepStrictEqual(messages("[1] | first | count"), [
+      "count: expected array, got null or number",
+    ]);
+    assert.deepStrictEqual(messages("merge {a: 1} [1]"), [
+      "merge: expected object, got array",
+    ]);
+    assert.deepStrictEqual(messages('[{a: "x"}] | sumby a'), [
+      "sumby: expected number, got string",
+    ]);
+    assert.deepStrictEqual(messages('(1?.a ?? "b") + 1'), [
+      "add: cannot add string and number",
+    ]);
+    assert.deepStrictEqual(messages('cond true 1 false "a" null | count'), [
+      "count: expected array, got null or number or string",
+    ]);
+    assert.deepStrictEqual(messages("{...[1]}"), [
+      "Cannot spread array into an object",
+    ]);
+    assert.deepStrictEqual(messages('{...{a: 1}, b: 2} | apply (a + "x")'), [
+      "add: cannot add number and string",
+    ]);
+  });
+
+  it("follows local refs and unions", () => {
+    const schema = {
+      $defs: { price: { type: "number" } },
+      type: "object",
+      properties: {
+        price: { $ref: "#/$defs/price" },
+        tag: { anyOf: [{ type: "string" }, { type: "null" }] },
+      },
+    };
+    assert.deepStrictEqual(messages('price + "a"', schema), [
+      "add: cannot add number and string",
+    ]);
+    assert.deepStrictEqual(messages("tag + 1", schema), [
+      "add: cannot add null or string and number",
+    ]);
+  });
+
+  it("only lets required properties shadow bindings", () => {
+    const items = {
+      type: "object",
+      properties: { x: { type: "string" } },
+    };
+    const query = "let x = 5 in @ | map x + 1";
+    // Items without an x fall back to the binding
+    assert.deepStrictEqual(messages(query, { type: "array", items }), []);
+    const required = { ...items, required: ["x"] };
+    assert.deepStrictEqual(messages(query, { type: "array", items: required }), [
+      "add: cannot add string and number",
+    ]);
+  });
+
+  it("points errors into the query", () => {
+    const [err] = new MistQLInstance().compile("1 + (count {})").analyze();
+    assert.strictEqual(err.span.start, 11);
+    assert.ok(err.message.includes("^^"));
+  });
+});
@@ -0,0 +1,956 @@
+// Static analysis of queries, for finding errors without running them.
+//
+// The analyzer walks the AST with a model of the stack, inferring what it can
+// about the type of each expression. It only reports errors that are certain
+// to happen when the offending expression is evaluated, so a query without
+// errors may still fail at runtime.
+
+import builtins from "./builtins";
+import { RuntimeError } from "./errors";
+import { getType } from "./runtimeValues";
+import {
+  ASTApplicationExpression,
+  ASTExpression,
+  ASTLiteralExpression,
+  ASTSpreadExpression,
+  FunctionClosure,
+  RuntimeValue,
+  RuntimeValueType,
+  Span,
+} from "./types";
+import { getArity } from "./util";
+
+type Kind = RuntimeValueType;
+
+// The argument counts a function accepts, and the builtin it is, if any.
+type FunctionInfo = {
+  name: string;
+  arity?: number[];
+  builtin?: boolean;
+};
+
+// What's known about the values an expression might evaluate to.
+//
+// Kinds are the runtime types the value might have, or undefined if it could
+// be anything. Objects track the types of their known properties, which of
+// them are certain to be present (all of them, unless given), and extra is
+// the type of any other property, with NEVER meaning there are none.
+// Arrays track the type of their items. Undefined stands in for ANY in both.
+export class Type {
+  kinds?: Set<Kind>;
+  properties: { [key: string]: Type };
+  required: Set<string>;
+  extra?: Type;
+  items?: Type;
+  fn?: FunctionInfo;
+
+  constructor(
+    kinds?: Kind[] | Set<Kind>,
+    properties: { [key: string]: Type } = {},
+    extra?: Type,
+    items?: Type,
+    fn?: FunctionInfo,
+    required?: string[] | Set<string>
+  ) {
+    this.kinds = kinds === undefined ? undefined : new Set(kinds);
+    this.properties = properties;
+    this.required = new Set(required ?? Object.keys(properties));
+    this.extra = extra;
+    this.items = items;
+    this.fn = fn;
+  }
+
+  canBe(...kinds: Kind[]) {
+    return this.kinds === undefined || kinds.some((kind) => this.kinds.has(kind));
+  }
+
+  // Whether the value definitely exists and has none of these kinds
+  cannotBe(kinds: Kind[]) {
+    return this.kinds !== undefined && this.kinds.size > 0 && !this.canBe(...kinds);
+  }
+
+  // The type of a property, or undefined if it's definitely missing
+  field(name: string): Type | undefined {
+    if (!this.canBe("object")) {
+      return undefined;
+    }
+    if (this.properties.hasOwnProperty(name)) {
+      return this.properties[name];
+    }
+    const extra = this.extra ?? ANY;
+    return extra.kinds?.size === 0 ? undefined : extra;
+  }
+
+  // Whether the property is definitely present
+  hasField(name: string) {
+    return (
+      this.kinds?.size === 1 &&
+      this.kinds.has("object") &&
+      this.required.has(name)
+    );
+  }
+
+  item(): Type {
+    return this.items ?? ANY;
+  }
+
+  toString() {
+    if (this.kinds === undefined) {
+      return "any";
+    }
+    return Array.from(this.kinds).sort().join(" or ");
+  }
+}
+
+const JSON_KINDS: Kind[] = [
+  "null",
+  "boolean",
+  "number",
+  "string",
+  "object",
+  "array",
+];
+
+const ANY = new Type();
+const NEVER = new Type([]);
+const NULL = new Type(["null"]);
+const BOOLEAN = new Type(["boolean"]);
+const NUMBER = new Type(["number"]);
+const STRING = new Type(["string"]);
+const REGEX = new Type(["regex"]);
+// Any value that can come from JSON, which rules out functions and regexes
+const DATA = new Type(JSON_KINDS);
+DATA.extra = DATA;
+DATA.items = DATA;
+
+const arrayOf = (items: Type) => new Type(["array"], {}, undefined, items);
+
+const objectOf = (
+  properties: { [key: string]: Type },
+  extra: Type = NEVER,
+  required?: string[] | Set<string>
+) => new Type(["object"], properties, extra, undefined, undefined, required);
+
+const functionOf = (fn: FunctionInfo) =>
+  new Type(["function"], {}, undefined, undefined, fn);
+
+export const union = (...types: Type[]): Type => {
+  const distinct = types.filter((t, i) => types.indexOf(t) === i);
+  if (distinct.length === 0) {
+    return NEVER;
+  }
+  if (distinct.length === 1) {
+    return distinct[0];
+  }
+  if (distinct.some((t) => t.kinds === undefined)) {
+    return ANY;
+  }
+  const kinds = new Set<Kind>();
+  distinct.forEach((t) => t.kinds.forEach((kind) => kinds.add(kind)));
+  const arrays = distinct.filter((t) => t.canBe("array")).map((t) => t.item());
+  const objects = distinct.filter((t) => t.canBe("object"));
+  const properties: { [key: string]: Type } = {};
+  objects.forEach((t) =>
+    Object.keys(t.properties).forEach((name) => {
+      properties[name] = union(...objects.map((o) => o.field(name) ?? NULL));
+    })
+  );
+  const fns = distinct
+    .filter((t) => t.canBe("function"))
+    .map((t) => t.fn)
+    .filter((fn, i, all) => all.indexOf(fn) === i);
+  return new Type(
+    kinds,
+    properties,
+    objects.length ? union(...objects.map((t) => t.extra ?? ANY)) : undefined,
+    arrays.length ? union(...arrays) : undefined,
+    fns.length === 1 ? fns[0] : undefined,
+    Object.keys(properties).filter((name) =>
+      objects.every((t) => t.required.has(name))
+    )
+  );
+};
+
+const fromValue = (value: RuntimeValue): Type => {
+  const type = getType(value);
+  if (type === "array") {
+    return arrayOf(union(...value.map(fromValue)));
+  }
+  if (type === "object") {
+    const properties = {};
+    Object.keys(value).forEach((key) => {
+      properties[key] = fromValue(value[key]);
+    });
+    return objectOf(properties);
+  }
+  return new Type([type]);
+};
+
+const schemaKinds: { [key: string]: Kind } = {
+  null: "null",
+  boolean: "boolean",
+  number: "number",
+  integer: "number",
+  string: "string",
+  object: "object",
+  array: "array",
+};
+
+const isObject = (value: unknown): value is { [key: string]: any } =>
+  typeof value === "object" && value !== null && !Array.isArray(value);
+
+// Converts a JSON Schema into the type of the values it accepts. Only local
+// $refs are followed, and anything not understood is treated as any JSON
+// value.
+export const fromSchema = (
+  schema: unknown,
+  root: unknown = schema,
+  seen: string[] = []
+): Type => {
+  if (!isObject(schema)) {
+    return schema === false ? NEVER : DATA;
+  }
+  if ("$ref" in schema) {
+    const ref = schema.$ref;
+    if (typeof ref !== "string" || ref[0] !== "#" || seen.indexOf(ref) > -1) {
+      return DATA;
+    }
+    let target: any = root;
+    ref
+      .substring(1)
+      .split("/")
+      .slice(1)
+      .forEach((part) => {
+        const key = part.replace(/~1/g, "/").replace(/~0/g, "~");
+        target =
+          typeof target === "object" && target !== null ? target[key] : undefined;
+      });
+    return fromSchema(target, root, seen.concat(ref));
+  }
+  for (const key of ["anyOf", "oneOf"]) {
+    if (Array.isArray(schema[key])) {
+      return union(...schema[key].map((sub) => fromSchema(sub, root, seen)));
+    }
+  }
+  if ("const" in schema) {
+    return fromValue(schema.const);
+  }
+  if (Array.isArray(schema.enum)) {
+    return union(...schema.enum.map(fromValue));
+  }
+
+  let kinds = JSON_KINDS;
+  const schemaType =
+    typeof schema.type === "string" ? [schema.type] : schema.type;
+  if (Array.isArray(schemaType)) {
+    kinds = schemaType
+      .filter((t) => schemaKinds.hasOwnProperty(t))
+      .map((t) => schemaKinds[t]);
+  } else if ("properties" in schema) {
+    kinds = ["object"];
+  } else if ("items" in schema) {
+    kinds = ["array"];
+  }
+
+  const properties: { [key: string]: Type } = {};
+  if (isObject(schema.properties)) {
+    Object.keys(schema.properties).forEach((name) => {
+      properties[name] = fromSchema(schema.properties[name], root, seen);
+    });
+  }
+  const extra = fromSchema(schema.additionalProperties ?? true, root, seen);
+  const items = schema.items ?? true;
+  const itemsType = Array.isArray(items)
+    ? union(...items.map((sub) => fromSchema(sub, root, seen)))
+    : fromSchema(items, root, seen);
+  const required = Array.isArray(schema.required)
+    ? schema.required.filter((name) => properties.hasOwnProperty(name))
+    : [];
+  return new Type(kinds, properties, extra, itemsType, undefined, required);
+};
+
+// A frame is either bindings by name, or a value whose fields are bindings
+type Frame = { [name: string]: Type } | Type;
+type Scope = Frame[];
+
+// The type of a reference, or undefined if it definitely can't be found.
+// When looking up a function to call, fields that can't hold functions are
+// skipped, as calling them would fail regardless.
+// Quoted references are always fields, even when named like a context.
+const lookup = (
+  name: string,
+  scope: Scope,
+  callee = false,
+  quoted = false
+): Type | undefined => {
+  if (name.startsWith("@^") && !quoted) {
+    // The value some number of contexts out, as in findEnclosingContext
+    const contexts = scope.filter((frame): frame is Type => frame instanceof Type);
+    const depth = name.length - 1;
+    return contexts[contexts.length - 1 - depth];
+  }
+  for (let i = scope.length - 1; i >= 0; i--) {
+    const frame = scope[i];
+    if (!(frame instanceof Type)) {
+      if (frame.hasOwnProperty(name)) {
+        return frame[name];
+      }
+      continue;
+    }
+    if (name === "@" && !quoted) {
+      return frame;
+    }
+    if (name === "$") {
+      // Fields can't shadow the root reference, as in ValueFrame
+      continue;
+    }
+    const field = frame.field(name);
+    if (field === undefined || (callee && !field.canBe("function"))) {
+      continue;
+    }
+    if (frame.hasField(name)) {
+      return field;
+    }
+    const below = lookup(name, scope.slice(0, i), callee, quoted);
+    return below === undefined ? field : union(field, below);
+  }
+  return undefined;
+};
+
+// The arguments of a call to a builtin, inferred as the builtin's signature
+// asks for them, so that each is evaluated in the right scope. The piped
+// value, if any, is the final argument.
+class Call {
+  types: Type[] = [];
+
+  constructor(
+    readonly analyzer: Analyzer,
+    readonly node: ASTExpression,
+    readonly name: string,
+    readonly args: ASTExpression[],
+    readonly scope: Scope,
+    readonly piped?: Type,
+    readonly pipedSpan?: Span
+  ) {}
+
+  get length() {
+    return this.args.length + (this.piped === undefined ? 0 : 1);
+  }
+
+  span(i: number) {
+    return i < this.args.length ? this.args[i].span : this.pipedSpan;
+  }
+
+  // Infers an argument evaluated in the caller's scope
+  arg(i: number, expected?: Kind[]) {
+    return this.argIn(i, undefined, expected);
+  }
+
+  // Infers an argument evaluated with the item pushed onto the stack
+  argIn(i: number, item: Type | undefined, expected?: Kind[]) {
+    if (i < 0) {
+      i += this.length;
+    }
+    if (this.types[i] === undefined) {
+      this.types[i] =
+        i < this.args.length
+          ? this.analyzer.infer(
+              this.args[i],
+              item === undefined ? this.scope : this.scope.concat(item)
+            )
+          : this.piped ?? ANY;
+    }
+    const result = this.types[i];
+    if (expected !== undefined && result.cannotBe(expected)) {
+      this.analyzer.report(
+        `${this.name}: expected ${new Type(expected)}, got ${result}`,
+        this.span(i)
+      );
+    }
+    return result;
+  }
+
+  // Infers the arguments the signature didn't ask for
+  rest() {
+    for (let i = 0; i < this.length; i++) {
+      this.arg(i);
+    }
+  }
+}
+
+type Signature = (call: Call) => Type;
+
+const ARRAY: Kind[] = ["array"];
+const OBJECT: Kind[] = ["object"];
+const NUMBERS: Kind[] = ["number"];
+const STRINGS: Kind[] = ["string"];
+const PATTERNS: Kind[] = ["string", "regex"];
+const DATES: Kind[] = ["string", "number"];
+const ADDABLE: Kind[] = ["number", "string", "array"];
+const COMPARABLE: Kind[] = ["boolean", "number", "string"];
+
+// A builtin that evaluates its arguments in the caller's scope
+const simple =
+  (params: (Kind[] | undefined)[], result: Type = ANY): Signature =>
+  (call) => {
+    for (let i = 0; i < Math.min(call.length, params.length); i++) {
+      call.arg(i, params[i]);
+    }
+    return result;
+  };
+
+const numericOperator = simple([NUMBERS, NUMBERS], NUMBER);
+
+const values = (target: Type) =>
+  union(
+    ...Object.keys(target.properties).map((key) => target.properties[key]),
+    target.extra ?? ANY
+  );
+
+const compare: Signature = (call) => {
+  const left = call.arg(0);
+  const right = call.arg(1);
+  const incomparable = [left, right].find((t) => t.cannotBe(COMPARABLE));
+  if (incomparable) {
+    call.analyzer.report(
+      `Cannot compare values of type ${incomparable}`,
+      call.node.span
+    );
+  } else if (
+    left.kinds?.size &&
+    right.kinds?.size &&
+    !left.canBe(...Array.from(right.kinds))
+  ) {
+    call.analyzer.report(`Cannot compare ${left} and ${right}`, call.node.span);
+  }
+  return BOOLEAN;
+};
+
+const iterate =
+  (result: (item: Type, body: Type) => Type): Signature =>
+  (call) => {
+    const item = call.arg(1, ARRAY).item();
+    return result(item, call.argIn(0, item));
+  };
+
+const pad: Signature = (call) => {
+  call.arg(0, NUMBERS);
+  if (call.length === 3) {
+    call.arg(1, STRINGS);
+  }
+  call.arg(-1, STRINGS);
+  return STRING;
+};
+
+const mergeObjects: Signature = (call) => {
+  for (let i = 0; i < call.length; i++) {
+    call.arg(i, OBJECT);
+  }
+  return objectOf({}, ANY);
+};
+
+const extremumBy: Signature = (call) => {
+  const item = call.arg(1, ARRAY).item();
+  call.argIn(0, item, COMPARABLE);
+  return union(item, NULL);
+};
+
+const slice: Signature = (call) => {
+  call.arg(0, NUMBERS);
+  return arrayOf(call.arg(1, ARRAY).item());
+};
+
+const access =
+  (lenient: boolean): Signature =>
+  (call) => {
+    const target = call.arg(0);
+    const member = call.args[1];
+    if (member?.type !== "reference") {
+      return ANY;
+    }
+    // The member is a name rather than an expression to evaluate
+    call.types[1] = ANY;
+    if (target.cannotBe(["object", "null"])) {
+      if (lenient) {
+        return NULL;
+      }
+      call.analyzer.report(
+        `Cannot access ${member.ref} on ${target}`,
+        call.node.span
+      );
+      return ANY;
+    }
+    const field = target.field(member.ref) ?? NULL;
+    return target.hasField(member.ref) ? field : union(field, NULL);
+  };
+
+// The right is only used in place of a null
+const coalesce: Signature = (call) => {
+  const left = call.arg(0);
+  if (left.kinds === undefined || !left.kinds.has("null")) {
+    return union(left, call.arg(1));
+  }
+  const kinds = Array.from(left.kinds).filter((kind) => kind !== "null");
+  const present = new Type(
+    kinds,
+    left.properties,
+    left.extra,
+    left.items,
+    undefined,
+    left.required
+  );
+  return union(present, call.arg(1));
+};
+
+const quantifier: Signature = (call) => {
+  const target = call.arg(-1, ARRAY);
+  if (call.length === 2) {
+    call.argIn(0, target.item());
+  }
+  return BOOLEAN;
+};
+
+const extremum: Signature = (call) => {
+  if (call.length === 2) {
+    call.arg(0, NUMBERS);
+    call.arg(1, NUMBERS);
+  } else {
+    call.arg(0, ARRAY);
+  }
+  return union(NUMBER, NULL);
+};
+
+const signatures: { [name: string]: Signature } = {
+  count: simple([ARRAY], NUMBER),
+  sum: simple([ARRAY], NUMBER),
+  summarize: simple(
+    [ARRAY],
+    objectOf({
+      max: NUMBER,
+      min: NUMBER,
+      mean: NUMBER,
+      median: NUMBER,
+      variance: NUMBER,
+      stddev: NUMBER,
+    })
+  ),
+  fromentries: simple([ARRAY], objectOf({}, ANY)),
+  merge: mergeObjects,
+  deepmerge: mergeObjects,
+  pick: simple([ARRAY, OBJECT], objectOf({}, ANY)),
+  omit: simple([ARRAY, OBJECT], objectOf({}, ANY)),
+  haskey: simple([STRINGS, OBJECT], BOOLEAN),
+  percentile: simple([NUMBERS, ARRAY], union(NUMBER, NULL)),
+  getpath: simple([ARRAY, undefined]),
+  setpath: simple([ARRAY, undefined, undefined]),
+  string: simple([undefined], STRING),
+  float: simple([undefined], NUMBER),
+  keys: simple([undefined], arrayOf(STRING)),
+  values: simple([undefined], arrayOf(ANY)),
+  entries: simple([undefined], arrayOf(arrayOf(ANY))),
+  "!/unary": simple([undefined], BOOLEAN),
+  "-/unary": simple([NUMBERS], NUMBER),
+  "-": numericOperator,
+  "*": numericOperator,
+  "/": numericOperator,
+  "%": numericOperator,
+  "==": simple([undefined, undefined], BOOLEAN),
+  "!=": simple([undefined, undefined], BOOLEAN),
+  regex: simple([STRINGS, STRINGS], REGEX),
+  match: simple([PATTERNS, STRINGS], BOOLEAN),
+  "=~": simple([STRINGS, PATTERNS], BOOLEAN),
+  replace: simple([PATTERNS, STRINGS, STRINGS], STRING),
+  split: simple([PATTERNS, STRINGS], arrayOf(STRING)),
+  stringjoin: simple([STRINGS, ARRAY], STRING),
+  join: simple([STRINGS, ARRAY], STRING),
+  range: simple([NUMBERS, NUMBERS, NUMBERS], arrayOf(NUMBER)),
+  lower: simple([STRINGS], STRING),
+  upper: simple([STRINGS], STRING),
+  trim: simple([STRINGS], STRING),
+  startswith: simple([STRINGS, STRINGS], BOOLEAN),
+  endswith: simple([STRINGS, STRINGS], BOOLEAN),
+  contains: simple([STRINGS, STRINGS], BOOLEAN),
+  padstart: pad,
+  padend: pad,
+  substring: (call) => {
+    call.arg(0, NUMBERS);
+    if (call.length === 3) {
+      call.arg(1, NUMBERS);
+    }
+    call.arg(-1, STRINGS);
+    return STRING;
+  },
+  length: simple([STRINGS], NUMBER),
+  format: simple([STRINGS, ["array", "object"]], STRING),
+  abs: simple([NUMBERS], NUMBER),
+  floor: simple([NUMBERS], NUMBER),
+  ceil: simple([NUMBERS], NUMBER),
+  round: simple([NUMBERS, NUMBERS], NUMBER),
+  min: extremum,
+  max: extremum,
+  clamp: simple([NUMBERS, NUMBERS, NUMBERS], NUMBER),
+  // Results that aren't real numbers or are infinite come out as null
+  pow: simple([NUMBERS, NUMBERS], union(NUMBER, NULL)),
+  sqrt: simple([NUMBERS], union(NUMBER, NULL)),
+  ln: simple([NUMBERS], union(NUMBER, NULL)),
+  intdiv: simple([NUMBERS, NUMBERS], union(NUMBER, NULL)),
+  parsedate: simple([DATES], NUMBER),
+  formatdate: (call) => {
+    if (call.length === 2) {
+      call.arg(0, STRINGS);
+    }
+    call.arg(-1, DATES);
+    return STRING;
+  },
+  datediff: simple([STRINGS, DATES, DATES], NUMBER),
+  dateadd: simple([STRINGS, NUMBERS, DATES], STRING),
+  truncdate: simple([STRINGS, DATES], STRING),
+  log: (call) => call.arg(0),
+  reverse: (call) => arrayOf(call.arg(0, ARRAY).item()),
+  sort: (call) => arrayOf(call.arg(0, ARRAY).item()),
+  unique: (call) => arrayOf(call.arg(0, ARRAY).item()),
+  zip: (call) =>
+    arrayOf(
+      arrayOf(
+        union(
+          ...Array.from({ length: call.length }, (_, i) =>
+            call.arg(i, ARRAY).item()
+          )
+        )
+      )
+    ),
+  chunk: (call) => {
+    call.arg(0, NUMBERS);
+    return arrayOf(call.arg(1, ARRAY));
+  },
+  take: slice,
+  drop: slice,
+  first: (call) => union(call.arg(0, ARRAY).item(), NULL),
+  last: (call) => union(call.arg(0, ARRAY).item(), NULL),
+  any: quantifier,
+  all: quantifier,
+  flatten: (call) => arrayOf(call.arg(0, ARRAY).item().item()),
+  withindices: (call) =>
+    arrayOf(arrayOf(union(NUMBER, call.arg(0, ARRAY).item()))),
+  if: (call) => {
+    call.arg(0);
+    return union(call.arg(1), call.arg(2));
+  },
+  cond: (call) => {
+    if (call.length % 2 === 0) {
+      call.analyzer.report(
+        "cond: Expected pairs of conditions and results, followed by a default",
+        call.node.span
+      );
+    }
+    const results: Type[] = [];
+    for (let i = 0; i < call.length - 1; i += 2) {
+      call.arg(i);
+      results.push(call.arg(i + 1));
+    }
+    return union(...results, call.arg(call.length - 1));
+  },
+  "&&": (call) => union(call.arg(0), call.arg(1)),
+  "||": (call) => union(call.arg(0), call.arg(1)),
+  "??": coalesce,
+  "+": (call) => {
+    const left = call.arg(0);
+    const right = call.arg(1);
+    if (left.kinds === undefined || right.kinds === undefined) {
+      return ANY;
+    }
+    const kinds = ADDABLE.filter(
+      (kind) => left.kinds.has(kind) && right.kinds.has(kind)
+    );
+    if (kinds.length === 0 && left.kinds.size && right.kinds.size) {
+      call.analyzer.report(
+        `add: cannot add ${left} and ${right}`,
+        call.node.span
+      );
+    }
+    if (kinds.length === 1 && kinds[0] === "array") {
+      return arrayOf(union(left.item(), right.item()));
+    }
+    return new Type(kinds);
+  },
+  "<": compare,
+  "<=": compare,
+  ">": compare,
+  ">=": compare,
+  ".": access(false),
+  "?.": access(true),
+  index: (call) => {
+    for (let i = 0; i < call.length - 1; i++) {
+      call.arg(i);
+    }
+    const target = call.arg(-1);
+    if (call.length === 3) {
+      return target;
+    }
+    if (target.kinds?.size === 1 && target.kinds.has("array")) {
+      return union(target.item(), NULL);
+    }
+    return ANY;
+  },
+  map: iterate((_, body) => arrayOf(body)),
+  filter: iterate((item) => arrayOf(item)),
+  sortby: iterate((item) => arrayOf(item)),
+  uniqueby: iterate((item) => arrayOf(item)),
+  find: iterate((item) => union(item, NULL)),
+  groupby: iterate((item) => objectOf({}, arrayOf(item))),
+  countby: iterate(() => objectOf({}, NUMBER)),
+  sumby: (call) => {
+    call.argIn(0, call.arg(1, ARRAY).item(), NUMBERS);
+    return NUMBER;
+  },
+  avgby: (call) => {
+    call.argIn(0, call.arg(1, ARRAY).item(), NUMBERS);
+    return union(NUMBER, NULL);
+  },
+  minby: extremumBy,
+  maxby: extremumBy,
+  sequence: (call) => {
+    const item = call.arg(-1, ARRAY).item();
+    for (let i = 0; i < call.length - 1; i++) {
+      call.argIn(i, item);
+    }
+    return arrayOf(arrayOf(item));
+  },
+  reduce: (call) => {
+    call.arg(1);
+    call.arg(2, ARRAY);
+    call.argIn(0, arrayOf(ANY));
+    return ANY;
+  },
+  apply: (call) => call.argIn(0, call.arg(1)),
+  mapvalues: (call) => {
+    const target = call.arg(1, OBJECT);
+    const result = call.argIn(0, values(target));
+    const properties = {};
+    Object.keys(target.properties).forEach((key) => {
+      properties[key] = result;
+    });
+    return objectOf(properties, result, target.required);
+  },
+  filtervalues: (call) => {
+    const target = call.arg(1, OBJECT);
+    call.argIn(0, values(target));
+    return objectOf({}, values(target));
+  },
+  mapkeys: (call) => {
+    const target = call.arg(1, OBJECT);
+    call.argIn(0, STRING);
+    return objectOf({}, values(target));
+  },
+  filterkeys: (call) => {
+    const target = call.arg(1, OBJECT);
+    call.argIn(0, STRING);
+    return objectOf({}, values(target));
+  },
+};
+
+class Analyzer {
+  errors: RuntimeError[] = [];
+
+  constructor(readonly builtinsFrame: { [name: string]: Type }) {}
+
+  report(message: string, span: Span | undefined) {
+    const error = new RuntimeError(message);
+    error.span = span;
+    this.errors.push(error);
+  }
+
+  infer(node: ASTExpression, scope: Scope): Type {
+    switch (node.type) {
+      case "parenthetical":
+        return this.infer(node.expression, scope);
+      case "literal":
+        return this.inferLiteral(node, scope);
+      case "reference": {
+        const found = lookup(
+          node.ref,
+          node.internal ? [this.builtinsFrame] : scope,
+          false,
+          node.quoted
+        );
+        if (found === undefined) {
+          this.report(`Unknown reference ${node.ref}`, node.span);
+          return ANY;
+        }
+        return found;
+      }
+      case "application":
+        return this.inferCall(node, node.arguments, scope);
+      case "pipeline": {
+        let data = this.infer(node.stages[0], scope);
+        let previous = node.stages[0];
+        node.stages.slice(1).forEach((stage) => {
+          const args = stage.type === "application" ? stage.arguments : [];
+          data = this.inferCall(
+            stage,
+            args,
+            scope.concat(data),
+            data,
+            previous.span
+          );
+          previous = stage;
+        });
+        return data;
+      }
+      case "let": {
+        const value = this.infer(node.value, scope);
+        return this.infer(node.body, scope.concat({ [node.name]: value }));
+      }
+      case "lambda": {
+        const fn = functionOf({ name: node.name, arity: [node.params.length] });
+        const bindings = { [node.name]: fn };
+        node.params.forEach((param) => {
+          bindings[param] = ANY;
+        });
+        this.infer(node.body, scope.concat(bindings));
+        return fn;
+      }
+    }
+    return ANY;
+  }
+
+  inferSpread(spread: ASTSpreadExpression, scope: Scope, into: Kind): Type {
+    const type = this.infer(spread.value, scope);
+    if (type.cannotBe([into])) {
+      this.report(`Cannot spread ${type} into an ${into}`, spread.span);
+    }
+    return type;
+  }
+
+  inferLiteral(node: ASTLiteralExpression, scope: Scope): Type {
+    if (node.valueType === "array") {
+      const items = node.value.map((item) =>
+        item.type === "spread"
+          ? this.inferSpread(item, scope, "array").item()
+          : this.infer(item, scope)
+      );
+      return arrayOf(union(...items));
+    }
+    if (node.valueType === "object") {
+      let properties: { [key: string]: Type } = {};
+      let required = new Set<string>();
+      let extra = NEVER;
+      node.value.forEach((entry) => {
+        if ("key" in entry) {
+          if (typeof entry.key === "string") {
+            properties[entry.key] = this.infer(entry.value, scope);
+            required.add(entry.key);
+            return;
+          }
+          this.infer(entry.key, scope);
+          properties = {};
+          required = new Set();
+          extra = ANY;
+          this.infer(entry.value, scope);
+          return;
+        }
+        const spread = this.inferSpread(entry, scope, "object");
+        // Any earlier property might have been overridden
+        properties = {};
+        required = new Set();
+        extra = ANY;
+        if (spread.kinds?.size === 1 && spread.kinds.has("object")) {
+          Object.keys(spread.properties).forEach((key) => {
+            properties[key] = spread.properties[key];
+          });
+          required = new Set(spread.required);
+        }
+      });
+      return objectOf(properties, extra, required);
+    }
+    return new Type([node.valueType]);
+  }
+
+  inferCallee(fn: ASTExpression, scope: Scope): Type {
+    if (fn.type !== "reference") {
+      const callee = this.infer(fn, scope);
+      if (callee.cannotBe(["function"])) {
+        this.report(`Cannot call ${callee}`, fn.span);
+        return NEVER;
+      }
+      return callee;
+    }
+    const callee = lookup(
+      fn.ref,
+      fn.internal ? [this.builtinsFrame] : scope,
+      true,
+      fn.quoted
+    );
+    if (callee === undefined) {
+      this.report(`Unknown function ${fn.ref}`, fn.span);
+      return NEVER;
+    }
+    return callee;
+  }
+
+  inferCall(
+    node: ASTExpression,
+    args: ASTExpression[],
+    scope: Scope,
+    piped?: Type,
+    pipedSpan?: Span
+  ): Type {
+    const fn = node.type === "application" ? node.function : node;
+    const name = fn.type === "reference" ? fn.ref : "function";
+    const call = new Call(this, node, name, args, scope, piped, pipedSpan);
+    const info = this.inferCallee(fn, scope).fn;
+    if (info === undefined) {
+      call.rest();
+      return ANY;
+    }
+    if (info.arity !== undefined && info.arity.indexOf(call.length) === -1) {
+      this.report(
+        `Expected ${info.arity} arguments, got ${call.length}`,
+        node.span
+      );
+    } else if (info.builtin && signatures.hasOwnProperty(info.name)) {
+      const result = signatures[info.name](call);
+      call.rest();
+      return result;
+    }
+    call.rest();
+    return ANY;
+  }
+}
+
+const functionTypes = (functions: FunctionClosure, builtin: boolean) => {
+  const frame: { [name: string]: Type } = {};
+  Object.keys(functions).forEach((name) => {
+    frame[name] = functionOf({
+      name,
+      arity: getArity(functions[name]),
+      builtin,
+    });
+  });
+  return frame;
+};
+
+// Finds, without running a query, the errors certain to happen when each of
+// its expressions is evaluated. The schema is an optional JSON Schema
+// describing the data.
+export const analyze = (
+  ast: ASTExpression,
+  schema?: unknown,
+  extras?: FunctionClosure
+): RuntimeError[] => {
+  const builtinsFrame = functionTypes(builtins, true);
+  const functions = Object.assign(
+    {},
+    builtinsFrame,
+    { now: STRING },
+    functionTypes(extras ?? {}, false)
+  );
+  const data = schema === undefined ? DATA : fromSchema(schema);
+  const analyzer = new Analyzer(builtinsFrame);
+  analyzer.infer(ast, [
+    functions,
+    { $: objectOf(Object.assign({}, functions, { "@": data })) },
+    data,
+  ]);
+  return analyzer.errors;
+};
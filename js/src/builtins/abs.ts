@@ -0,0 +1,8 @@
+import { BuiltinFunction } from "../types";
+import { arity, numberResult, validateType } from "../util";
+
+const abs: BuiltinFunction = arity(1, (args, stack, exec) =>
+  numberResult(Math.abs(validateType("number", exec(args[0], stack))))
+);
+
+export default abs;
@@ -0,0 +1,11 @@
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+import { truth } from "./any";
+
+const all: BuiltinFunction = arity([1, 2], (args, stack, exec) =>
+  validateType("array", exec(args[args.length - 1], stack)).every(
+    truth(args, stack, exec)
+  )
+);
+
+export default all;
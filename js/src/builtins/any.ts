@@ -0,0 +1,29 @@
+import { truthy } from "../runtimeValues";
+import { pushRuntimeValueToStack } from "../stackManip";
+import {
+  ASTExpression,
+  BuiltinFunction,
+  ExecutionFunction,
+  RuntimeValue,
+  Stack,
+} from "../types";
+import { arity, validateType } from "../util";
+
+// Whether each item, or the predicate applied to it, is truthy. `some` and
+// `every` stop at the first item that decides the result.
+export const truth =
+  (args: ASTExpression[], stack: Stack, exec: ExecutionFunction) =>
+  (item: RuntimeValue) =>
+    truthy(
+      args.length === 2
+        ? exec(args[0], pushRuntimeValueToStack(item, stack))
+        : item
+    );
+
+const any: BuiltinFunction = arity([1, 2], (args, stack, exec) =>
+  validateType("array", exec(args[args.length - 1], stack)).some(
+    truth(args, stack, exec)
+  )
+);
+
+export default any;
@@ -0,0 +1,13 @@
+import { BuiltinFunction } from "../types";
+import { arity } from "../util";
+import { numbersBy } from "./sumby";
+
+const avgby: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const numbers = numbersBy(args, stack, exec);
+  if (numbers.length === 0) {
+    return null;
+  }
+  return numbers.reduce((acc, cur) => acc + cur, 0) / numbers.length;
+});
+
+export default avgby;
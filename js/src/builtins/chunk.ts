@@ -0,0 +1,19 @@
+import { RuntimeError } from "../errors";
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { arity, validateType } from "../util";
+import { countArgument } from "./take";
+
+const chunk: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const size = countArgument("chunk", args, stack, exec);
+  if (size === 0) {
+    throw new RuntimeError("chunk: Size must be at least 1");
+  }
+  const target = validateType("array", exec(args[1], stack));
+  const result: RuntimeValue[][] = [];
+  for (let start = 0; start < target.length; start += size) {
+    result.push(target.slice(start, start + size));
+  }
+  return result;
+});
+
+export default chunk;
@@ -0,0 +1,15 @@
+import { RuntimeError } from "../errors";
+import { BuiltinFunction } from "../types";
+import { arity, numberResult, validateType } from "../util";
+
+const clamp: BuiltinFunction = arity(3, (args, stack, exec) => {
+  const [low, high, value] = args.map((arg) =>
+    validateType("number", exec(arg, stack))
+  );
+  if (low > high) {
+    throw new RuntimeError("clamp: The lower bound is above the upper bound");
+  }
+  return numberResult(Math.min(Math.max(value, low), high));
+});
+
+export default clamp;
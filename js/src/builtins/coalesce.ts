@@ -0,0 +1,10 @@
+import { BuiltinFunction } from "../types";
+import { arity } from "../util";
+
+const coalesce: BuiltinFunction =
+  arity(2, (args, stack, exec) => {
+    const a = exec(args[0], stack);
+    return a === null ? exec(args[1], stack) : a;
+  });
+
+export default coalesce;
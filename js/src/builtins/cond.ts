@@ -0,0 +1,23 @@
+import { RuntimeError } from "../errors";
+import { truthy } from "../runtimeValues";
+import { BuiltinFunction } from "../types";
+
+// Conditions are tried in order, and only the chosen result is evaluated
+const cond: BuiltinFunction = (args, stack, exec) => {
+  if (args.length < 3) {
+    throw new RuntimeError("Expected at least 3 arguments, got " + args.length);
+  }
+  if (args.length % 2 === 0) {
+    throw new RuntimeError(
+      "cond: Expected pairs of conditions and results, followed by a default"
+    );
+  }
+  for (let i = 0; i < args.length - 1; i += 2) {
+    if (truthy(exec(args[i], stack))) {
+      return exec(args[i + 1], stack);
+    }
+  }
+  return exec(args[args.length - 1], stack);
+};
+
+export default cond;
@@ -0,0 +1,10 @@
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+const contains: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const needle = validateType("string", exec(args[0], stack));
+  const target = validateType("string", exec(args[1], stack));
+  return target.indexOf(needle) !== -1;
+});
+
+export default contains;
@@ -0,0 +1,17 @@
+import { castToString } from "../runtimeValues";
+import { pushRuntimeValueToStack } from "../stackManip";
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { arity, validateType } from "../util";
+
+const countby: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const target = validateType("array", exec(args[1], stack));
+  const counts: { [key: string]: number } = {};
+  target.forEach((innerValue: RuntimeValue) => {
+    const newStack = pushRuntimeValueToStack(innerValue, stack);
+    const key = castToString(exec(args[0], newStack));
+    counts[key] = (counts[key] || 0) + 1;
+  });
+  return counts;
+});
+
+export default countby;
@@ -0,0 +1,16 @@
+import { RuntimeError } from "../errors";
+import { add, formatIso, toMs } from "../dates";
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+const dateadd: BuiltinFunction = arity(3, (args, stack, exec) => {
+  const unit = validateType("string", exec(args[0], stack));
+  const amount = validateType("number", exec(args[1], stack));
+  if (!Number.isInteger(amount)) {
+    throw new RuntimeError("Expected integer, got " + amount);
+  }
+  const date = toMs(exec(args[2], stack));
+  return formatIso(add(unit, amount, date));
+});
+
+export default dateadd;
@@ -0,0 +1,12 @@
+import { diff, toMs } from "../dates";
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+const datediff: BuiltinFunction = arity(3, (args, stack, exec) => {
+  const unit = validateType("string", exec(args[0], stack));
+  const start = toMs(exec(args[1], stack));
+  const end = toMs(exec(args[2], stack));
+  return diff(unit, start, end);
+});
+
+export default datediff;
@@ -0,0 +1,31 @@
+import { RuntimeError } from "../errors";
+import { getType } from "../runtimeValues";
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { validateType } from "../util";
+
+const deepmergeInner = (left: RuntimeValue, right: RuntimeValue) => {
+  if (getType(left) !== "object" || getType(right) !== "object") {
+    return right;
+  }
+  const retval: { [key: string]: RuntimeValue } = {};
+  for (const key in left) {
+    retval[key] = left[key];
+  }
+  for (const key in right) {
+    retval[key] = Object.prototype.hasOwnProperty.call(retval, key)
+      ? deepmergeInner(retval[key], right[key])
+      : right[key];
+  }
+  return retval;
+};
+
+const deepmerge: BuiltinFunction = (args, stack, exec) => {
+  if (args.length < 2) {
+    throw new RuntimeError("Expected at least 2 arguments, got " + args.length);
+  }
+  return args
+    .map((arg) => validateType("object", exec(arg, stack)))
+    .reduce((left, right) => deepmergeInner(left, right));
+};
+
+export default deepmerge;
@@ -0,0 +1,10 @@
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+import { countArgument } from "./take";
+
+const drop: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const count = countArgument("drop", args, stack, exec);
+  return validateType("array", exec(args[1], stack)).slice(count);
+});
+
+export default drop;
@@ -0,0 +1,9 @@
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+const first: BuiltinFunction = arity(1, (args, stack, exec) => {
+  const target = validateType("array", exec(args[0], stack));
+  return target.length ? target[0] : null;
+});
+
+export default first;
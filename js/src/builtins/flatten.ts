@@ -1,14 +1,16 @@
+import { checkSize } from "../limits";
 import { BuiltinFunction } from "../types";
 import { arity, validateType } from "../util";
 
 const flatten: BuiltinFunction = arity(1, (args, stack, exec) => {
   const target = validateType("array", exec(args[0], stack));
-  const newValue = target.reduce(
-    (acc: unknown[], cur: unknown) => 
-      acc.concat(validateType("array", cur)), 
-    []
-  );
+  const entries: unknown[][] = target.map((cur: unknown) => validateType("array", cur));
+  checkSize(entries.reduce((size, entry) => size + entry.length, 0));
+  const newValue: unknown[] = [];
+  entries.forEach((entry) => {
+    entry.forEach((item) => newValue.push(item));
+  });
   return newValue;
 });
 
-export default flatten;
\ No newline at end of file
+export default flatten;
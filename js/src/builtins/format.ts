@@ -0,0 +1,66 @@
+import { RuntimeError } from "../errors";
+import { castToString, getType } from "../runtimeValues";
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { arity, validateType } from "../util";
+
+const field = (name: string, values: RuntimeValue, position: number) => {
+  if (getType(values) === "object") {
+    if (name === "") {
+      throw new RuntimeError("format: {} needs an array of values");
+    }
+    if (!Object.prototype.hasOwnProperty.call(values, name)) {
+      throw new RuntimeError(`format: No value for {${name}}`);
+    }
+    return values[name];
+  }
+  let index = position;
+  if (name !== "") {
+    if (!/^[0-9]+$/.test(name)) {
+      throw new RuntimeError(`format: {${name}} needs an object of values`);
+    }
+    index = parseInt(name, 10);
+  }
+  if (index >= values.length) {
+    throw new RuntimeError(`format: No value for {${name}}`);
+  }
+  return values[index];
+};
+
+const format: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const template = validateType("string", exec(args[0], stack));
+  const values = exec(args[1], stack);
+  if (["array", "object"].indexOf(getType(values)) === -1) {
+    throw new RuntimeError(
+      "Expected array or object as second argument to format"
+    );
+  }
+  const result: string[] = [];
+  let position = 0;
+  let i = 0;
+  while (i < template.length) {
+    const char = template[i];
+    if ((char === "{" || char === "}") && template[i + 1] === char) {
+      result.push(char);
+      i += 2;
+    } else if (char === "}") {
+      throw new RuntimeError("format: Unmatched } in template");
+    } else if (char === "{") {
+      const end = template.indexOf("}", i);
+      if (end === -1) {
+        throw new RuntimeError("format: Unmatched { in template");
+      }
+      const name = template.slice(i + 1, end);
+      result.push(castToString(field(name, values, position)));
+      if (name === "") {
+        position++;
+      }
+      i = end + 1;
+    } else {
+      result.push(char);
+      i++;
+    }
+  }
+  return result.join("");
+});
+
+export default format;
@@ -0,0 +1,14 @@
+import { formatDate, formatIso, toMs } from "../dates";
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+const formatdate: BuiltinFunction = arity([1, 2], (args, stack, exec) => {
+  const date = toMs(exec(args[args.length - 1], stack));
+  if (args.length === 1) {
+    return formatIso(date);
+  }
+  const format = validateType("string", exec(args[0], stack));
+  return formatDate(format, date);
+});
+
+export default formatdate;
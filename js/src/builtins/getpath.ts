@@ -0,0 +1,49 @@
+import { RuntimeError } from "../errors";
+import { getType } from "../runtimeValues";
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { arity, validateType } from "../util";
+
+// The keys and indices of a path, checking that they are one or the other
+export const pathSteps = (value: RuntimeValue): (string | number)[] =>
+  validateType("array", value).map((step: RuntimeValue) => {
+    const type = getType(step);
+    if (type === "number" && !Number.isInteger(step)) {
+      throw new RuntimeError("Expected integer, got " + step);
+    }
+    if (type !== "string" && type !== "number") {
+      throw new RuntimeError(
+        "Expected path of strings and integers, got " + type
+      );
+    }
+    return step;
+  });
+
+// The position an index refers to, counting negative ones from the end
+export const arrayIndex = (items: RuntimeValue[], index: number) => {
+  const position = index < 0 ? index + items.length : index;
+  return position >= 0 && position < items.length ? position : null;
+};
+
+const getpath: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const path = pathSteps(exec(args[0], stack));
+  let current = exec(args[1], stack);
+  for (const step of path) {
+    const type = getType(current);
+    if (typeof step === "string" && type === "object") {
+      current = Object.prototype.hasOwnProperty.call(current, step)
+        ? current[step]
+        : null;
+    } else if (typeof step === "number" && type === "array") {
+      const position = arrayIndex(current, step);
+      if (position === null) {
+        return null;
+      }
+      current = current[position];
+    } else {
+      return null;
+    }
+  }
+  return current;
+});
+
+export default getpath;
@@ -0,0 +1,10 @@
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+const haskey: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const key = validateType("string", exec(args[0], stack));
+  const target = validateType("object", exec(args[1], stack));
+  return Object.prototype.hasOwnProperty.call(target, key);
+});
+
+export default haskey;
@@ -1,47 +1,99 @@
 import { RuntimeError } from "../errors";
-import { compare, truthy } from "../runtimeValues";
+import { compare, getType, truthy } from "../runtimeValues";
 import { BuiltinFunction } from "../types";
 import { arity, validateType } from "../util";
+import abs from "./abs";
+import all from "./all";
 import and from "./and";
+import any from "./any";
 import apply from "./apply";
+import avgby from "./avgby";
+import ceil from "./ceil";
+import chunk from "./chunk";
+import clamp from "./clamp";
+import coalesce from "./coalesce";
+import cond from "./cond";
+import contains from "./contains";
 import count from "./count";
+import countby from "./countby";
+import dateadd from "./dateadd";
+import datediff from "./datediff";
+import deepmerge from "./deepmerge";
+import drop from "./drop";
+import endswith from "./endswith";
 import entries from "./entries";
 import equal from "./equal";
 import filter from "./filter";
 import filterkeys from "./filterkeys";
 import filtervalues from "./filtervalues";
 import find from "./find";
+import first from "./first";
 import flatten from "./flatten";
 import float from "./float";
+import floor from "./floor";
+import format from "./format";
+import formatdate from "./formatdate";
 import fromentries from "./fromentries";
+import getpath from "./getpath";
 import groupby from "./groupby";
+import haskey from "./haskey";
 import indexFn, {indexInner} from "./indexFn";
+import intdiv from "./intdiv";
 import join from "./join";
 import keys from "./keys";
+import last from "./last";
+import length from "./length";
+import ln from "./ln";
 import log from "./log";
+import lower from "./lower";
 import map from "./map";
 import mapkeys from "./mapkeys";
 import mapvalues from "./mapvalues";
 import match from "./match";
+import max from "./max";
+import maxby from "./maxby";
+import merge from "./merge";
+import min from "./min";
+import minby from "./minby";
 import not from "./not";
 import notequal from "./notequal";
+import omit from "./omit";
 import or from "./or";
+import padend from "./padend";
+import padstart from "./padstart";
+import parsedate from "./parsedate";
+import percentile from "./percentile";
+import pick from "./pick";
 import plus from "./plus";
+import pow from "./pow";
 import range from "./range";
 import reduce from "./reduce";
 import regex from "./regex";
 import replace from "./replace";
 import reverse from "./reverse";
+import round from "./round";
 import sequence from "./sequence";
+import setpath from "./setpath";
 import sort from "./sort";
 import sortby from "./sortby";
 import split from "./split";
+import sqrt from "./sqrt";
+import startswith from "./startswith";
 import string from "./string";
+import substring from "./substring";
 import sum from "./sum";
+import sumby from "./sumby";
 import summarize from "./summarize";
+import take from "./take";
+import trim from "./trim";
+import truncdate from "./truncdate";
 import unaryMinus from "./unaryMinus";
+import unique from "./unique";
+import uniqueby from "./uniqueby";
+import upper from "./upper";
 import values from "./values";
 import withindices from "./withindices";
+import zip from "./zip";
 
 const numericBinaryOperator = (
   op: (a: number, b: number) => number
@@ -74,7 +126,20 @@ const dotAccessor: BuiltinFunction = arity(2, (args, stack, exec) => {
   const former = exec(args[0], stack);
   const ref = args[1];
   if (ref.type !== "reference") {
-    throw new Error("Only references are allowed as rhs to dot access");
+    throw new RuntimeError("Only references are allowed as rhs to dot access");
+  }
+  return indexInner(former, ref.ref, undefined);
+});
+
+// Unlike `.`, anything other than an object gives null rather than an error
+const lenientDotAccessor: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const former = exec(args[0], stack);
+  const ref = args[1];
+  if (ref.type !== "reference") {
+    throw new RuntimeError("Only references are allowed as rhs to dot access");
+  }
+  if (getType(former) !== "object") {
+    return null;
   }
   return indexInner(former, ref.ref, undefined);
 });
@@ -96,52 +161,118 @@ const divide = numericBinaryOperator((a, b) => {
   return a / b;
 });
 
+const modulo = numericBinaryOperator((a, b) => {
+  if (b === 0) {
+    throw new RuntimeError("Modulo by zero");
+  }
+  // Floored, as in Python: the result takes the sign of the divisor, even
+  // when it's zero.
+  const remainder = a % b;
+  if (remainder === 0) {
+    return b < 0 ? -0 : 0;
+  }
+  return remainder < 0 !== b < 0 ? remainder + b : remainder;
+});
+
 export default {
+  abs,
+  all,
+  any,
   apply,
+  avgby,
+  ceil,
+  chunk,
+  clamp,
+  cond,
+  contains,
   count,
+  countby,
+  dateadd,
+  datediff,
+  deepmerge,
+  drop,
+  endswith,
   entries,
   filter,
   filterkeys,
   filtervalues,
   find,
+  first,
   flatten,
   float,
+  floor,
+  format,
+  formatdate,
   fromentries,
+  getpath,
   groupby,
+  haskey,
   if: ifFunction,
   index: indexFn,
+  intdiv,
   join,
+  last,
+  maxby,
+  merge,
+  minby,
+  omit,
+  percentile,
+  pick,
+  setpath,
   stringjoin: join,
   keys,
+  length,
+  ln,
   log,
+  lower,
   match,
   map,
   mapkeys,
   mapvalues,
+  max,
+  min,
+  padend,
+  padstart,
+  parsedate,
+  pow,
   range,
   reduce,
   regex,
   replace,
   reverse,
+  round,
   sequence,
   sort,
   sortby,
   split,
+  sqrt,
+  startswith,
   string,
+  substring,
   sum,
+  sumby,
   summarize,
+  take,
+  trim,
+  truncdate,
+  unique,
+  uniqueby,
+  upper,
   values,
   withindices,
+  zip,
   "!/unary": not,
   "-/unary": unaryMinus,
   ".": dotAccessor,
+  "?.": lenientDotAccessor,
   "+": plus,
   "-": numericBinaryOperator((a, b) => a - b),
   "*": numericBinaryOperator((a, b) => a * b),
   "/": divide,
-  "%": numericBinaryOperator((a, b) => a % b),
+  "%": modulo,
   "||": or,
   "&&": and,
+  "??": coalesce,
   "==": equal,
   "!=": notequal,
   ">": binaryCompareFunction([true, false, false]),
@@ -0,0 +1,14 @@
+import { RuntimeError } from "../errors";
+import { BuiltinFunction } from "../types";
+import { arity, numberResult, validateType } from "../util";
+
+const intdiv: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const dividend = validateType("number", exec(args[0], stack));
+  const divisor = validateType("number", exec(args[1], stack));
+  if (divisor === 0) {
+    throw new RuntimeError("Division by zero");
+  }
+  return numberResult(Math.floor(dividend / divisor));
+});
+
+export default intdiv;
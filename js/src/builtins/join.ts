@@ -1,3 +1,4 @@
+import { checkSize, step, stringSize } from "../limits";
 import { castToString } from "../runtimeValues";
 import { BuiltinFunction } from "../types";
 import { arity, validateType } from "../util";
@@ -5,7 +6,13 @@ import { arity, validateType } from "../util";
 const split: BuiltinFunction = arity(2, (args, stack, exec) => {
   const joiner = validateType("string", exec(args[0], stack));
   const source = validateType("array", exec(args[1], stack));
-  return source.map(castToString).join(joiner);
+  const strings: string[] = source.map((item: unknown) => {
+    step();
+    return castToString(item);
+  });
+  const separators = stringSize(joiner) * Math.max(strings.length - 1, 0);
+  checkSize(strings.reduce((size, s) => size + stringSize(s), separators));
+  return strings.join(joiner);
 });
 
 export default split;
@@ -0,0 +1,9 @@
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+const last: BuiltinFunction = arity(1, (args, stack, exec) => {
+  const target = validateType("array", exec(args[0], stack));
+  return target.length ? target[target.length - 1] : null;
+});
+
+export default last;
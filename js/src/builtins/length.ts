@@ -0,0 +1,8 @@
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+const length: BuiltinFunction = arity(1, (args, stack, exec) =>
+  Array.from(validateType("string", exec(args[0], stack))).length
+);
+
+export default length;
@@ -0,0 +1,7 @@
+import { BuiltinFunction } from "../types";
+import { arity } from "../util";
+import { extremum } from "./min";
+
+const max: BuiltinFunction = arity([1, 2], extremum(Math.max));
+
+export default max;
@@ -0,0 +1,7 @@
+import { BuiltinFunction } from "../types";
+import { arity } from "../util";
+import { extremumBy } from "./minby";
+
+const maxby: BuiltinFunction = arity(2, extremumBy("maxby", -1));
+
+export default maxby;
@@ -0,0 +1,19 @@
+import { RuntimeError } from "../errors";
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { validateType } from "../util";
+
+const merge: BuiltinFunction = (args, stack, exec) => {
+  if (args.length < 2) {
+    throw new RuntimeError("Expected at least 2 arguments, got " + args.length);
+  }
+  const retval: { [key: string]: RuntimeValue } = {};
+  args.forEach((arg) => {
+    const source = validateType("object", exec(arg, stack));
+    for (const key in source) {
+      retval[key] = source[key];
+    }
+  });
+  return retval;
+};
+
+export default merge;
@@ -0,0 +1,27 @@
+import {
+  ASTExpression,
+  BuiltinFunction,
+  ExecutionFunction,
+  RuntimeValue,
+  Stack,
+} from "../types";
+import { arity, numberResult, validateType } from "../util";
+
+// The smallest or largest of two numbers or an array of them, per `pick`
+export const extremum =
+  (pick: (a: number, b: number) => number) =>
+  (args: ASTExpression[], stack: Stack, exec: ExecutionFunction) => {
+    const values: RuntimeValue[] =
+      args.length === 2
+        ? args.map((arg) => exec(arg, stack))
+        : validateType("array", exec(args[0], stack));
+    const numbers = values.map((value) => validateType("number", value));
+    if (numbers.length === 0) {
+      return null;
+    }
+    return numberResult(numbers.reduce((a, b) => pick(a, b)));
+  };
+
+const min: BuiltinFunction = arity([1, 2], extremum(Math.min));
+
+export default min;
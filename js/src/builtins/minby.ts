@@ -0,0 +1,37 @@
+import { RuntimeError } from "../errors";
+import { comparable, compare, getType } from "../runtimeValues";
+import { pushRuntimeValueToStack } from "../stackManip";
+import {
+  ASTExpression,
+  BuiltinFunction,
+  ExecutionFunction,
+  RuntimeValue,
+  Stack,
+} from "../types";
+import { arity, validateType } from "../util";
+
+// The item with the smallest or largest key. `compare` is positive when its
+// second argument is larger, so `sign` is 1 for the smallest.
+export const extremumBy =
+  (name: string, sign: number) =>
+  (args: ASTExpression[], stack: Stack, exec: ExecutionFunction) => {
+    const target = validateType("array", exec(args[1], stack));
+    let best: RuntimeValue = null;
+    let bestKey: RuntimeValue = undefined;
+    target.forEach((item: RuntimeValue) => {
+      const key = exec(args[0], pushRuntimeValueToStack(item, stack));
+      if (!comparable(key)) {
+        throw new RuntimeError(name + ": Cannot compare " + getType(key));
+      }
+      // Ties go to the earliest item
+      if (bestKey === undefined || sign * compare(key, bestKey) > 0) {
+        best = item;
+        bestKey = key;
+      }
+    });
+    return best;
+  };
+
+const minby: BuiltinFunction = arity(2, extremumBy("minby", 1));
+
+export default minby;
@@ -0,0 +1,17 @@
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { arity, validateType } from "../util";
+import { keyList } from "./pick";
+
+const omit: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const keys = keyList(exec(args[0], stack));
+  const source = validateType("object", exec(args[1], stack));
+  const retval: { [key: string]: RuntimeValue } = {};
+  for (const key in source) {
+    if (keys.indexOf(key) === -1) {
+      retval[key] = source[key];
+    }
+  }
+  return retval;
+});
+
+export default omit;
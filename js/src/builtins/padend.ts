@@ -0,0 +1,10 @@
+import { BuiltinFunction } from "../types";
+import { arity } from "../util";
+import { padding } from "./padstart";
+
+const padend: BuiltinFunction = arity([2, 3], (args, stack, exec) => {
+  const [target, pad] = padding(args, stack, exec);
+  return target + pad;
+});
+
+export default padend;
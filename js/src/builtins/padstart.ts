@@ -0,0 +1,44 @@
+import { RuntimeError } from "../errors";
+import { checkSize } from "../limits";
+import {
+  ASTExpression,
+  BuiltinFunction,
+  ExecutionFunction,
+  Stack,
+} from "../types";
+import { arity, validateType } from "../util";
+
+// The target of padstart or padend, and the padding it needs
+export const padding = (
+  args: ASTExpression[],
+  stack: Stack,
+  exec: ExecutionFunction
+): [string, string] => {
+  const width = validateType("number", exec(args[0], stack));
+  if (!Number.isInteger(width)) {
+    throw new RuntimeError("Expected integer, got " + width);
+  }
+  let fill = " ";
+  if (args.length === 3) {
+    fill = validateType("string", exec(args[1], stack));
+    if (fill === "") {
+      throw new RuntimeError("Cannot pad with an empty string");
+    }
+  }
+  const target = validateType("string", exec(args[args.length - 1], stack));
+  const missing = Math.max(width - Array.from(target).length, 0);
+  checkSize(width);
+  const fillCodepoints = Array.from(fill);
+  const result: string[] = [];
+  for (let i = 0; i < missing; i++) {
+    result.push(fillCodepoints[i % fillCodepoints.length]);
+  }
+  return [target, result.join("")];
+};
+
+const padstart: BuiltinFunction = arity([2, 3], (args, stack, exec) => {
+  const [target, pad] = padding(args, stack, exec);
+  return pad + target;
+});
+
+export default padstart;
@@ -0,0 +1,9 @@
+import { toMs } from "../dates";
+import { BuiltinFunction } from "../types";
+import { arity } from "../util";
+
+const parsedate: BuiltinFunction = arity(1, (args, stack, exec) =>
+  toMs(exec(args[0], stack))
+);
+
+export default parsedate;
@@ -0,0 +1,25 @@
+import { RuntimeError } from "../errors";
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { arity, validateType } from "../util";
+
+const percentile: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const p = validateType("number", exec(args[0], stack));
+  if (p < 0 || p > 100) {
+    throw new RuntimeError("percentile: Percentile must be from 0 to 100");
+  }
+  const values: number[] = validateType("array", exec(args[1], stack))
+    .map((item: RuntimeValue) => validateType("number", item))
+    .sort((a: number, b: number) => a - b);
+  if (values.length === 0) {
+    return null;
+  }
+  // Interpolates between the closest ranks, which makes the 50th percentile
+  // the median as summarize finds it
+  const position = (p * (values.length - 1)) / 100;
+  const lower = Math.floor(position);
+  const upper = Math.min(lower + 1, values.length - 1);
+  const fraction = position - lower;
+  return values[lower] * (1 - fraction) + values[upper] * fraction;
+});
+
+export default percentile;
@@ -0,0 +1,22 @@
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { arity, validateType } from "../util";
+
+// The keys given to pick or omit
+export const keyList = (value: RuntimeValue): string[] =>
+  validateType("array", value).map((key: RuntimeValue) =>
+    validateType("string", key)
+  );
+
+const pick: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const keys = keyList(exec(args[0], stack));
+  const source = validateType("object", exec(args[1], stack));
+  const retval: { [key: string]: RuntimeValue } = {};
+  keys.forEach((key) => {
+    if (Object.prototype.hasOwnProperty.call(source, key)) {
+      retval[key] = source[key];
+    }
+  });
+  return retval;
+});
+
+export default pick;
@@ -1,4 +1,5 @@
 import { RuntimeError } from "../errors";
+import { checkSize, stringSize } from "../limits";
 import { getType } from "../runtimeValues";
 import { BuiltinFunction } from "../types";
 import { arity } from "../util";
@@ -10,9 +11,14 @@ const plus: BuiltinFunction = arity(2, (args, stack, exec) => {
   if (type !== getType(b)) {
     throw new RuntimeError("Cannot add values of different types");
   }
+  // The size of the result is known before building it
   if (type === "array") {
+    checkSize(a.length + b.length);
     return [].concat(a, b)
   }
+  if (type === "string") {
+    checkSize(stringSize(a) + stringSize(b));
+  }
   if (type !== 'string' && type !== 'number') {
     throw new RuntimeError("Cannot add values of type " + type)
   }
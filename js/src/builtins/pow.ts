@@ -0,0 +1,10 @@
+import { BuiltinFunction } from "../types";
+import { arity, numberResult, validateType } from "../util";
+
+const pow: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const base = validateType("number", exec(args[0], stack));
+  const exponent = validateType("number", exec(args[1], stack));
+  return numberResult(Math.pow(base, exponent));
+});
+
+export default pow;
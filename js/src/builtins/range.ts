@@ -1,3 +1,5 @@
+import { RuntimeError } from "../errors";
+import { checkSize, step as countStep } from "../limits";
 import { pushRuntimeValueToStack } from "../stackManip";
 import { BuiltinFunction, RuntimeValue } from "../types";
 import { arity, validateType } from "../util";
@@ -5,7 +7,7 @@ import { arity, validateType } from "../util";
 const validateIsInteger = (value: RuntimeValue) => {
   const res = validateType("number", value);
   if (!Number.isInteger(value)) {
-    throw new Error("Arguments to range must be integers");
+    throw new RuntimeError("Arguments to range must be integers");
   }
   return res;
 };
@@ -27,13 +29,18 @@ const range: BuiltinFunction = arity([1, 2, 3], (args, stack, exec) => {
   }
   // Iteration Methods
   if (step === 0) {
-    throw new Error("Range: Step size cannot be 0");
-  } else if (step > 0 && start < end) {
+    throw new RuntimeError("Range: Step size cannot be 0");
+  }
+  // Checked up front, so that a range too large to return is never built
+  checkSize(Math.max(0, Math.ceil((end - start) / step)));
+  if (step > 0 && start < end) {
     for (let i = start; i < end; i += step) {
+      countStep();
       target.push(i);
     }
   } else if (step < 0 && start > end) {
     for (let i = start; i > end; i += step) {
+      countStep();
       target.push(i);
     }
   } else {
@@ -1,14 +1,36 @@
 import { RuntimeError } from "../errors";
+import { checkSize, step, stringSize } from "../limits";
 import { getType } from "../runtimeValues";
 import { BuiltinFunction } from "../types";
 import { arity, validateType } from "../util";
 
+// A global regex can grow the target many times over, so each match counts a
+// step, and the result is measured before it's built when the replacement is
+// plain text.
+const checkGlobalReplace = (matcher: RegExp, replacer: string, target: string) => {
+  const pattern = new RegExp(matcher.source, matcher.flags);
+  let size = stringSize(target);
+  let match: RegExpExecArray | null;
+  while ((match = pattern.exec(target)) !== null) {
+    step();
+    size += stringSize(replacer) - stringSize(match[0]);
+    if (match[0] === "") {
+      pattern.lastIndex++;
+    }
+  }
+  if (replacer.indexOf("$") === -1) {
+    checkSize(size);
+  }
+};
 
 const replace: BuiltinFunction = arity(3, (args, stack, exec) => {
   const matcher = exec(args[0], stack);
   const replacer = validateType("string", exec(args[1], stack));
   const target = validateType("string", exec(args[2], stack));
   if (getType(matcher) === 'regex' || getType(matcher) === 'string') {
+    if (getType(matcher) === 'regex' && matcher.global) {
+      checkGlobalReplace(matcher, replacer, target);
+    }
     return target.replace(matcher, replacer);
   } else {
     throw new RuntimeError("Replacing only works with strings or regexes")
@@ -0,0 +1,38 @@
+import { RuntimeError } from "../errors";
+import { BuiltinFunction } from "../types";
+import { arity, numberResult, validateType } from "../util";
+
+const roundHalfAway = (value: number) => {
+  let whole = Math.floor(Math.abs(value));
+  if (Math.abs(value) - whole >= 0.5) {
+    whole += 1;
+  }
+  return value < 0 ? -whole : whole;
+};
+
+const round: BuiltinFunction = arity([1, 2], (args, stack, exec) => {
+  let precision = 0;
+  if (args.length === 2) {
+    precision = validateType("number", exec(args[0], stack));
+    if (!Number.isInteger(precision)) {
+      throw new RuntimeError("Expected integer, got " + precision);
+    }
+    if (Math.abs(precision) > 308) {
+      throw new RuntimeError("round: Precision must be from -308 to 308");
+    }
+  }
+  const value = validateType("number", exec(args[args.length - 1], stack));
+  // Parsed rather than computed, so that it's the closest double everywhere
+  const factor = Number("1e" + Math.abs(precision));
+  if (precision >= 0) {
+    const scaled = value * factor;
+    // Too big to have any digits at this precision
+    if (!Number.isFinite(scaled)) {
+      return numberResult(value);
+    }
+    return numberResult(roundHalfAway(scaled) / factor);
+  }
+  return numberResult(roundHalfAway(value / factor) * factor);
+});
+
+export default round;
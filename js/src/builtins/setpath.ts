@@ -0,0 +1,51 @@
+import { RuntimeError } from "../errors";
+import { getType } from "../runtimeValues";
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { arity } from "../util";
+import { arrayIndex, pathSteps } from "./getpath";
+
+const setpathInner = (
+  target: RuntimeValue,
+  path: (string | number)[],
+  value: RuntimeValue
+): RuntimeValue => {
+  if (path.length === 0) {
+    return value;
+  }
+  const [step, ...rest] = path;
+  const type = getType(target);
+  if (typeof step === "string") {
+    if (type !== "object" && type !== "null") {
+      throw new RuntimeError("setpath: Cannot set " + step + " on " + type);
+    }
+    const retval: { [key: string]: RuntimeValue } = {};
+    for (const key in target) {
+      retval[key] = target[key];
+    }
+    const existing = Object.prototype.hasOwnProperty.call(retval, step)
+      ? retval[step]
+      : null;
+    retval[step] = setpathInner(existing, rest, value);
+    return retval;
+  }
+  if (type !== "array") {
+    throw new RuntimeError(
+      "setpath: Cannot set index " + step + " on " + type
+    );
+  }
+  const position = arrayIndex(target, step);
+  if (position === null) {
+    throw new RuntimeError("setpath: Index " + step + " is out of range");
+  }
+  const items = target.slice();
+  items[position] = setpathInner(items[position], rest, value);
+  return items;
+};
+
+const setpath: BuiltinFunction = arity(3, (args, stack, exec) => {
+  const path = pathSteps(exec(args[0], stack));
+  const value = exec(args[1], stack);
+  return setpathInner(exec(args[2], stack), path, value);
+});
+
+export default setpath;
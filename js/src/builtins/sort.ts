@@ -1,3 +1,5 @@
+import { RuntimeError } from "../errors";
+import { step } from "../limits";
 import { comparable, compare } from "../runtimeValues";
 import { BuiltinFunction } from "../types";
 import { arity, validateType } from "../util";
@@ -6,10 +8,13 @@ const sort: BuiltinFunction = arity(1, (args, stack, exec) => {
   const arg = validateType("array", exec(args[0], stack));
 
   if (arg.some(value => !comparable(value))) {
-    throw new Error("Cannot sort non-comparable values");
+    throw new RuntimeError("Cannot sort non-comparable values");
   }
-  // default to ascending
-  return arg.slice().sort((b, a) => compare(a, b));
+  // default to ascending, where each comparison counts a step
+  return arg.slice().sort((b, a) => {
+    step();
+    return compare(a, b);
+  });
 });
 
 export default sort;
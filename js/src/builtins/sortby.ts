@@ -1,3 +1,5 @@
+import { RuntimeError } from "../errors";
+import { step } from "../limits";
 import { comparable, compare } from "../runtimeValues";
 import { pushRuntimeValueToStack } from "../stackManip";
 import { BuiltinFunction } from "../types";
@@ -9,11 +11,15 @@ const sortby: BuiltinFunction = arity(2, (args, stack, exec) => {
     const sortValue = exec(args[0], pushRuntimeValueToStack(item, stack))
 
     if (!comparable(sortValue)) {
-      throw new Error("Cannot sort non-comparable values");
+      throw new RuntimeError("Cannot sort non-comparable values");
     }
 
     return ({ sortValue, item })
-  }).sort(({ sortValue: a }, { sortValue: b }) => compare(b, a)).map(({ item }) => item);
+  }).sort(({ sortValue: a }, { sortValue: b }) => {
+    // Each comparison counts a step
+    step();
+    return compare(b, a);
+  }).map(({ item }) => item);
 });
 
 export default sortby;
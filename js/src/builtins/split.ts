@@ -1,4 +1,5 @@
 import { RuntimeError } from "../errors";
+import { checkSize, step, stringSize } from "../limits";
 import { getType } from "../runtimeValues";
 import { BuiltinFunction } from "../types";
 import { arity, validateType } from "../util";
@@ -11,8 +12,22 @@ const split: BuiltinFunction = arity(2, (args, stack, exec) => {
     throw new RuntimeError("Expected string or regex as second argument to split");
   }
   if (splitter === "") {
+    checkSize(stringSize(source));
     return [...source];
   }
+  if (getType(splitter) === "string") {
+    // The pieces are counted before any are built
+    let pieces = 1;
+    for (
+      let i = source.indexOf(splitter);
+      i !== -1;
+      i = source.indexOf(splitter, i + splitter.length)
+    ) {
+      step();
+      pieces++;
+    }
+    checkSize(pieces);
+  }
   return source.split(splitter);
 });
 
@@ -0,0 +1,10 @@
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+const startswith: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const prefix = validateType("string", exec(args[0], stack));
+  const target = validateType("string", exec(args[1], stack));
+  return target.startsWith(prefix);
+});
+
+export default startswith;
@@ -0,0 +1,21 @@
+import { RuntimeError } from "../errors";
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { arity, validateType } from "../util";
+
+const validateInteger = (value: RuntimeValue) => {
+  const res = validateType("number", value);
+  if (!Number.isInteger(res)) {
+    throw new RuntimeError("Expected integer, got " + res);
+  }
+  return res;
+};
+
+const substring: BuiltinFunction = arity([2, 3], (args, stack, exec) => {
+  const start = validateInteger(exec(args[0], stack));
+  const end =
+    args.length === 3 ? validateInteger(exec(args[1], stack)) : undefined;
+  const target = validateType("string", exec(args[args.length - 1], stack));
+  return Array.from(target).slice(start, end).join("");
+});
+
+export default substring;
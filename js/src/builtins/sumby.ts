@@ -0,0 +1,25 @@
+import { pushRuntimeValueToStack } from "../stackManip";
+import {
+  ASTExpression,
+  BuiltinFunction,
+  ExecutionFunction,
+  RuntimeValue,
+  Stack,
+} from "../types";
+import { arity, validateType } from "../util";
+
+// The numbers the contextual expression gives for each item of the target
+export const numbersBy = (
+  args: ASTExpression[],
+  stack: Stack,
+  exec: ExecutionFunction
+): number[] =>
+  validateType("array", exec(args[1], stack)).map((item: RuntimeValue) =>
+    validateType("number", exec(args[0], pushRuntimeValueToStack(item, stack)))
+  );
+
+const sumby: BuiltinFunction = arity(2, (args, stack, exec) =>
+  numbersBy(args, stack, exec).reduce((acc, cur) => acc + cur, 0)
+);
+
+export default sumby;
@@ -0,0 +1,32 @@
+import { RuntimeError } from "../errors";
+import {
+  ASTExpression,
+  BuiltinFunction,
+  ExecutionFunction,
+  Stack,
+} from "../types";
+import { arity, validateType } from "../util";
+
+// The count argument of take, drop, or chunk
+export const countArgument = (
+  name: string,
+  args: ASTExpression[],
+  stack: Stack,
+  exec: ExecutionFunction
+): number => {
+  const count = validateType("number", exec(args[0], stack));
+  if (!Number.isInteger(count)) {
+    throw new RuntimeError("Expected integer, got " + count);
+  }
+  if (count < 0) {
+    throw new RuntimeError(name + ": Count cannot be negative");
+  }
+  return count;
+};
+
+const take: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const count = countArgument("take", args, stack, exec);
+  return validateType("array", exec(args[1], stack)).slice(0, count);
+});
+
+export default take;
@@ -0,0 +1,13 @@
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+// String.prototype.trim disagrees with other languages about what whitespace
+// is, so this is the Unicode White_Space property, spelled out
+const whitespace =
+  /^[\t\n\v\f\r \x85\xa0\u1680\u2000-\u200a\u2028\u2029\u202f\u205f\u3000]+|[\t\n\v\f\r \x85\xa0\u1680\u2000-\u200a\u2028\u2029\u202f\u205f\u3000]+$/g;
+
+const trim: BuiltinFunction = arity(1, (args, stack, exec) =>
+  validateType("string", exec(args[0], stack)).replace(whitespace, "")
+);
+
+export default trim;
@@ -0,0 +1,11 @@
+import { formatIso, toMs, truncate } from "../dates";
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+const truncdate: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const unit = validateType("string", exec(args[0], stack));
+  const date = toMs(exec(args[1], stack));
+  return formatIso(truncate(unit, date));
+});
+
+export default truncdate;
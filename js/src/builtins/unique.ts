@@ -0,0 +1,29 @@
+import { step } from "../limits";
+import { equal } from "../runtimeValues";
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { arity, validateType } from "../util";
+
+// The items whose keys don't equal the key of any earlier item
+export const uniqueByKey = (items: RuntimeValue[], keys: RuntimeValue[]) => {
+  const result: RuntimeValue[] = [];
+  const seen: RuntimeValue[] = [];
+  items.forEach((item, i) => {
+    const isNew = seen.every((other) => {
+      // Each item is compared against every distinct key so far
+      step();
+      return !equal(keys[i], other);
+    });
+    if (isNew) {
+      seen.push(keys[i]);
+      result.push(item);
+    }
+  });
+  return result;
+};
+
+const unique: BuiltinFunction = arity(1, (args, stack, exec) => {
+  const target = validateType("array", exec(args[0], stack));
+  return uniqueByKey(target, target);
+});
+
+export default unique;
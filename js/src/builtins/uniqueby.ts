@@ -0,0 +1,14 @@
+import { pushRuntimeValueToStack } from "../stackManip";
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+import { uniqueByKey } from "./unique";
+
+const uniqueby: BuiltinFunction = arity(2, (args, stack, exec) => {
+  const target = validateType("array", exec(args[1], stack));
+  const keys = target.map((item: unknown) =>
+    exec(args[0], pushRuntimeValueToStack(item, stack))
+  );
+  return uniqueByKey(target, keys);
+});
+
+export default uniqueby;
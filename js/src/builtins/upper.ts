@@ -0,0 +1,8 @@
+import { BuiltinFunction } from "../types";
+import { arity, validateType } from "../util";
+
+const upper: BuiltinFunction = arity(1, (args, stack, exec) =>
+  validateType("string", exec(args[0], stack)).toUpperCase()
+);
+
+export default upper;
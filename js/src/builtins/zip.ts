@@ -0,0 +1,20 @@
+import { RuntimeError } from "../errors";
+import { BuiltinFunction, RuntimeValue } from "../types";
+import { validateType } from "../util";
+
+const zip: BuiltinFunction = (args, stack, exec) => {
+  if (args.length < 2) {
+    throw new RuntimeError("Expected at least 2 arguments, got " + args.length);
+  }
+  const targets: RuntimeValue[][] = args.map((arg) =>
+    validateType("array", exec(arg, stack))
+  );
+  const length = Math.min(...targets.map((target) => target.length));
+  const result: RuntimeValue[][] = [];
+  for (let i = 0; i < length; i++) {
+    result.push(targets.map((target) => target[i]));
+  }
+  return result;
+};
+
+export default zip;
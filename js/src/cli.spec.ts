@@ -0,0 +1,87 @@
+import assert from "assert";
+import { spawnSync } from "child_process";
+import fs from "fs";
+import os from "os";
+import path from "path";
+
+// These run the built CLI, so depend on dist/ being up to date.
+const mqPath = path.join(__dirname, "..", "bin", "mq.js");
+
+const mq = (args: string[], input?: string) =>
+  spawnSync(process.execPath, [mqPath, ...args], {
+    input: input ?? "",
+    encoding: "utf-8",
+  });
+
+const tempFile = (contents = "") => {
+  const dir = fs.mkdtempSync(path.join(os.tmpdir(), "mq-"));
+  const file = path.join(dir, "data");
+  fs.writeFileSync(file, contents);
+  return file;
+};
+
+describe("cli", () => {
+  it("reads data from stdin and pretty prints by default", () => {
+    const res = mq(["a"], '{"a": [1, 2]}');
+    assert.strictEqual(res.status, 0);
+    assert.strictEqual(res.stdout, "[\n  1,\n  2\n]\n");
+  });
+
+  it("reads data from --data, --file, and a positional file", () => {
+    const file = tempFile('{"a": 1}');
+    assert.strictEqual(mq(["a", "--data", '{"a": 1}']).stdout, "1\n");
+    assert.strictEqual(mq(["a", "--file", file]).stdout, "1\n");
+    assert.strictEqual(mq(["a", file]).stdout, "1\n");
+  });
+
+  it("prints compact output with --compact", () => {
+    assert.strictEqual(mq(["@", "-c", "-d", "[1, 2]"]).stdout, "[1,2]\n");
+  });
+
+  it("writes to --output", () => {
+    const output = tempFile();
+    const res = mq(["@", "--compact", "-d", '{"a": "b"}', "--output", output]);
+    assert.strictEqual(res.status, 0);
+    assert.strictEqual(fs.readFileSync(output).toString(), '{"a":"b"}');
+  });
+
+  it("writes one result per line for json-lines input", () => {
+    const res = mq(["a", "--jsonl"], '{"a": 1}\n\n{"a": [2]}\n');
+    assert.strictEqual(res.status, 0);
+    assert.strictEqual(res.stdout, "1\n[2]\n");
+    const file = tempFile('{"a": 1}\n{"a": 2}\n');
+    assert.strictEqual(mq(["a", "--file_jsonl", file]).stdout, "1\n2\n");
+  });
+
+  it("runs the query once over every line with --slurp", () => {
+    const res = mq(["@ | map a", "--jsonl", "--slurp", "-c"], '{"a": 1}\n{"a": 2}\n');
+    assert.strictEqual(res.stdout, "[1,2]\n");
+  });
+
+  it("prints the version", () => {
+    const packagejson = JSON.parse(
+      fs.readFileSync(path.join(__dirname, "..", "package.json")).toString()
+    );
+    assert.strictEqual(mq(["--version"]).stdout, packagejson.version + "\n");
+  });
+
+  it("exits non-zero on parse and runtime errors", () => {
+    const parseRes = mq(["1 +", "-d", "null"]);
+    assert.strictEqual(parseRes.status, 1);
+    assert.notStrictEqual(parseRes.stderr, "");
+    const runtimeRes = mq(['1 + "a"', "-d", "null"]);
+    assert.strictEqual(runtimeRes.status, 1);
+    assert.ok(runtimeRes.stderr.includes("^^^^^^^"));
+  });
+
+  it("exits non-zero on invalid data", () => {
+    assert.strictEqual(mq(["@", "-d", "{"]).status, 1);
+    assert.strictEqual(mq(["@", "--jsonl"], "1\nnope\n").status, 1);
+  });
+
+  it("exits non-zero on invalid usage", () => {
+    assert.strictEqual(mq([]).status, 2);
+    assert.strictEqual(mq(["@", "--unknown"]).status, 2);
+    assert.strictEqual(mq(["@", "--slurp", "-d", "1"]).status, 2);
+  });
+});
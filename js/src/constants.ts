@@ -4,13 +4,14 @@
 export const amalgamatingBinaryOperators = [" ", "|"];
 
 export const simpleBinaryOperators = [
-  ["."],
+  [".", "?."],
   ["*", "/", "%"],
   ["+", "-"],
   ["<", ">", "<=", ">="],
   ["==", "!=", "=~"],
   ["&&"],
   ["||"],
+  ["??"],
 ];
 
 export const binaryExpressionStrings = [].concat(
@@ -27,6 +28,8 @@ export const specials = binaryExpressionStrings.concat([
   "}",
   ":",
   ",",
+  "=",
+  "...",
 ]);
 
 export const unaryExpressions = ["-", "!"];
@@ -0,0 +1,283 @@
+import { RuntimeError } from "./errors";
+import { getType } from "./runtimeValues";
+import { RuntimeValue } from "./types";
+
+// Dates are whole milliseconds since the Unix epoch, always in UTC. Calendar
+// math is done by hand, rather than with Date, so that every implementation
+// parses, formats, and rounds dates exactly the same way.
+
+const MS_PER_SECOND = 1000;
+const MS_PER_MINUTE = 60 * MS_PER_SECOND;
+const MS_PER_HOUR = 60 * MS_PER_MINUTE;
+const MS_PER_DAY = 24 * MS_PER_HOUR;
+
+// Units with a fixed length, in milliseconds
+const fixedUnits: { [unit: string]: number } = {
+  millisecond: 1,
+  second: MS_PER_SECOND,
+  minute: MS_PER_MINUTE,
+  hour: MS_PER_HOUR,
+  day: MS_PER_DAY,
+  week: 7 * MS_PER_DAY,
+};
+
+// Units whose length depends on the calendar, in months
+const calendarUnits: { [unit: string]: number } = { month: 1, year: 12 };
+
+const isoRegex =
+  /^([0-9]{4})-([0-9]{2})-([0-9]{2})(?:[T ]([0-9]{2}):([0-9]{2})(?::([0-9]{2})(?:\.([0-9]+))?)?(Z|[+-][0-9]{2}:?[0-9]{2})?)?$/;
+
+const weekdays = [
+  "Monday",
+  "Tuesday",
+  "Wednesday",
+  "Thursday",
+  "Friday",
+  "Saturday",
+  "Sunday",
+];
+const months = [
+  "January",
+  "February",
+  "March",
+  "April",
+  "May",
+  "June",
+  "July",
+  "August",
+  "September",
+  "October",
+  "November",
+  "December",
+];
+
+// Division and remainder rounding towards negative infinity
+const div = (a: number, b: number) => Math.floor(a / b);
+const mod = (a: number, b: number) => a - div(a, b) * b;
+
+// Truncating division, which doesn't leave -0 behind
+const truncDiv = (a: number, b: number) => {
+  const quotient = Math.trunc(a / b);
+  return quotient === 0 ? 0 : quotient;
+};
+
+const pad = (n: number, width: number) => {
+  let digits = String(n);
+  while (digits.length < width) {
+    digits = "0" + digits;
+  }
+  return digits;
+};
+
+// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+const daysFromCivil = (year: number, month: number, day: number) => {
+  year -= month <= 2 ? 1 : 0;
+  const era = div(year, 400);
+  const yearOfEra = year - era * 400;
+  const dayOfYear = div(153 * (month + (month > 2 ? -3 : 9)) + 2, 5) + day - 1;
+  const dayOfEra =
+    yearOfEra * 365 + div(yearOfEra, 4) - div(yearOfEra, 100) + dayOfYear;
+  return era * 146097 + dayOfEra - 719468;
+};
+
+// The year, month, and day of a number of days since 1970-01-01.
+const civilFromDays = (days: number): [number, number, number] => {
+  days += 719468;
+  const era = div(days, 146097);
+  const dayOfEra = days - era * 146097;
+  const yearOfEra = div(
+    dayOfEra - div(dayOfEra, 1460) + div(dayOfEra, 36524) - div(dayOfEra, 146096),
+    365
+  );
+  const dayOfYear =
+    dayOfEra - (365 * yearOfEra + div(yearOfEra, 4) - div(yearOfEra, 100));
+  const mp = div(5 * dayOfYear + 2, 153);
+  const day = dayOfYear - div(153 * mp + 2, 5) + 1;
+  const month = mp < 10 ? mp + 3 : mp - 9;
+  return [yearOfEra + era * 400 + (month <= 2 ? 1 : 0), month, day];
+};
+
+const daysInMonth = (year: number, month: number) =>
+  month === 12
+    ? 31
+    : daysFromCivil(year, month + 1, 1) - daysFromCivil(year, month, 1);
+
+const MIN_DATE = daysFromCivil(0, 1, 1) * MS_PER_DAY;
+const MAX_DATE = daysFromCivil(10000, 1, 1) * MS_PER_DAY - 1;
+
+const checkRange = (ms: number) => {
+  if (ms < MIN_DATE || ms > MAX_DATE) {
+    throw new RuntimeError("Date out of range: years must be 0 to 9999");
+  }
+  return ms;
+};
+
+const parseIso = (text: string) => {
+  const match = isoRegex.exec(text);
+  if (match === null) {
+    throw new RuntimeError("Invalid ISO 8601 date: " + text);
+  }
+  const [year, month, day, hour, minute, second] = match
+    .slice(1, 7)
+    .map((group) => parseInt(group ?? "0", 10));
+  const fraction = match[7];
+  const offset = match[8];
+  if (
+    !(
+      month >= 1 &&
+      month <= 12 &&
+      day >= 1 &&
+      day <= daysInMonth(year, month) &&
+      hour <= 23 &&
+      minute <= 59 &&
+      second <= 59
+    )
+  ) {
+    throw new RuntimeError("Invalid ISO 8601 date: " + text);
+  }
+  let ms = daysFromCivil(year, month, day) * MS_PER_DAY;
+  ms += hour * MS_PER_HOUR + minute * MS_PER_MINUTE + second * MS_PER_SECOND;
+  // Digits past milliseconds are truncated
+  ms += parseInt(((fraction ?? "") + "000").slice(0, 3), 10);
+  if (offset && offset !== "Z") {
+    const offsetHours = parseInt(offset.slice(1, 3), 10);
+    const offsetMinutes = parseInt(offset.slice(-2), 10);
+    if (offsetHours > 23 || offsetMinutes > 59) {
+      throw new RuntimeError("Invalid ISO 8601 date: " + text);
+    }
+    const sign = offset[0] === "+" ? 1 : -1;
+    ms -= sign * (offsetHours * MS_PER_HOUR + offsetMinutes * MS_PER_MINUTE);
+  }
+  return checkRange(ms);
+};
+
+// Reads a date from an ISO 8601 string or epoch milliseconds.
+export const toMs = (value: RuntimeValue): number => {
+  const type = getType(value);
+  if (type === "string") {
+    return parseIso(value);
+  }
+  if (type === "number") {
+    if (!Number.isInteger(value)) {
+      throw new RuntimeError("Expected integer milliseconds, got " + value);
+    }
+    return checkRange(value);
+  }
+  throw new RuntimeError("Expected a date string or number, got " + type);
+};
+
+// The year, month, day, and milliseconds into the day of a date.
+const split = (ms: number): [number, number, number, number] => {
+  const [year, month, day] = civilFromDays(div(ms, MS_PER_DAY));
+  return [year, month, day, mod(ms, MS_PER_DAY)];
+};
+
+const formatDirective = (directive: string, ms: number) => {
+  const [year, month, day, timeOfDay] = split(ms);
+  const days = div(ms, MS_PER_DAY);
+  switch (directive) {
+    case "Y":
+      return pad(year, 4);
+    case "m":
+      return pad(month, 2);
+    case "d":
+      return pad(day, 2);
+    case "H":
+      return pad(div(timeOfDay, MS_PER_HOUR), 2);
+    case "M":
+      return pad(div(timeOfDay, MS_PER_MINUTE) % 60, 2);
+    case "S":
+      return pad(div(timeOfDay, MS_PER_SECOND) % 60, 2);
+    case "L":
+      return pad(timeOfDay % MS_PER_SECOND, 3);
+    case "j":
+      return pad(days - daysFromCivil(year, 1, 1) + 1, 3);
+    case "a":
+    case "A": {
+      // 1970-01-01 was a Thursday
+      const name = weekdays[mod(days + 3, 7)];
+      return directive === "A" ? name : name.slice(0, 3);
+    }
+    case "b":
+    case "B": {
+      const name = months[month - 1];
+      return directive === "B" ? name : name.slice(0, 3);
+    }
+    case "%":
+      return "%";
+  }
+  throw new RuntimeError("Unknown date format directive %" + directive);
+};
+
+export const formatDate = (format: string, ms: number) => {
+  const result: string[] = [];
+  for (let i = 0; i < format.length; i++) {
+    if (format[i] !== "%") {
+      result.push(format[i]);
+      continue;
+    }
+    if (i + 1 === format.length) {
+      throw new RuntimeError("Date format ends with a lone %");
+    }
+    result.push(formatDirective(format[i + 1], ms));
+    i++;
+  }
+  return result.join("");
+};
+
+export const formatIso = (ms: number) =>
+  formatDate("%Y-%m-%dT%H:%M:%S.%LZ", checkRange(ms));
+
+const checkUnit = (unit: string) => {
+  if (!fixedUnits.hasOwnProperty(unit) && !calendarUnits.hasOwnProperty(unit)) {
+    throw new RuntimeError("Unknown date unit " + unit);
+  }
+  return unit;
+};
+
+const addMonths = (ms: number, count: number) => {
+  const [year, month, day, timeOfDay] = split(ms);
+  const total = year * 12 + month - 1 + count;
+  const newYear = div(total, 12);
+  const newMonth = mod(total, 12) + 1;
+  // Days past the end of the new month are clamped to its last day
+  const newDay = Math.min(day, daysInMonth(newYear, newMonth));
+  return daysFromCivil(newYear, newMonth, newDay) * MS_PER_DAY + timeOfDay;
+};
+
+export const add = (unit: string, amount: number, ms: number) => {
+  if (fixedUnits.hasOwnProperty(checkUnit(unit))) {
+    return checkRange(ms + amount * fixedUnits[unit]);
+  }
+  return checkRange(addMonths(ms, amount * calendarUnits[unit]));
+};
+
+// The number of whole units from start to end, truncated toward zero.
+export const diff = (unit: string, start: number, end: number) => {
+  if (fixedUnits.hasOwnProperty(checkUnit(unit))) {
+    return truncDiv(end - start, fixedUnits[unit]);
+  }
+  const [startYear, startMonth] = split(start);
+  const [endYear, endMonth] = split(end);
+  let count = (endYear - startYear) * 12 + endMonth - startMonth;
+  if (count > 0 && addMonths(start, count) > end) {
+    count--;
+  } else if (count < 0 && addMonths(start, count) < end) {
+    count++;
+  }
+  return truncDiv(count, calendarUnits[unit]);
+};
+
+// The start of the unit the date falls in. Weeks start on Monday.
+export const truncate = (unit: string, ms: number) => {
+  checkUnit(unit);
+  if (unit === "week") {
+    const days = div(ms, MS_PER_DAY);
+    return (days - mod(days + 3, 7)) * MS_PER_DAY;
+  }
+  if (fixedUnits.hasOwnProperty(unit)) {
+    return ms - mod(ms, fixedUnits[unit]);
+  }
+  const [year, month] = split(ms);
+  return daysFromCivil(year, unit === "year" ? 1 : month, 1) * MS_PER_DAY;
+};
@@ -22,5 +22,15 @@ describe("errors", () => {
       const expected = "hello\n  ^\nthere";
       assert.strictEqual(makeIndicator('hello\nthere', 2), expected);
     });
+
+    it("underlines the given length", () => {
+      const expected = "hello there\n      ^^^^^";
+      assert.strictEqual(makeIndicator('hello there', 6, 5), expected);
+    });
+
+    it("doesn't underline past the end of the line", () => {
+      const expected = "hello\n   ^^\nthere";
+      assert.strictEqual(makeIndicator('hello\nthere', 3, 6), expected);
+    });
   });
 })
\ No newline at end of file
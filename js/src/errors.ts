@@ -1,10 +1,14 @@
 
+import { Span } from "./types";
 
-export const makeIndicator = (text: string, position: number) => {
+// Underlines up to `length` characters, without running past the line.
+export const makeIndicator = (text: string, position: number, length = 1) => {
   const prevStringLines = text.substring(0, position).replace(/[^\s]/g, ' ').split('\n');
   const lines = text.split('\n');
   const indicatorLine = prevStringLines.length - 1;
-  const indicator = prevStringLines.slice(-1)[0] + '^';
+  const prefix = prevStringLines.slice(-1)[0];
+  const restOfLine = lines[indicatorLine].length - prefix.length;
+  const indicator = prefix + '^'.repeat(Math.max(1, Math.min(length, restOfLine)));
   const formatted = [].concat(
     lines.slice(0, indicatorLine + 1),
     [indicator],
@@ -13,10 +17,10 @@ export const makeIndicator = (text: string, position: number) => {
   return formatted;
 }
 
-const makeMessage = (message: string, position: number, query: string) =>
+const makeMessage = (message: string, position: number, query: string, length = 1) =>
   `${message} at position ${position}
 ---
-${makeIndicator(query, position)}
+${makeIndicator(query, position, length)}
 ---
 `;
 
@@ -36,6 +40,24 @@ export class UnpositionableParseError extends Error {
 }
 
 export class RuntimeError extends Error {
+  // Span of the innermost expression that failed, once known.
+  span?: Span;
+  // The query the span points into.
+  query?: string;
 }
 
+// Thrown when a query exceeds one of the execution limits of its instance.
+export class ResourceLimitError extends RuntimeError {
+}
+
+// Rewrites the message of a runtime error to point at the failing expression.
+export const pointIntoQuery = (err: unknown, query: string) => {
+  if (err instanceof RuntimeError && err.span && err.query === undefined) {
+    err.query = query;
+    const { start, end } = err.span;
+    err.message = makeMessage(err.message, start, query, end - start);
+  }
+  return err;
+};
+
 export class OpenAnIssueIfThisOccursError extends Error { }
\ No newline at end of file
@@ -1,17 +1,35 @@
 import builtins from "./builtins";
-import { RuntimeError } from "./errors";
-import { getProperties, getType } from "./runtimeValues";
-import { pushRuntimeValueToStack } from "./stackManip";
+import { ResourceLimitError, RuntimeError } from "./errors";
+import {
+  checkSize,
+  getBudget,
+  Limits,
+  step,
+  stringSize,
+  withLimits,
+} from "./limits";
+import { castToString, getProperties, getType } from "./runtimeValues";
+import {
+  findInStack,
+  pushBindingsToStack,
+  pushRuntimeValueToStack,
+} from "./stackManip";
 import {
   ASTApplicationExpression,
   ASTExpression,
+  ASTLambdaExpression,
   ASTLiteralExpression,
   ASTReferenceExpression,
+  ASTSpreadExpression,
   Closure,
   ExecutionFunction,
   FunctionClosure,
+  FunctionValue,
   RuntimeValue,
+  Span,
+  Stack,
 } from "./types";
+import { arity } from "./util";
 
 export const inputGardenWall = (data: unknown) => {
   if (typeof data === "number" || data instanceof Number) {
@@ -72,7 +90,8 @@ export const outputGardenWall = (data: unknown) => {
 export const execute = (
   node: ASTExpression,
   variables: unknown,
-  extras?: FunctionClosure
+  extras?: FunctionClosure,
+  limits?: Limits
 ) => {
   const data = inputGardenWall(variables);
   const functions = extras ? Object.assign({}, builtins, extras) : builtins;
@@ -83,16 +102,61 @@ export const execute = (
     },
   ];
 
-  const result = executeInner(
-    node,
-    pushRuntimeValueToStack(data, initialStack)
-  );
+  const result = withLimits(limits, () => {
+    try {
+      return executeInner(node, pushRuntimeValueToStack(data, initialStack));
+    } catch (err) {
+      if (err instanceof RangeError && /call stack/.test(err.message)) {
+        throw new ResourceLimitError("Exceeded the maximum recursion depth");
+      }
+      throw err;
+    }
+  });
   return outputGardenWall(result);
 };
 
+// The innermost expression with a span is the most precise location, so
+// spans are only attached to errors that don't have one yet.
+const attachSpan = (err: unknown, span: Span | undefined) => {
+  if (err instanceof RuntimeError && err.span === undefined) {
+    err.span = span;
+  }
+  return err;
+};
+
+// Functions defined in a query close over the stack they're defined in
+const makeFunction = (
+  lambda: ASTLambdaExpression,
+  stack: Stack
+): FunctionValue => {
+  const fn: FunctionValue = arity(
+    lambda.params.length,
+    (args, callerStack, exec) => {
+      const bindings: Closure = { [lambda.name]: fn };
+      lambda.params.forEach((param, i) => {
+        bindings[param] = exec(args[i], callerStack);
+      });
+      return executeInner(lambda.body, pushBindingsToStack(bindings, stack));
+    }
+  );
+  return fn;
+};
+
 const executeInner: ExecutionFunction = (
   statement: ASTExpression,
-  stack: Closure[]
+  stack: Stack
+): RuntimeValue => {
+  try {
+    step();
+    return executeStatement(statement, stack);
+  } catch (err) {
+    throw attachSpan(err, statement.span);
+  }
+};
+
+const executeStatement = (
+  statement: ASTExpression,
+  stack: Stack
 ): RuntimeValue => {
   switch (statement.type) {
     case "parenthetical":
@@ -103,6 +167,15 @@ const executeInner: ExecutionFunction = (
       return executeReference(statement, stack);
     case "application":
       return executeApplication(statement, stack);
+    case "let": {
+      const value = executeInner(statement.value, stack);
+      return executeInner(
+        statement.body,
+        pushBindingsToStack({ [statement.name]: value }, stack)
+      );
+    }
+    case "lambda":
+      return makeFunction(statement, stack);
     case "pipeline":
       let last: RuntimeValue = executeInner(statement.stages[0], stack);
       for (let i = 1; i < statement.stages.length; i++) {
@@ -115,7 +188,11 @@ const executeInner: ExecutionFunction = (
           function: stage.function,
           arguments: stage.arguments.concat(atRef),
         };
-        last = executeApplication(app, pushRuntimeValueToStack(last, stack));
+        try {
+          last = executeApplication(app, pushRuntimeValueToStack(last, stack));
+        } catch (err) {
+          throw attachSpan(err, stage.span);
+        }
       }
       return last;
   }
@@ -123,7 +200,7 @@ const executeInner: ExecutionFunction = (
 
 const executeLiteral = (
   statement: ASTLiteralExpression,
-  stack: Closure[]
+  stack: Stack
 ): RuntimeValue => {
   switch (statement.valueType) {
     case "string":
@@ -131,33 +208,65 @@ const executeLiteral = (
     case "boolean":
     case "null":
       return statement.value;
-    case "array":
-      return statement.value.map((exp: ASTExpression) =>
-        executeInner(exp, stack)
-      );
+    case "array": {
+      const result: RuntimeValue[] = [];
+      statement.value.forEach((item) => {
+        if (item.type === "spread") {
+          const spread = executeSpread(item, stack, "array");
+          spread.forEach((value: RuntimeValue) => result.push(value));
+          checkSize(result.length);
+        } else {
+          result.push(executeInner(item, stack));
+        }
+      });
+      return result;
+    }
     case "object": {
+      // Later entries override earlier ones
       const result = {};
-      getProperties(statement.value).forEach((prop) => {
-        result[prop] = executeInner(statement.value[prop], stack);
-      })
+      statement.value.forEach((entry) => {
+        if ("key" in entry) {
+          const key =
+            typeof entry.key === "string"
+              ? entry.key
+              : castToString(executeInner(entry.key, stack));
+          result[key] = executeInner(entry.value, stack);
+          return;
+        }
+        const spread = executeSpread(entry, stack, "object");
+        getProperties(spread).forEach((prop) => {
+          result[prop] = spread[prop];
+        });
+        checkSize(getProperties(result).length);
+      });
       return result;
     }
   }
 };
 
+const executeSpread = (
+  spread: ASTSpreadExpression,
+  stack: Stack,
+  into: "array" | "object"
+): RuntimeValue => {
+  const value = executeInner(spread.value, stack);
+  const type = getType(value);
+  if (type !== into) {
+    // Spreads aren't executed on their own, so point at them here
+    throw attachSpan(
+      new RuntimeError(`Cannot spread ${type} into an ${into}`),
+      spread.span
+    );
+  }
+  return value;
+};
+
 const executeReference = (
   statement: ASTReferenceExpression,
-  inputStack: Closure[]
+  inputStack: Stack
 ): RuntimeValue => {
   const stack = statement.internal ? [builtins] : inputStack;
-  // first, find the appropriate referenced variable:
-  let referencedInStack = undefined;
-  for (let i = stack.length - 1; i >= 0; i--) {
-    if (stack[i][statement.ref] !== undefined) {
-      referencedInStack = stack[i][statement.ref];
-      break;
-    }
-  }
+  const referencedInStack = findInStack(stack, statement.ref, statement.quoted);
   if (referencedInStack === undefined) {
     throw new RuntimeError("Could not find referenced variable " + statement.ref);
   }
@@ -166,12 +275,31 @@ const executeReference = (
 
 const executeApplication = (
   statement: ASTApplicationExpression,
-  stack: Closure[]
+  stack: Stack
 ): RuntimeValue => {
   const fn = executeInner(statement.function, stack);
   const fnType = getType(fn);
   if (fnType !== "function") {
     throw new RuntimeError(`Attempted to call a variable of type "${fnType}". Only functions are callable`);
   }
-  return fn(statement.arguments, stack, executeInner);
+  const budget = getBudget();
+  if (!budget) {
+    return fn(statement.arguments, stack, executeInner);
+  }
+  budget.enter();
+  let result: RuntimeValue;
+  try {
+    result = fn(statement.arguments, stack, executeInner);
+  } finally {
+    budget.leave();
+  }
+  const resultType = getType(result);
+  if (resultType === "array") {
+    budget.checkSize(result.length);
+  } else if (resultType === "string") {
+    budget.checkSize(stringSize(result));
+  } else if (resultType === "object") {
+    budget.checkSize(getProperties(result).length);
+  }
+  return result;
 };
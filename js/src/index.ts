@@ -1,4 +1,6 @@
+import { ResourceLimitError } from "./errors";
 import {
+  CompiledQuery as CQ,
   defaultInstance as DI,
   MistQLInstance as MQI,
   MistQLOptions as MQO,
@@ -14,7 +16,12 @@ export {
 export const MistQLInstance = MQI;
 export const defaultInstance = DI;
 export const query = DI.query;
+export const compile = DI.compile;
+export const analyze = (query: string, schema?: unknown) =>
+  DI.compile(query).analyze(schema);
+export const CompiledQuery = CQ;
+export const MistQLResourceLimitError = ResourceLimitError;
 
 export type MistQLOptions = MQO;
 
-export default { query, defaultInstance, MistQLInstance };
+export default { query, compile, analyze, defaultInstance, MistQLInstance };
@@ -1,3 +1,4 @@
+import { RuntimeError } from "./errors";
 import { MistQLInstance } from "./instance";
 import assert from "assert";
 import { jsFunctionToMistQLFunction } from "./util";
@@ -10,6 +11,58 @@ describe("Instance", () => {
     });
   });
 
+  describe("#compile", () => {
+    it("should run a compiled query against different data", () => {
+      const instance = new MistQLInstance();
+      const compiled = instance.compile("@ | filter type == \"click\" | count");
+      assert.strictEqual(
+        compiled.run([{ type: "click" }, { type: "purchase" }]),
+        1
+      );
+      assert.strictEqual(
+        compiled.run([{ type: "click" }, { type: "click" }]),
+        2
+      );
+    });
+
+    it("should use the instance's extras", () => {
+      const instance = new MistQLInstance({
+        extras: {
+          double: (x) => x * 2,
+        },
+      });
+      const compiled = instance.compile("double @");
+      assert.strictEqual(compiled.run(2), 4);
+      assert.strictEqual(compiled.run(5), 10);
+    });
+
+    it("should reuse parsed queries", () => {
+      const instance = new MistQLInstance();
+      assert.strictEqual(
+        instance.compile("count @").ast,
+        instance.compile("count @").ast
+      );
+    });
+
+    it("should throw on invalid queries", () => {
+      const instance = new MistQLInstance();
+      assert.throws(() => instance.compile("count ("));
+    });
+  });
+
+  describe("now", () => {
+    it("should come from the instance's clock", () => {
+      const instance = new MistQLInstance({
+        now: () => new Date(Date.UTC(2021, 2, 4, 5, 6, 7, 890)),
+      });
+      assert.strictEqual(instance.query("now", null), "2021-03-04T05:06:07.890Z");
+      assert.strictEqual(
+        instance.query('truncdate "month" now', null),
+        "2021-03-01T00:00:00.000Z"
+      );
+    });
+  });
+
   describe("extras", () => {
     it("should allow for basic extra functions", () => {
       const instance = new MistQLInstance({
@@ -49,5 +102,20 @@ describe("Instance", () => {
         instance.query("intersperse 1 2 3", null);
       });
     });
+
+    it("should point runtime errors at the failing expression", () => {
+      const instance = new MistQLInstance();
+      assert.throws(
+        () => instance.query('[1, 2] | map (@ + "a")', null),
+        (err: RuntimeError) => {
+          assert.ok(err instanceof RuntimeError);
+          assert.deepStrictEqual(err.span, { start: 14, end: 21 });
+          assert.ok(err.message.endsWith(
+            ' at position 14\n---\n[1, 2] | map (@ + "a")\n              ^^^^^^^\n---\n'
+          ));
+          return true;
+        }
+      );
+    });
   });
 });
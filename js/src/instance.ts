@@ -1,6 +1,10 @@
+import { analyze } from "./analyze";
+import { formatIso } from "./dates";
+import { pointIntoQuery, RuntimeError } from "./errors";
 import { execute } from "./executor";
-import { parseOrThrow } from "./parser";
-import { FunctionClosure, FunctionValue } from "./types";
+import { Limits } from "./limits";
+import { parseCached } from "./parser";
+import { ASTExpression, FunctionClosure, FunctionValue } from "./types";
 import { jsFunctionToMistQLFunction } from "./util";
 
 type Extras = {
@@ -11,14 +15,69 @@ type Extras = {
       };
 };
 
+type Clock = () => Date;
+
+const systemClock: Clock = () => new Date();
+
+// Queries exceeding any of the limits throw a ResourceLimitError. now
+// returns the time that queries see as `now`, defaulting to the system clock.
 export type MistQLOptions = {
   extras?: Extras;
-};
+  now?: Clock;
+} & Limits;
+
+export class CompiledQuery {
+  readonly query: string;
+  readonly ast: ASTExpression;
+  _extras: FunctionClosure;
+  _limits: Limits;
+  _clock: Clock;
+
+  constructor(
+    query: string,
+    ast: ASTExpression,
+    extras?: FunctionClosure,
+    limits?: Limits,
+    clock: Clock = systemClock
+  ) {
+    this.query = query;
+    this.ast = ast;
+    this._extras = extras;
+    this._limits = limits;
+    this._clock = clock;
+  }
+
+  run = (data: any) => {
+    // now is read once per run, so it's the same everywhere in a query
+    const extras = Object.assign(
+      { now: formatIso(this._clock().getTime()) },
+      this._extras
+    );
+    try {
+      return execute(this.ast, data, extras, this._limits);
+    } catch (err) {
+      throw pointIntoQuery(err, this.query);
+    }
+  };
+
+  // Finds, without running this query, the errors certain to happen when each
+  // of its expressions is evaluated. The schema is an optional JSON Schema
+  // describing the data.
+  analyze = (schema?: unknown): RuntimeError[] =>
+    analyze(this.ast, schema, this._extras).map(
+      (err) => pointIntoQuery(err, this.query) as RuntimeError
+    );
+}
 
 export class MistQLInstance {
   _extras: FunctionClosure;
+  _limits: Limits;
+  _clock: Clock;
 
   constructor(options: MistQLOptions = {}) {
+    const { maxSteps, timeout, maxCollectionSize, maxDepth } = options;
+    this._limits = { maxSteps, timeout, maxCollectionSize, maxDepth };
+    this._clock = options.now ?? systemClock;
     if (options.extras) {
       this._extras = {};
       for (let i in options.extras) {
@@ -34,8 +93,18 @@ export class MistQLInstance {
     }
   }
 
+  compile = (query: string) => {
+    return new CompiledQuery(
+      query,
+      parseCached(query),
+      this._extras,
+      this._limits,
+      this._clock
+    );
+  };
+
   query = (query: string, data: any) => {
-    return execute(parseOrThrow(query), data, this._extras);
+    return this.compile(query).run(data);
   };
 }
 
@@ -60,6 +60,26 @@ describe("lexer", () => {
       assert.throws(() => lex('"sup'));
     });
 
+    it("should lex quoted identifiers", () => {
+      assert.deepStrictEqual(lex("@.`first-name` `null`"), [
+        { token: "ref", value: "@", position: 0 },
+        { token: "special", value: ".", position: 1 },
+        { token: "ref", value: "first-name", position: 2, quoted: true },
+        { token: "special", value: " ", position: 14 },
+        { token: "ref", value: "null", position: 15, quoted: true },
+      ]);
+      assert.throws(() => lex("`sup"));
+    });
+
+    it("should lex references to enclosing contexts", () => {
+      assert.deepStrictEqual(lex("@^^.id"), [
+        { token: "ref", value: "@^^", position: 0 },
+        { token: "special", value: ".", position: 3 },
+        { token: "ref", value: "id", position: 4 },
+      ]);
+      assert.throws(() => lex("$^"));
+    });
+
     it("trims whitespace", () => {
       assert.deepStrictEqual(lex("  @  "), [
         { token: "ref", value: "@", position: 2 },
@@ -5,8 +5,8 @@ import { LexToken } from "./types";
 // This defines how various tokens capture whitespace
 const whitespaceBehavior = {
   l: ")}]".split(""),
-  r: "({[".split(""),
-  rl: ".:|,".split("").concat(binaryExpressionStrings),
+  r: "({[".split("").concat(["..."]),
+  rl: ".:|,=".split("").concat(binaryExpressionStrings),
 };
 
 // gross hacky way to ensure that we can tell between string literal end and escaped quote;
@@ -102,11 +102,29 @@ export function lex(raw: string): LexToken[] {
         });
       }
     } else if (buffer === "@" || buffer === "$") {
+      // `@^` is the context enclosing `@`, `@^^` the one enclosing that
+      while (buffer[0] === "@" && raw[i + 1] === "^") {
+        i++;
+        buffer += raw[i];
+      }
       tokens.push({
         token: "ref",
         value: buffer,
         position,
       });
+    } else if (buffer === "`") {
+      // Backticks quote names that aren't valid identifiers, e.g. `first-name`
+      const end = raw.indexOf("`", i + 1);
+      if (end === -1) {
+        throw new LexError("Unterminated quoted identifier", position, raw);
+      }
+      tokens.push({
+        token: "ref",
+        value: raw.substring(i + 1, end),
+        position,
+        quoted: true,
+      });
+      i = end;
     } else if (buffer === '"') {
       buffer = "";
       while (raw[i + 1] !== '"') {
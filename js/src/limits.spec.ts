@@ -0,0 +1,74 @@
+import assert from "assert";
+import { ResourceLimitError } from "./errors";
+import { MistQLInstance } from "./instance";
+
+const throwsLimit = (fn: () => unknown, message: RegExp) =>
+  assert.throws(
+    fn,
+    (err: Error) => err instanceof ResourceLimitError && message.test(err.message)
+  );
+
+const down = "def down n = if (n > 0) (down (n - 1)) n in down @";
+
+describe("limits", () => {
+  it("runs without limits by default", () => {
+    assert.strictEqual(new MistQLInstance().query("range 10000 | count", null), 10000);
+  });
+
+  it("limits steps", () => {
+    const mq = new MistQLInstance({ maxSteps: 100 });
+    assert.strictEqual(mq.query("1 + 2", null), 3);
+    throwsLimit(() => mq.query("range 1000 | map @ + 1 | sum", null), /100 steps/);
+  });
+
+  it("limits time", () => {
+    const mq = new MistQLInstance({ timeout: 50 });
+    const query = "range 40 | sequence true true true true true | count";
+    throwsLimit(() => mq.query(query, null), /timeout/);
+  });
+
+  it("limits time within functions", () => {
+    const mq = new MistQLInstance({ timeout: 50 });
+    throwsLimit(() => mq.query("range 100000000 | count", null), /timeout/);
+    const data = Array.from({ length: 1000000 }, (_, i) => (i * 7919) % 1000003);
+    throwsLimit(() => mq.query("sort @ | count", data), /timeout/);
+  });
+
+  it("limits collection size", () => {
+    const mq = new MistQLInstance({ maxCollectionSize: 100 });
+    assert.strictEqual(mq.query("range 100 | count", null), 100);
+    throwsLimit(() => mq.query("range 100000000", null), /collection size/);
+    throwsLimit(() => mq.query("range 20 | sequence @ @ @ @", null), /collection size/);
+    throwsLimit(
+      () => mq.query("let s = @ + @ in let t = s + s in t + t", "a".repeat(30)),
+      /collection size/
+    );
+  });
+
+  it("limits call depth", () => {
+    const mq = new MistQLInstance({ maxDepth: 20 });
+    assert.strictEqual(mq.query(down, 3), 0);
+    throwsLimit(() => mq.query(down, 50), /depth of 20/);
+  });
+
+  it("reports running out of stack as a resource limit", () => {
+    throwsLimit(() => new MistQLInstance().query(down, 100000), /recursion depth/);
+  });
+
+  it("applies limits to each run", () => {
+    const compiled = new MistQLInstance({ maxSteps: 50 }).compile(
+      "range 5 | map @ * 2 | sum"
+    );
+    for (let i = 0; i < 3; i++) {
+      assert.strictEqual(compiled.run(null), 20);
+    }
+  });
+
+  it("points errors into the query", () => {
+    const mq = new MistQLInstance({ maxCollectionSize: 10 });
+    assert.throws(
+      () => mq.query("[1, 2] | map (range 100)", null),
+      (err: ResourceLimitError) => err.span.start === 14
+    );
+  });
+});
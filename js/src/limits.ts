@@ -0,0 +1,115 @@
+import { ResourceLimitError } from "./errors";
+
+// Caps on the work a single run of a query may do. Limits left undefined
+// aren't enforced.
+export type Limits = {
+  // The most steps a query may take. Each expression it evaluates is a step,
+  // as is each item a function works through.
+  maxSteps?: number;
+  // The most milliseconds a query may run for.
+  timeout?: number;
+  // The largest array, object, or string a function may return. Strings are
+  // measured in code points, so that the limit means the same as in Python.
+  maxCollectionSize?: number;
+  // The most deeply function calls may nest.
+  maxDepth?: number;
+};
+
+const hasLimits = (limits: Limits) =>
+  limits.maxSteps !== undefined ||
+  limits.timeout !== undefined ||
+  limits.maxCollectionSize !== undefined ||
+  limits.maxDepth !== undefined;
+
+// The resources left to a single run of a query.
+class Budget {
+  limits: Limits;
+  steps = 0;
+  depth = 0;
+  deadline?: number;
+
+  constructor(limits: Limits) {
+    this.limits = limits;
+    if (limits.timeout !== undefined) {
+      this.deadline = Date.now() + limits.timeout;
+    }
+  }
+
+  step() {
+    this.steps++;
+    const { maxSteps, timeout } = this.limits;
+    if (maxSteps !== undefined && this.steps > maxSteps) {
+      throw new ResourceLimitError(`Exceeded the limit of ${maxSteps} steps`);
+    }
+    if (this.deadline !== undefined && Date.now() > this.deadline) {
+      throw new ResourceLimitError(`Exceeded the timeout of ${timeout}ms`);
+    }
+  }
+
+  enter() {
+    this.depth++;
+    const { maxDepth } = this.limits;
+    if (maxDepth !== undefined && this.depth > maxDepth) {
+      throw new ResourceLimitError(
+        `Exceeded the maximum call depth of ${maxDepth}`
+      );
+    }
+  }
+
+  leave() {
+    this.depth--;
+  }
+
+  checkSize(size: number) {
+    const { maxCollectionSize } = this.limits;
+    if (maxCollectionSize !== undefined && size > maxCollectionSize) {
+      throw new ResourceLimitError(
+        `Exceeded the maximum collection size of ${maxCollectionSize}`
+      );
+    }
+  }
+}
+
+// The budget of the query that's running, if it has any limits
+let current: Budget | undefined;
+
+// Runs fn with a fresh budget, restoring the outer one after, so that
+// queries run from within extras get their own.
+export const withLimits = <T>(limits: Limits | undefined, fn: () => T): T => {
+  const outer = current;
+  current = limits && hasLimits(limits) ? new Budget(limits) : undefined;
+  try {
+    return fn();
+  } finally {
+    current = outer;
+  }
+};
+
+export const getBudget = () => current;
+
+// Counts one step towards the step limit, and checks the deadline.
+export const step = () => {
+  if (current) {
+    current.step();
+  }
+};
+
+// The length of a string in code points, counting each surrogate pair once.
+export const stringSize = (value: string) => {
+  let size = value.length;
+  for (let i = 1; i < value.length; i++) {
+    const code = value.charCodeAt(i);
+    const previous = value.charCodeAt(i - 1);
+    if (code >= 0xdc00 && code <= 0xdfff && previous >= 0xd800 && previous <= 0xdbff) {
+      size--;
+    }
+  }
+  return size;
+};
+
+// Checks the size of a collection that's about to be returned.
+export const checkSize = (size: number) => {
+  if (current) {
+    current.checkSize(size);
+  }
+};
@@ -84,11 +84,28 @@ describe("parser", () => {
       it("parses object literals", () => {
         assert.deepStrictEqual(
           parseOrThrow("{one: 1, two: 2, three: 3}"),
-          lit("object", {
-            one: lit("number", 1),
-            two: lit("number", 2),
-            three: lit("number", 3),
-          })
+          lit("object", [
+            { key: "one", value: lit("number", 1) },
+            { key: "two", value: lit("number", 2) },
+            { key: "three", value: lit("number", 3) },
+          ])
+        );
+      });
+
+      it("parses computed keys and spreads", () => {
+        assert.deepStrictEqual(
+          parseOrThrow("{(a): 1, ...b}"),
+          lit("object", [
+            {
+              key: { type: "parenthetical", expression: ref("a") },
+              value: lit("number", 1),
+            },
+            { type: "spread", value: ref("b") },
+          ])
+        );
+        assert.deepStrictEqual(
+          parseOrThrow("[1, ... a]"),
+          lit("array", [lit("number", 1), { type: "spread", value: ref("a") }])
         );
       });
     });
@@ -144,6 +161,16 @@ describe("parser", () => {
 
         assert.deepStrictEqual(parseOrThrow("@.hello.there"), target);
       });
+
+      it("parses lenient dot access", () => {
+        assert.deepStrictEqual(
+          parseOrThrow("@?.hello ?. there"),
+          app(ref("?.", true), [
+            app(ref("?.", true), [ref("@"), ref("hello")]),
+            ref("there"),
+          ])
+        );
+      });
     });
 
     describe("pipes", () => {
@@ -412,13 +439,16 @@ describe("parser", () => {
           arguments: [
             {
               type: "literal",
-              value: {
-                hello: {
-                  type: "literal",
-                  value: 1,
-                  valueType: "number",
+              value: [
+                {
+                  key: "hello",
+                  value: {
+                    type: "literal",
+                    value: 1,
+                    valueType: "number",
+                  },
                 },
-              },
+              ],
               valueType: "object",
             },
             {
@@ -450,7 +480,7 @@ describe("parser", () => {
           function: ref("index", true),
           arguments: [
             lit("string", "hello"),
-            lit("object", { hello: lit("string", "there") }),
+            lit("object", [{ key: "hello", value: lit("string", "there") }]),
           ],
         };
         assert.deepStrictEqual(
@@ -678,6 +708,15 @@ describe("parser", () => {
         };
         assert.deepStrictEqual(parseOrThrow("one - two - three"), expected);
       });
+      it("binds coalescing loosest", () => {
+        assert.deepStrictEqual(
+          parseOrThrow("one || two ?? three"),
+          app(ref("??", true), [
+            app(ref("||", true), [ref("one"), ref("two")]),
+            ref("three"),
+          ])
+        );
+      });
     });
     it("errors with incomplete binary expressions", () => {
       assert.throws(() => parseOrThrow("here +"));
@@ -686,4 +725,84 @@ describe("parser", () => {
       assert.throws(() => parseOrThrow("a | b |"));
     });
   });
+
+  describe("let bindings", () => {
+    it("parses a binding", () => {
+      assert.deepStrictEqual(parseOrThrow("let x = f 1 in x"), {
+        type: "let",
+        name: "x",
+        value: {
+          type: "application",
+          function: ref("f"),
+          arguments: [lit("number", 1)],
+          _shouldntWrapInPipedExpressions: true,
+        },
+        body: ref("x"),
+      });
+    });
+
+    it("treats let and in as references elsewhere", () => {
+      assert.deepStrictEqual(parseOrThrow("let"), ref("let"));
+      assert.deepStrictEqual(parseOrThrow("let x = (f in) in x"), {
+        type: "let",
+        name: "x",
+        value: par({
+          type: "application",
+          function: ref("f"),
+          arguments: [ref("in")],
+          _shouldntWrapInPipedExpressions: true,
+        }),
+        body: ref("x"),
+      });
+    });
+  });
+
+  describe("function definitions", () => {
+    it("parses a definition as a binding to a function", () => {
+      assert.deepStrictEqual(parseOrThrow("def f x y = x in f"), {
+        type: "let",
+        name: "f",
+        value: {
+          type: "lambda",
+          name: "f",
+          params: ["x", "y"],
+          body: ref("x"),
+        },
+        body: ref("f"),
+      });
+    });
+
+    it("treats def as a reference elsewhere", () => {
+      assert.deepStrictEqual(parseOrThrow("def"), ref("def"));
+      assert.throws(() => parseOrThrow("def f = 1 in f"));
+    });
+  });
+
+  describe("spans", () => {
+    const text = (query: string, ast: ASTExpression) =>
+      query.substring(ast.span.start, ast.span.end);
+
+    it("records the span of every expression", () => {
+      const query = "[1, 2] | map (@ + 1)";
+      const ast = parseOrThrow(query);
+      assert.strictEqual(text(query, ast), query);
+      if (ast.type !== "pipeline") {
+        throw new Error("Expected a pipeline");
+      }
+      assert.strictEqual(text(query, ast.stages[0]), "[1, 2]");
+      assert.strictEqual(text(query, ast.stages[1]), "map (@ + 1)");
+    });
+
+    it("includes postfix and unary operators", () => {
+      assert.strictEqual(text("-a.b[0]", parseOrThrow("-a.b[0]")), "-a.b[0]");
+    });
+
+    it("doesn't make spans part of the AST's structure", () => {
+      assert.deepStrictEqual(Object.keys(parseOrThrow("a + b")), [
+        "type",
+        "function",
+        "arguments",
+      ]);
+    });
+  });
 });
@@ -6,7 +6,15 @@ import {
 } from "./constants";
 import { OpenAnIssueIfThisOccursError, ParseError, UnpositionableParseError } from "./errors";
 import { lex } from "./lexer";
-import { ASTApplicationExpression, ASTExpression, LexToken } from "./types";
+import {
+  ASTApplicationExpression,
+  ASTExpression,
+  ASTObjectEntry,
+  ASTReferenceExpression,
+  ASTSpreadExpression,
+  LexToken,
+  Span,
+} from "./types";
 
 /*
  * To all who dare enter:
@@ -14,16 +22,34 @@ import { ASTApplicationExpression, ASTExpression, LexToken } from "./types";
  * Please refactor it if you can, but keep bundle size down to a minimum.
  */
 
+// Spans are non-enumerable, so that ASTs can still be compared structurally.
+const withSpan = <T extends ASTExpression | ASTSpreadExpression>(node: T, span: Span | undefined): T => {
+  if (span) {
+    Object.defineProperty(node, "span", {
+      value: span,
+      enumerable: false,
+      writable: true,
+      configurable: true,
+    });
+  }
+  return node;
+};
+
+const joinSpans = (first: ASTExpression, last: ASTExpression) =>
+  first.span && last.span
+    ? { start: first.span.start, end: last.span.end }
+    : undefined;
+
 const amalgamationTechniques: {
   [key: string]: (start: ASTExpression[]) => ASTExpression;
 } = {
-  " ": (asts) => ({
+  " ": (asts) => withSpan({
     type: "application",
     function: asts[0],
     arguments: asts.slice(1),
     _shouldntWrapInPipedExpressions: true,
-  }),
-  "|": (asts) => ({
+  }, joinSpans(asts[0], asts[asts.length - 1])),
+  "|": (asts) => withSpan({
     type: "pipeline",
     stages: [].concat(
       asts.slice(0, 1),
@@ -34,14 +60,14 @@ const amalgamationTechniques: {
         ) {
           return expr;
         }
-        return {
+        return withSpan({
           type: "application",
           function: expr,
           arguments: [],
-        };
+        }, expr.span);
       })
     ),
-  }),
+  }, joinSpans(asts[0], asts[asts.length - 1])),
 };
 
 type ParseResult = {
@@ -51,8 +77,32 @@ type ParseResult = {
 
 type ParseContext = {
   rawQuery: string;
+  // Whether a whitespace separated `in` ends the current expression, as it
+  // does in the value of a let-binding
+  inLetValue?: boolean;
+  // How many expressions the parser is currently inside of
+  depth: number;
 };
 
+// How deeply expressions may nest, e.g. in brackets, before parsing fails.
+// Each level recurses through the parser, so deeper input could overflow the
+// stack.
+const MAX_NESTING = 64;
+
+// Fails at the token that would have nested too deeply.
+const nestingTooDeep = (tokens: LexToken[], index: number, ctx: ParseContext) =>
+  new ParseError(
+    "Exceeded the maximum nesting depth",
+    index < tokens.length ? tokens[index].position : ctx.rawQuery.length,
+    ctx.rawQuery
+  );
+
+// Within brackets, `in` can't end a let-binding's value
+const nested = (ctx: ParseContext): ParseContext => ({
+  ...ctx,
+  inLetValue: false,
+});
+
 type Parser = (
   tokens: LexToken[],
   offset: number,
@@ -65,8 +115,14 @@ type ParameterizedParser = (
   ctx: ParseContext
 ) => ParseResult;
 
+// Quoted refs never match, as they are never keywords
 const tmatch = (token: string, value: unknown, root: LexToken) => {
-  return root !== undefined && root.token === token && root.value === value;
+  return (
+    root !== undefined &&
+    root.token === token &&
+    root.value === value &&
+    !(root.token === "ref" && root.quoted)
+  );
 };
 
 const buildTMatchThrower =
@@ -77,9 +133,62 @@ const buildTMatchThrower =
     }
   };
 
-const tmatchOrThrow = buildTMatchThrower(ParseError);
 const tmatchOrThrowBad = buildTMatchThrower(OpenAnIssueIfThisOccursError);
 
+// Running off the end of the token stream is reported as an unexpected EOF.
+const unexpectedToken = (
+  root: LexToken | undefined,
+  ctx: ParseContext,
+  expected?: string
+) => {
+  const suffix = expected ? ", expected " + expected : "";
+  if (root === undefined) {
+    return new ParseError(
+      "Unexpected EOF" + suffix,
+      ctx.rawQuery.length,
+      ctx.rawQuery
+    );
+  }
+  return new ParseError(
+    "Unexpected token " + root.value + suffix,
+    root.position,
+    ctx.rawQuery
+  );
+};
+
+const tmatchOrThrow = (
+  token: string,
+  value: unknown,
+  root: LexToken,
+  ctx: ParseContext
+) => {
+  if (!tmatch(token, value, root)) {
+    throw unexpectedToken(root, ctx, String(value));
+  }
+};
+
+// Tokens only record where they start, so a token ends where the next one
+// begins, less any whitespace in between.
+const tokenEnd = (tokens: LexToken[], index: number, ctx: ParseContext) => {
+  const start = tokens[index].position;
+  let end =
+    index + 1 < tokens.length ? tokens[index + 1].position : ctx.rawQuery.length;
+  while (end > start + 1 && /\s/.test(ctx.rawQuery[end - 1])) {
+    end--;
+  }
+  return end;
+};
+
+const tokenSpan = (
+  tokens: LexToken[],
+  first: number,
+  last: number,
+  ctx: ParseContext
+): Span => ({
+  start: tokens[first].position,
+  end: tokenEnd(tokens, last, ctx),
+});
+
 const isBinExp = (token: LexToken) => {
   const res =
     token.token === "special" &&
@@ -94,9 +203,14 @@ const isUnExp = (token: LexToken) => {
 };
 
 const consumeParenthetical: Parser = (tokens, offset, ctx) => {
+  const start = offset;
   tmatchOrThrowBad("special", "(", tokens[offset]);
   offset++;
-  const { result, offset: nextOffset } = consumeExpression(tokens, offset, ctx);
+  const { result, offset: nextOffset } = consumeExpression(
+    tokens,
+    offset,
+    nested(ctx)
+  );
   offset = nextOffset;
   if (!tokens[offset]) {
     throw new ParseError(
@@ -105,31 +219,58 @@ const consumeParenthetical: Parser = (tokens, offset, ctx) => {
       ctx.rawQuery
     );
   }
-  tmatchOrThrow("special", ")", tokens[offset]);
+  tmatchOrThrow("special", ")", tokens[offset], ctx);
   offset++;
   return {
-    result: {
+    result: withSpan({
       type: "parenthetical",
       expression: result,
-    },
+    }, tokenSpan(tokens, start, offset - 1, ctx)),
+    offset,
+  };
+};
+
+// `...value` within an array or object literal
+const consumeSpread = (
+  tokens: LexToken[],
+  offset: number,
+  ctx: ParseContext
+): { result: ASTSpreadExpression; offset: number } => {
+  const start = offset;
+  tmatchOrThrowBad("special", "...", tokens[offset]);
+  offset++;
+  const { result: value, offset: newOffset } = consumeExpression(
+    tokens,
     offset,
+    nested(ctx)
+  );
+  return {
+    result: withSpan(
+      { type: "spread", value },
+      tokenSpan(tokens, start, newOffset - 1, ctx)
+    ),
+    offset: newOffset,
   };
 };
 
 const consumeArray: Parser = (tokens, offset, ctx) => {
+  const start = offset;
   tmatchOrThrowBad("special", "[", tokens[offset]);
   offset++;
-  let entries: ASTExpression[] = [];
+  let entries: Array<ASTExpression | ASTSpreadExpression> = [];
   // dirty explicit check for an empty array -- should be fixed up
   while (true) {
     if (tmatch("special", "]", tokens[offset])) {
       offset++;
       break;
     }
-    const { result, offset: newOffset } = consumeExpression(
+    const consumeItem = tmatch("special", "...", tokens[offset])
+      ? consumeSpread
+      : consumeExpression;
+    const { result, offset: newOffset } = consumeItem(
       tokens,
       offset,
-      ctx
+      nested(ctx)
     );
     entries.push(result);
     offset = newOffset;
@@ -140,24 +281,21 @@ const consumeArray: Parser = (tokens, offset, ctx) => {
       offset++;
       break;
     } else {
-      throw new ParseError(
-        "Unexpected token " + tokens[offset].value,
-        tokens[offset].position,
-        ctx.rawQuery
-      );
+      throw unexpectedToken(tokens[offset], ctx);
     }
   }
   return {
-    result: {
+    result: withSpan({
       type: "literal",
       valueType: "array",
       value: entries,
-    },
+    }, tokenSpan(tokens, start, offset - 1, ctx)),
     offset,
   };
 };
 
 const consumeIndexer: Parser = (tokens, offset, ctx) => {
+  const start = offset;
   tmatchOrThrowBad("special", "[", tokens[offset]);
   offset++;
   let entries: ASTExpression[] = [];
@@ -183,7 +321,7 @@ const consumeIndexer: Parser = (tokens, offset, ctx) => {
     const { result, offset: newOffset } = consumeExpression(
       tokens,
       offset,
-      ctx
+      nested(ctx)
     );
     entries.push(result);
     offset = newOffset;
@@ -194,15 +332,12 @@ const consumeIndexer: Parser = (tokens, offset, ctx) => {
       offset++;
       break;
     } else {
-      throw new ParseError(
-        "Unexpected token " + tokens[offset].value,
-        tokens[offset].position,
-        ctx.rawQuery
-      );
+      throw unexpectedToken(tokens[offset], ctx);
     }
   }
 
-  const app: ASTExpression = {
+  // Only spans the brackets, as the indexed expression is added later
+  const app: ASTExpression = withSpan({
     type: "application",
     function: {
       type: "reference",
@@ -210,7 +345,7 @@ const consumeIndexer: Parser = (tokens, offset, ctx) => {
       internal: true,
     },
     arguments: entries,
-  };
+  }, tokenSpan(tokens, start, offset - 1, ctx));
   return {
     result: app,
     offset,
@@ -218,9 +353,10 @@ const consumeIndexer: Parser = (tokens, offset, ctx) => {
 };
 
 const consumeStruct: Parser = (tokens, offset, ctx) => {
+  const start = offset;
   tmatchOrThrowBad("special", "{", tokens[offset]);
   offset++;
-  let entries: { [key: string]: ASTExpression } = {};
+  let entries: ASTObjectEntry[] = [];
   while (true) {
     if (tmatch("special", "}", tokens[offset])) {
       offset++;
@@ -229,26 +365,40 @@ const consumeStruct: Parser = (tokens, offset, ctx) => {
     if (tokens[offset] === undefined) {
       throw new ParseError("Unexpected EOF", ctx.rawQuery.length, ctx.rawQuery);
     }
-    let key: string;
-    if (tokens[offset].token === "ref" || tokens[offset].token === "value") {
-      key = tokens[offset].value.toString();
-      offset++;
+    if (tmatch("special", "...", tokens[offset])) {
+      const { result, offset: newOffset } = consumeSpread(tokens, offset, ctx);
+      entries.push(result);
+      offset = newOffset;
     } else {
-      throw new ParseError(
-        "Unexpected token " + tokens[offset].value,
-        tokens[offset].position,
-        ctx.rawQuery
+      let key: string | ASTExpression;
+      if (tmatch("special", "(", tokens[offset])) {
+        // Computed keys, e.g. `{(name): 1}`
+        const { result, offset: newOffset } = consumeParenthetical(
+          tokens,
+          offset,
+          ctx
+        );
+        key = result;
+        offset = newOffset;
+      } else if (
+        tokens[offset].token === "ref" ||
+        tokens[offset].token === "value"
+      ) {
+        key = tokens[offset].value.toString();
+        offset++;
+      } else {
+        throw unexpectedToken(tokens[offset], ctx);
+      }
+      tmatchOrThrow("special", ":", tokens[offset], ctx);
+      offset++;
+      const { result, offset: newOffset } = consumeExpression(
+        tokens,
+        offset,
+        nested(ctx)
       );
+      offset = newOffset;
+      entries.push({ key, value: result });
     }
-    tmatchOrThrow("special", ":", tokens[offset]);
-    offset++;
-    const { result, offset: newOffset } = consumeExpression(
-      tokens,
-      offset,
-      ctx
-    );
-    offset = newOffset;
-    entries[key] = result;
     if (tmatch("special", ",", tokens[offset])) {
       offset++;
       continue;
@@ -256,49 +406,164 @@ const consumeStruct: Parser = (tokens, offset, ctx) => {
       offset++;
       break;
     } else {
-      throw new ParseError(
-        "Unexpected token " + tokens[offset].value,
-        tokens[offset].position,
-        ctx.rawQuery
-      );
+      throw unexpectedToken(tokens[offset], ctx);
     }
   }
   return {
-    result: {
+    result: withSpan({
       type: "literal",
       valueType: "object",
       value: entries,
-    },
+    }, tokenSpan(tokens, start, offset - 1, ctx)),
     offset,
   };
 };
 
+// Handles both `.` and its lenient form `?.`, which gives null on non-objects
 const consumeDotAccess: ParameterizedParser = (left, tokens, offset, ctx) => {
-  tmatchOrThrowBad("special", ".", tokens[offset]);
+  const accessor = tokens[offset].value as string;
+  if (accessor !== "?.") {
+    tmatchOrThrowBad("special", ".", tokens[offset]);
+  }
   offset++;
   let ref: string;
   let refToken = tokens[offset];
   if (!refToken || refToken.token !== "ref") {
-    throw new ParseError(
-      "Unexpected token " + tokens[offset].value + ", expected :",
-      tokens[offset].position,
-      ctx.rawQuery
-    );
+    throw unexpectedToken(refToken, ctx, "reference");
   }
   ref = refToken.value;
+  const refSpan = tokenSpan(tokens, offset, offset, ctx);
   offset++;
-  const result: ASTExpression = {
+  const result: ASTExpression = withSpan({
     type: "application",
     function: {
       type: "reference",
-      ref: ".",
+      ref: accessor,
       internal: true,
     },
-    arguments: [left, { type: "reference", ref: ref }],
-  };
+    arguments: [left, withSpan({ type: "reference", ref: ref }, refSpan)],
+  }, left.span && { start: left.span.start, end: refSpan.end });
+  return { result, offset };
+};
+
+const isBinding = (token: LexToken | undefined) =>
+  token !== undefined &&
+  token.token === "ref" &&
+  !token.quoted &&
+  token.value[0] !== "@" &&
+  token.value !== "$";
+
+// `let` is only a keyword when followed by a binding, so references named
+// `let` keep working
+const isLet = (tokens: LexToken[], offset: number) => {
+  return (
+    tmatch("ref", "let", tokens[offset]) &&
+    tmatch("special", " ", tokens[offset + 1]) &&
+    isBinding(tokens[offset + 2]) &&
+    tmatch("special", "=", tokens[offset + 3])
+  );
+};
+
+// The parameters of the function defined at offset, if there is one
+const defParams = (tokens: LexToken[], offset: number): string[] | null => {
+  if (
+    !tmatch("ref", "def", tokens[offset]) ||
+    !tmatch("special", " ", tokens[offset + 1]) ||
+    !isBinding(tokens[offset + 2])
+  ) {
+    return null;
+  }
+  const params: string[] = [];
+  let distance = 3;
+  while (
+    tmatch("special", " ", tokens[offset + distance]) &&
+    isBinding(tokens[offset + distance + 1])
+  ) {
+    params.push(tokens[offset + distance + 1].value as string);
+    distance += 2;
+  }
+  if (
+    params.length === 0 ||
+    !tmatch("special", "=", tokens[offset + distance])
+  ) {
+    return null;
+  }
+  return params;
+};
+
+// Consumes the value of a binding, along with the `in` that ends it
+const consumeLetValue: Parser = (tokens, offset, ctx) => {
+  const { result, offset: valueOffset } = consumeExpression(tokens, offset, {
+    ...ctx,
+    inLetValue: true,
+  });
+  offset = valueOffset;
+  if (
+    !tmatch("special", " ", tokens[offset]) ||
+    !tmatch("ref", "in", tokens[offset + 1])
+  ) {
+    const found = tmatch("special", " ", tokens[offset])
+      ? tokens[offset + 1]
+      : tokens[offset];
+    throw unexpectedToken(found, ctx, "in");
+  }
+  offset += 2;
+  if (tmatch("special", " ", tokens[offset])) {
+    offset++;
+  }
   return { result, offset };
 };
 
+const consumeLet: Parser = (tokens, offset, ctx) => {
+  const start = tokens[offset].position;
+  const name = tokens[offset + 2].value as string;
+  const { result: value, offset: valueOffset } = consumeLetValue(
+    tokens,
+    offset + 4,
+    ctx
+  );
+  const { result: body, offset: bodyOffset } = consumeExpression(
+    tokens,
+    valueOffset,
+    ctx
+  );
+  const result: ASTExpression = withSpan(
+    { type: "let", name, value, body },
+    body.span && { start, end: body.span.end }
+  );
+  return { result, offset: bodyOffset };
+};
+
+// `def f x = value in body` is shorthand for binding f to a function
+const consumeDef = (
+  tokens: LexToken[],
+  offset: number,
+  ctx: ParseContext,
+  params: string[]
+) => {
+  const start = tokens[offset].position;
+  const name = tokens[offset + 2].value as string;
+  const { result: value, offset: valueOffset } = consumeLetValue(
+    tokens,
+    offset + 4 + 2 * params.length,
+    ctx
+  );
+  const { result: body, offset: bodyOffset } = consumeExpression(
+    tokens,
+    valueOffset,
+    ctx
+  );
+  const lambda: ASTExpression = withSpan(
+    { type: "lambda", name, params, body: value },
+    value.span && { start, end: value.span.end }
+  );
+  const result: ASTExpression = withSpan(
+    { type: "let", name, value: lambda, body },
+    body.span && { start, end: body.span.end }
+  );
+  return { result, offset: bodyOffset };
+};
+
 // This might be the worst function i've ever written.
 // But at least it's a contained transformation.
 type BinaryExpressionSequence = { items: ASTExpression[]; joiners: string[] };
@@ -331,7 +596,7 @@ const turnBinaryExpressionSequenceIntoASTExpression = (
       if (currentPrecedenceLevel.indexOf(current.joiners[j]) > -1) {
         const l = newItems[newItems.length - 2];
         const r = newItems[newItems.length - 1];
-        newItems[newItems.length - 2] = {
+        newItems[newItems.length - 2] = withSpan({
           type: "application",
           function: {
             type: "reference",
@@ -339,7 +604,7 @@ const turnBinaryExpressionSequenceIntoASTExpression = (
             internal: true,
           },
           arguments: [l, r],
-        };
+        }, joinSpans(l, r));
         newItems.length = newItems.length - 1;
       } else {
         newJoiners.push(current.joiners[j]);
@@ -392,7 +657,18 @@ const turnBinaryExpressionSequenceIntoASTExpression = (
   return current.items[0];
 };
 
-const consumeExpression: Parser = (tokens, offset, ctx) => {
+const consumeExpression: Parser = (tokens, offset, outerCtx) => {
+  if (outerCtx.depth === MAX_NESTING) {
+    throw nestingTooDeep(tokens, offset, outerCtx);
+  }
+  const ctx = { ...outerCtx, depth: outerCtx.depth + 1 };
+  if (isLet(tokens, offset)) {
+    return consumeLet(tokens, offset, ctx);
+  }
+  const params = defParams(tokens, offset);
+  if (params !== null) {
+    return consumeDef(tokens, offset, ctx, params);
+  }
   let items: ASTExpression[] = [];
   let joiners: LexToken[] = [];
 
@@ -439,11 +715,18 @@ const consumeExpression: Parser = (tokens, offset, ctx) => {
         }
       }
       const unaries = tokens.slice(offset, i);
+      // Each unary operator nests its operand a level deeper
+      if (ctx.depth + unaries.length > MAX_NESTING) {
+        throw nestingTooDeep(tokens, offset + MAX_NESTING - ctx.depth + 1, ctx);
+      }
       offset = i;
       next = tokens[offset];
+      if (next === undefined) {
+        throw unexpectedToken(next, ctx, "expression");
+      }
       hackyUnaryPostProcess = (item) =>
         unaries.reduceRight(
-          (acc, cur) => ({
+          (acc, cur) => withSpan({
             type: "application",
             function: {
               type: "reference",
@@ -451,7 +734,7 @@ const consumeExpression: Parser = (tokens, offset, ctx) => {
               internal: true,
             },
             arguments: [acc],
-          }),
+          }, item.span && { start: cur.position, end: item.span.end }),
           item
         );
     }
@@ -465,9 +748,13 @@ const consumeExpression: Parser = (tokens, offset, ctx) => {
       );
       items.push(result);
       offset = newOffset;
-    } else if (tmatch("special", ".", next)) {
+    } else if (tmatch("special", ".", next) || tmatch("special", "?.", next)) {
       if (items.length === 0) {
-        throw new ParseError("Unexpected Token .", next.position, ctx.rawQuery);
+        throw new ParseError(
+          "Unexpected Token " + next.value,
+          next.position,
+          ctx.rawQuery
+        );
       }
       const { result, offset: newOffset } = consumeDotAccess(
         items.pop(),
@@ -492,13 +779,13 @@ const consumeExpression: Parser = (tokens, offset, ctx) => {
           offset,
           ctx
         );
-        items[items.length - 1] = {
+        items[items.length - 1] = withSpan({
           type: "application",
           function: (app as ASTApplicationExpression).function,
           arguments: (app as ASTApplicationExpression).arguments.concat([
             items[items.length - 1],
           ]),
-        };
+        }, joinSpans(items[items.length - 1], app));
         offset = newOffset;
       }
     } else if (tmatch("special", "{", next)) {
@@ -508,18 +795,32 @@ const consumeExpression: Parser = (tokens, offset, ctx) => {
       offset = newOffset;
     } else if (next.token === "value") {
       itemPushGuard(next);
-      items.push({
+      items.push(withSpan({
         type: "literal",
         valueType: next.value !== null ? (typeof next.value as any) : "null",
         value: next.value,
-      });
+      }, tokenSpan(tokens, offset, offset, ctx)));
       offset++;
+    } else if (
+      ctx.inLetValue &&
+      tmatch("ref", "in", next) &&
+      joiners.length === items.length &&
+      tmatch("special", " ", tokens[offset - 1])
+    ) {
+      // `in` ends a let-binding's value, so give back the whitespace before it
+      joiners.pop();
+      offset--;
+      break;
     } else if (next.token === "ref") {
       itemPushGuard(next);
-      items.push({
+      const ref: ASTReferenceExpression = {
         type: "reference",
         ref: next.value,
-      });
+      };
+      if (next.quoted) {
+        ref.quoted = true;
+      }
+      items.push(withSpan(ref, tokenSpan(tokens, offset, offset, ctx)));
       offset++;
     } else if (isBinExp(next) && !hackyUnaryPostProcess) {
       joinerPushGuard(next);
@@ -548,13 +849,13 @@ const consumeExpression: Parser = (tokens, offset, ctx) => {
       offset,
       ctx
     );
-    resolvedSequence = {
+    resolvedSequence = withSpan({
       type: "application",
       function: (app as ASTApplicationExpression).function,
       arguments: (app as ASTApplicationExpression).arguments.concat([
         resolvedSequence,
       ]),
-    };
+    }, joinSpans(resolvedSequence, app));
     offset = nextOffset;
   }
 
@@ -584,9 +885,31 @@ function parse(raw: string): ASTExpression {
   const lexed = lex(raw);
   const ctx: ParseContext = {
     rawQuery: raw,
+    depth: 0,
   }
   const parsed = parseQuery(lexed, ctx);
   return parsed;
 }
 
 export const parseOrThrow = parse;
+
+// Parsing dominates the cost of running small queries, so parsed ASTs are
+// cached by query string. ASTs are never mutated during execution, so sharing
+// them between calls is safe.
+export const PARSE_CACHE_SIZE = 1024;
+const parseCache = new Map<string, ASTExpression>();
+
+export function parseCached(raw: string): ASTExpression {
+  let ast = parseCache.get(raw);
+  if (ast !== undefined) {
+    // Reinsert, so that Map's insertion order tracks recency of use.
+    parseCache.delete(raw);
+  } else {
+    ast = parse(raw);
+    if (parseCache.size >= PARSE_CACHE_SIZE) {
+      parseCache.delete(parseCache.keys().next().value);
+    }
+  }
+  parseCache.set(raw, ast);
+  return ast;
+}
@@ -118,6 +118,34 @@ describe("mistql performance", () => {
       );
     });
 
+    it("pushes wide objects onto the stack as cheaply as narrow ones", () => {
+      const items = (width: number) => {
+        const item = {};
+        for (let i = 0; i < width; i++) {
+          item["field" + i] = i;
+        }
+        return { items: new Array(200).fill(item) };
+      };
+      const narrow = items(5);
+      const wide = items(500);
+      const query = mistql.compile(
+        "range 50 | map (items | filter field1 > 0 | count) | sum"
+      );
+      // Reading in the data copies it, which is proportional to its size, so
+      // the time a trivial query takes on the same data is subtracted out.
+      const baseline = mistql.compile("1");
+      const pushTime = (data: unknown, name: string) =>
+        time(() => query.run(data), { name, iterations: 20 }).median -
+        time(() => baseline.run(data), {
+          name: name + " baseline",
+          iterations: 20,
+        }).median;
+
+      const narrowTime = pushTime(narrow, "5 field objects");
+      const wideTime = pushTime(wide, "500 field objects");
+      assert.ok(wideTime < narrowTime * 3);
+    }).timeout(10000);
+
     it("has low fixed cost to do a query", () => {
       assertTime(
         () => {
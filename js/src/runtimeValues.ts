@@ -1,4 +1,4 @@
-import { OpenAnIssueIfThisOccursError } from "./errors";
+import { OpenAnIssueIfThisOccursError, RuntimeError } from "./errors";
 import { RuntimeValue, RuntimeValueType } from "./types";
 
 export const truthy = (runtimeValue: RuntimeValue): boolean => {
@@ -28,7 +28,7 @@ export const castToString = (value: RuntimeValue): RuntimeValue => {
   if (type === "string") {
     return value;
   } else if (type === "regex" || typeof value === "function") {
-    throw new Error("Cannot cast type " + type + " to string");
+    throw new RuntimeError("Cannot cast type " + type + " to string");
   } else {
     return JSON.stringify(value);
   }
@@ -48,7 +48,7 @@ export const castToFloat = (value: RuntimeValue): RuntimeValue => {
     if (value.match(validNumberFormat)) {
       return parseFloat(value);
     } else {
-      throw new Error("Cannot cast string to float: " + value);
+      throw new RuntimeError("Cannot cast string to float: " + value);
     }
   } else if (
     type === "regex" ||
@@ -56,7 +56,7 @@ export const castToFloat = (value: RuntimeValue): RuntimeValue => {
     type == "object" ||
     type === "array"
   ) {
-    throw new Error("Cannot cast type " + type + " to float");
+    throw new RuntimeError("Cannot cast type " + type + " to float");
   } else {
     return +value;
   }
@@ -88,16 +88,16 @@ export const comparable = (a: RuntimeValue) => {
 export const compare = (a: RuntimeValue, b: RuntimeValue): number => {
   const varType = getType(a);
   if (varType !== getType(b)) {
-    throw new Error("Comparison ill-defined between different variable types");
+    throw new RuntimeError("Comparison ill-defined between different variable types");
   }
   if (varType === "array") {
-    throw new Error("Comparison between arrays not permitted");
+    throw new RuntimeError("Comparison between arrays not permitted");
   } else if (varType === "object") {
-    throw new Error("Comparison between objects not permitted");
+    throw new RuntimeError("Comparison between objects not permitted");
   } else if (varType === "regex") {
-    throw new Error("Comparison between regexes not permitted");
+    throw new RuntimeError("Comparison between regexes not permitted");
   } else if (varType === "null") {
-    throw new Error("Comparison between nulls not permitted");
+    throw new RuntimeError("Comparison between nulls not permitted");
   } else if (varType === "number") {
     return b - a;
   } else if (varType === "boolean") {
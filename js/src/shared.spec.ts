@@ -1,5 +1,6 @@
 import assert from 'assert';
-import { query } from '.';
+import { MistQLInstance } from '.';
+import { LexError, ParseError, UnpositionableParseError } from './errors';
 import testdata from './shared/testdata.json';
 
 
@@ -13,7 +14,19 @@ describe("Shared tests", () => {
           innerblock.cases.forEach((testcase) => {
             const testCb = () => {
               testcase.assertions.forEach((assertion) => {
-                if (assertion.throws) {
+                const query = (queryString: string, data: unknown) =>
+                  new MistQLInstance(assertion.limits).query(queryString, data);
+                if (assertion.throws === "parse") {
+                  assert.throws(
+                    () => {
+                      query(assertion.query, assertion.data);
+                    },
+                    (err) =>
+                      err instanceof LexError ||
+                      err instanceof ParseError ||
+                      err instanceof UnpositionableParseError
+                  );
+                } else if (assertion.throws) {
                   assert.throws(() => {
                     query(assertion.query, assertion.data);
                   });
@@ -1,21 +1,103 @@
-import { getProperties } from "./runtimeValues";
-import { RuntimeValue, Stack } from "./types";
+import { getType } from "./runtimeValues";
+import { Closure, RuntimeValue, Stack } from "./types";
 
-const refRegex = /^[a-zA-Z_][a-zA-Z_0-9]*$/;
+// A stack frame binding `@` to a value, and each field of the value to its
+// name. Fields are looked up in the value as needed, rather than copied, so
+// pushing a wide object is as cheap as pushing a number.
+export class ValueFrame {
+  readonly value: RuntimeValue;
+
+  constructor(value: RuntimeValue) {
+    this.value = value;
+  }
+
+  get(name: string): RuntimeValue {
+    if (name === "@") {
+      return this.value;
+    }
+    // Fields can be named anything, by quoting them in backticks, but can't
+    // shadow the root reference
+    if (
+      getType(this.value) === "object" &&
+      name !== "$" &&
+      Object.prototype.hasOwnProperty.call(this.value, name)
+    ) {
+      return this.value[name];
+    }
+    return undefined;
+  }
+}
 
 export const pushRuntimeValueToStack = (
   runtimeValue: RuntimeValue,
   stack: Stack
 ): Stack => {
-  let nextEntry = {
-    "@": runtimeValue,
-  };
-  getProperties(runtimeValue).forEach((key) => {
-    if (refRegex.test(key)) {
-      nextEntry[key] = runtimeValue[key];
-    }
-  });
   const nextStack = stack.slice();
-  nextStack.push(nextEntry);
+  nextStack.push(new ValueFrame(runtimeValue));
+  return nextStack;
+};
+
+export const pushBindingsToStack = (
+  bindings: Closure,
+  stack: Stack
+): Stack => {
+  const nextStack = stack.slice();
+  nextStack.push(bindings);
   return nextStack;
 };
+
+// The value of `@` some number of contexts out, one for each caret in the
+// name. Every pipeline stage and every item a function evaluates an expression
+// against is a context of its own.
+const findEnclosingContext = (stack: Stack, name: string): RuntimeValue => {
+  let depth = name.length - 1;
+  for (let i = stack.length - 1; i >= 0; i--) {
+    const frame = stack[i];
+    if (frame instanceof ValueFrame) {
+      if (depth === 0) {
+        return frame.value;
+      }
+      depth--;
+    }
+  }
+  return undefined;
+};
+
+// A field named like a context, as `@` and `@^` can be when quoted in
+// backticks. Only contexts can hold such a name, so only their fields are
+// searched.
+const findContextField = (stack: Stack, name: string): RuntimeValue => {
+  for (let i = stack.length - 1; i >= 0; i--) {
+    const frame = stack[i];
+    if (
+      frame instanceof ValueFrame &&
+      getType(frame.value) === "object" &&
+      Object.prototype.hasOwnProperty.call(frame.value, name)
+    ) {
+      return frame.value[name];
+    }
+  }
+  return undefined;
+};
+
+// The value bound to a name, or undefined if nothing in the stack binds it.
+export const findInStack = (
+  stack: Stack,
+  name: string,
+  quoted = false
+): RuntimeValue => {
+  if (quoted && name.startsWith("@")) {
+    return findContextField(stack, name);
+  }
+  if (name.startsWith("@^")) {
+    return findEnclosingContext(stack, name);
+  }
+  for (let i = stack.length - 1; i >= 0; i--) {
+    const frame = stack[i];
+    const value = frame instanceof ValueFrame ? frame.get(name) : frame[name];
+    if (value !== undefined) {
+      return value;
+    }
+  }
+  return undefined;
+};
@@ -1,4 +1,13 @@
+import type { ValueFrame } from "./stackManip";
+
 // Parser Types
+
+// Offsets of an expression within the source query, end exclusive.
+export type Span = {
+  start: number;
+  end: number;
+};
+
 export type ASTLiteralExpression =
   | {
     type: "literal";
@@ -18,7 +27,7 @@ export type ASTLiteralExpression =
   | {
     type: "literal";
     valueType: "array";
-    value: Array<ASTExpression>;
+    value: Array<ASTExpression | ASTSpreadExpression>;
   }
   | {
     type: "literal";
@@ -28,9 +37,22 @@ export type ASTLiteralExpression =
   | {
     type: "literal";
     valueType: "object";
-    value: { [key: string]: ASTExpression };
+    value: ASTObjectEntry[];
   };
 
+// `...value` within an array or object literal, splicing in its contents
+export type ASTSpreadExpression = {
+  type: "spread";
+  value: ASTExpression;
+  span?: Span;
+};
+
+// Computed keys, e.g. `{(name): 1}`, are expressions. Later entries override
+// earlier ones with the same key.
+export type ASTObjectEntry =
+  | { key: string | ASTExpression; value: ASTExpression }
+  | ASTSpreadExpression;
+
 export type ASTPipelineExpression = {
   type: "pipeline";
   stages: ASTExpression[];
@@ -40,6 +62,9 @@ export type ASTReferenceExpression = {
   type: "reference";
   ref: string;
   internal?: true;
+  // Quoted in backticks, so a name like `@` or `@^` is a field rather than a
+  // context
+  quoted?: true;
 };
 
 export type ASTApplicationExpression = {
@@ -57,12 +82,33 @@ export type ASTParentheticalExpression = {
   expression: ASTExpression;
 };
 
-export type ASTExpression =
+export type ASTLetExpression = {
+  type: "let";
+  name: string;
+  value: ASTExpression;
+  body: ASTExpression;
+};
+
+export type ASTLambdaExpression = {
+  type: "lambda";
+  name: string;
+  params: string[];
+  body: ASTExpression;
+};
+
+export type ASTExpression = (
   | ASTApplicationExpression
   | ASTReferenceExpression
   | ASTPipelineExpression
   | ASTLiteralExpression
-  | ASTParentheticalExpression;
+  | ASTParentheticalExpression
+  | ASTLetExpression
+  | ASTLambdaExpression
+) & {
+  // Set by the parser as a non-enumerable property, so that spans don't
+  // take part in structural comparisons of ASTs.
+  span?: Span;
+};
 
 /* Runtime types */
 export type RuntimeValue =
@@ -90,7 +136,7 @@ export type Closure = {
   [varname: string]: RuntimeValue;
 };
 
-export type Stack = Closure[];
+export type Stack = (Closure | ValueFrame)[];
 
 export type FunctionClosure = {
   [varname: string]: FunctionValue;
@@ -119,6 +165,8 @@ export type LexToken =
     token: "ref";
     value: string;
     position: number;
+    // Written in backticks, so never a keyword
+    quoted?: true;
   }
   | {
     token: "special";
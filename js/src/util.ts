@@ -1,4 +1,5 @@
 import { RuntimeError } from "./errors";
+import { checkSize, step } from "./limits";
 import { getType } from "./runtimeValues";
 import {
   BuiltinFunction,
@@ -21,7 +22,10 @@ export const seqHelper = (arr: boolean[][], start = 0): number[][] => {
       } else {
         const subResult = seqHelper(arr.slice(1), idx + 1);
         for (let i = 0; i < subResult.length; i++) {
+          // The number of sequences is combinatorial, so each counts
+          step();
           result.push([idx].concat(subResult[i]));
+          checkSize(result.length);
         }
       }
     }
@@ -30,9 +34,19 @@ export const seqHelper = (arr: boolean[][], start = 0): number[][] => {
 };
 
 // Builtin Helpers
-export const arity =
-  (arityCount: number | number[], fn: BuiltinFunction): BuiltinFunction =>
-  (args, stack, exec) => {
+
+// The argument counts accepted by functions wrapped with `arity`, for
+// static analysis.
+const arities = new WeakMap<FunctionValue, number[]>();
+
+export const getArity = (fn: FunctionValue): number[] | undefined =>
+  arities.get(fn);
+
+export const arity = (
+  arityCount: number | number[],
+  fn: BuiltinFunction
+): BuiltinFunction => {
+  const wrapped: BuiltinFunction = (args, stack, exec) => {
     const validArity =
       typeof arityCount === "number"
         ? arityCount === args.length
@@ -44,6 +58,12 @@ export const arity =
     }
     return fn(args, stack, exec);
   };
+  arities.set(
+    wrapped,
+    typeof arityCount === "number" ? [arityCount] : arityCount
+  );
+  return wrapped;
+};
 
 export const validateType = (
   type: RuntimeValueType,
@@ -55,6 +75,15 @@ export const validateType = (
   return value;
 };
 
+// A math result, with NaN and the infinities as null, and -0 as 0 so that it
+// prints the same everywhere
+export const numberResult = (value: number): RuntimeValue => {
+  if (Number.isNaN(value) || !Number.isFinite(value)) {
+    return null;
+  }
+  return value === 0 ? 0 : value;
+};
+
 export const jsFunctionToMistQLFunction = (
   fn: (...args: any[]) => any
 ): FunctionValue => {
@@ -1,5 +1,10 @@
 __version__ = "0.4.12"
 
-from .query import query  # noqa: F401
-from .instance import MistQLInstance  # noqa: F401
+from .query import query, compile, analyze  # noqa: F401
+from .instance import MistQLInstance, CompiledQuery  # noqa: F401
 from .runtime_value import RuntimeValue  # noqa: F401
+from .exceptions import (  # noqa: F401
+    MistQLException,
+    MistQLParseError,
+    MistQLResourceLimitError,
+)
@@ -0,0 +1,950 @@
+"""
+Static analysis of queries, for finding errors without running them.
+
+The analyzer walks the expression tree with a model of the stack, inferring
+what it can about the type of each expression. It only reports errors that
+are certain to happen when the offending expression is evaluated, so a query
+without errors may still fail at runtime.
+"""
+
+from typing import (
+    Any,
+    Callable,
+    Dict,
+    FrozenSet,
+    Iterable,
+    List,
+    Optional,
+    Set,
+    Union,
+)
+
+from mistql.builtins import builtin_arities
+from mistql.exceptions import (
+    MistQLException,
+    MistQLReferenceError,
+    MistQLRuntimeError,
+    MistQLTypeError,
+)
+from mistql.expression import (
+    ArrayExpression,
+    BaseExpression,
+    FnExpression,
+    LambdaExpression,
+    LetExpression,
+    ObjectExpression,
+    PipeExpression,
+    RefExpression,
+    SpreadExpression,
+    ValueExpression,
+)
+from mistql.runtime_value import RuntimeValue, RuntimeValueType
+
+RVT = RuntimeValueType
+
+JSON_KINDS = frozenset(
+    {RVT.Null, RVT.Boolean, RVT.Number, RVT.String, RVT.Object, RVT.Array}
+)
+
+
+class FunctionInfo:
+    """The arity of a function, and the builtin it is, if any"""
+
+    def __init__(
+        self,
+        name: str,
+        min_args: Optional[int] = None,
+        max_args: Optional[int] = None,
+        builtin: bool = False,
+    ):
+        self.name = name
+        self.min_args = min_args
+        self.max_args = max_args
+        self.builtin = builtin
+
+
+class Type:
+    """
+    What's known about the values an expression might evaluate to.
+
+    Kinds are the runtime types the value might have, or None if it could be
+    anything. Objects track the types of their known properties, which of them
+    are certain to be present (all of them, unless given), and extra is the
+    type of any other property, with NEVER meaning there are none. Arrays
+    track the type of their items. None stands in for ANY in both.
+    """
+
+    def __init__(
+        self,
+        kinds: Optional[Iterable[RuntimeValueType]] = None,
+        properties: Optional[Dict[str, "Type"]] = None,
+        extra: Optional["Type"] = None,
+        items: Optional["Type"] = None,
+        function: Optional[FunctionInfo] = None,
+        required: Optional[Iterable[str]] = None,
+    ):
+        self.kinds: Optional[FrozenSet[RuntimeValueType]] = (
+            None if kinds is None else frozenset(kinds)
+        )
+        self.properties = properties or {}
+        self.required = frozenset(self.properties if required is None else required)
+        self.extra = extra
+        self.items = items
+        self.function = function
+
+    def can_be(self, *kinds: RuntimeValueType) -> bool:
+        return self.kinds is None or not self.kinds.isdisjoint(kinds)
+
+    def cannot_be(self, kinds: Iterable[RuntimeValueType]) -> bool:
+        """Whether the value definitely exists and has none of these kinds"""
+        return bool(self.kinds) and self.kinds.isdisjoint(kinds)  # type: ignore
+
+    def field(self, name: str) -> Optional["Type"]:
+        """The type of a property, or None if it's definitely missing"""
+        if not self.can_be(RVT.Object):
+            return None
+        if name in self.properties:
+            return self.properties[name]
+        extra = self.extra or ANY
+        if extra.kinds == frozenset():
+            return None
+        return extra
+
+    def has_field(self, name: str) -> bool:
+        """Whether the property is definitely present"""
+        return self.kinds == frozenset({RVT.Object}) and name in self.required
+
+    def item(self) -> "Type":
+        return self.items or ANY
+
+    def __str__(self):
+        if self.kinds is None:
+            return "any"
+        return " or ".join(sorted(kind.value for kind in self.kinds))
+
+
+ANY = Type()
+NEVER = Type(kinds=())
+NULL = Type({RVT.Null})
+BOOLEAN = Type({RVT.Boolean})
+NUMBER = Type({RVT.Number})
+STRING = Type({RVT.String})
+REGEX = Type({RVT.Regex})
+# Any value that can come from JSON, which rules out functions and regexes
+DATA = Type(JSON_KINDS)
+DATA.extra = DATA
+DATA.items = DATA
+
+
+def array_of(items: Type) -> Type:
+    return Type({RVT.Array}, items=items)
+
+
+def object_of(
+    properties: Dict[str, Type],
+    extra: Type = NEVER,
+    required: Optional[Iterable[str]] = None,
+) -> Type:
+    return Type({RVT.Object}, properties, extra, required=required)
+
+
+def function_of(info: FunctionInfo) -> Type:
+    return Type({RVT.Function}, function=info)
+
+
+def union(*types: Type) -> Type:
+    distinct: List[Type] = []
+    for t in types:
+        if not any(t is seen for seen in distinct):
+            distinct.append(t)
+    if len(distinct) == 1:
+        return distinct[0]
+    if not distinct:
+        return NEVER
+    if any(t.kinds is None for t in distinct):
+        return ANY
+    kinds = frozenset().union(*[t.kinds or () for t in distinct])
+    arrays = [t.item() for t in distinct if t.can_be(RVT.Array)]
+    objects = [t for t in distinct if t.can_be(RVT.Object)]
+    properties: Dict[str, Type] = {}
+    for name in {name for t in objects for name in t.properties}:
+        fields = [t.field(name) or NULL for t in objects]
+        properties[name] = union(*fields)
+    extras = [t.extra or ANY for t in objects]
+    required = [t.required for t in objects]
+    functions = {t.function for t in distinct if t.can_be(RVT.Function)}
+    return Type(
+        kinds,
+        properties,
+        union(*extras) if extras else None,
+        union(*arrays) if arrays else None,
+        functions.pop() if len(functions) == 1 else None,
+        frozenset.intersection(*required) if required else None,
+    )
+
+
+def from_value(value: RuntimeValue) -> Type:
+    if value.type == RVT.Array:
+        return array_of(union(*[from_value(item) for item in value.value]))
+    if value.type == RVT.Object:
+        return object_of({key: from_value(item) for key, item in value.value.items()})
+    return Type({value.type})
+
+
+SCHEMA_KINDS = {
+    "null": RVT.Null,
+    "boolean": RVT.Boolean,
+    "number": RVT.Number,
+    "integer": RVT.Number,
+    "string": RVT.String,
+    "object": RVT.Object,
+    "array": RVT.Array,
+}
+
+
+def from_schema(
+    schema: Any, root: Any = None, seen: FrozenSet[str] = frozenset()
+) -> Type:
+    """
+    Converts a JSON Schema into the type of the values it accepts.
+
+    Only local $refs are followed, and anything not understood is treated as
+    any JSON value.
+    """
+    if root is None:
+        root = schema
+    if not isinstance(schema, dict):
+        return NEVER if schema is False else DATA
+    if "$ref" in schema:
+        ref = schema["$ref"]
+        if not isinstance(ref, str) or not ref.startswith("#") or ref in seen:
+            return DATA
+        target = root
+        for part in ref[1:].split("/")[1:]:
+            part = part.replace("~1", "/").replace("~0", "~")
+            if isinstance(target, list) and part.isdigit():
+                target = target[int(part)] if int(part) < len(target) else None
+            elif isinstance(target, dict):
+                target = target.get(part)
+            else:
+                target = None
+        return from_schema(target, root, seen | {ref})
+    for key in ("anyOf", "oneOf"):
+        if isinstance(schema.get(key), list):
+            return union(*[from_schema(sub, root, seen) for sub in schema[key]])
+    if "const" in schema:
+        return from_value(RuntimeValue.of(schema["const"]))
+    if isinstance(schema.get("enum"), list):
+        return union(*[from_value(RuntimeValue.of(v)) for v in schema["enum"]])
+
+    kinds = JSON_KINDS
+    schema_type = schema.get("type")
+    if isinstance(schema_type, str):
+        schema_type = [schema_type]
+    if isinstance(schema_type, list):
+        kinds = frozenset(SCHEMA_KINDS[t] for t in schema_type if t in SCHEMA_KINDS)
+    elif "properties" in schema:
+        kinds = frozenset({RVT.Object})
+    elif "items" in schema:
+        kinds = frozenset({RVT.Array})
+
+    properties = {}
+    if isinstance(schema.get("properties"), dict):
+        for name, sub in schema["properties"].items():
+            properties[name] = from_schema(sub, root, seen)
+    extra = from_schema(schema.get("additionalProperties", True), root, seen)
+    items = schema.get("items", True)
+    if isinstance(items, list):
+        items_type = union(*[from_schema(sub, root, seen) for sub in items])
+    else:
+        items_type = from_schema(items, root, seen)
+    required = schema.get("required")
+    if not isinstance(required, list):
+        required = []
+    required = [name for name in required if name in properties]
+    return Type(kinds, properties, extra, items_type, required=required)
+
+
+# A frame is either bindings by name, or a value whose fields are bindings
+Frame = Union[Dict[str, Type], Type]
+Scope = List[Frame]
+
+
+def lookup(
+    name: str, scope: Scope, callee: bool = False, quoted: bool = False
+) -> Optional[Type]:
+    """
+    The type of a reference, or None if it definitely can't be found.
+
+    When looking up a function to call, fields that can't hold functions are
+    skipped, as calling them would fail regardless. Quoted references are
+    always fields, even when named like a context.
+    """
+    if name.startswith("@^") and not quoted:
+        # The value some number of contexts out, as in find_enclosing_context
+        contexts = [frame for frame in scope if isinstance(frame, Type)]
+        depth = len(name) - 1
+        return contexts[-1 - depth] if depth < len(contexts) else None
+    for i in range(len(scope) - 1, -1, -1):
+        frame = scope[i]
+        if isinstance(frame, dict):
+            if name in frame:
+                return frame[name]
+            continue
+        if name == "@" and not quoted:
+            return frame
+        if name == "$":
+            # Fields can't shadow the root reference, as in ValueFrame
+            continue
+        field = frame.field(name)
+        if field is None or (callee and not field.can_be(RVT.Function)):
+            continue
+        if frame.has_field(name):
+            return field
+        below = lookup(name, scope[:i], callee, quoted)
+        return field if below is None else union(field, below)
+    return None
+
+
+class Call:
+    """
+    The arguments of a call to a builtin, inferred as the builtin's signature
+    asks for them, so that each is evaluated in the right scope.
+    """
+
+    def __init__(
+        self,
+        analyzer: "Analyzer",
+        ast: BaseExpression,
+        args: List[BaseExpression],
+        scope: Scope,
+        piped: Optional[Type],
+        piped_ast: Optional[BaseExpression],
+    ):
+        self.analyzer = analyzer
+        self.ast = ast
+        self.args = args
+        self.scope = scope
+        self.piped = piped
+        self.piped_ast = piped_ast
+        self.types: Dict[int, Type] = {}
+
+    def __len__(self):
+        return len(self.args) + (0 if self.piped is None else 1)
+
+    def node(self, i: int) -> BaseExpression:
+        if i < len(self.args):
+            return self.args[i]
+        return self.piped_ast or self.ast
+
+    def arg(self, i: int, expected: Optional[Iterable[RVT]] = None) -> Type:
+        """Infers an argument evaluated in the caller's scope"""
+        return self.arg_in(i, None, expected)
+
+    def arg_in(
+        self, i: int, item: Optional[Type], expected: Optional[Iterable[RVT]] = None
+    ) -> Type:
+        """Infers an argument evaluated with the item pushed onto the stack"""
+        if i < 0:
+            i += len(self)
+        if i not in self.types:
+            if i < len(self.args):
+                scope = self.scope if item is None else self.scope + [item]
+                self.types[i] = self.analyzer.infer(self.args[i], scope)
+            else:
+                self.types[i] = self.piped or ANY
+        result = self.types[i]
+        if expected is not None and result.cannot_be(expected):
+            expected_type = Type(expected)
+            self.analyzer.report(
+                MistQLTypeError,
+                f"{self.name()}: expected {expected_type}, got {result}",
+                self.node(i),
+            )
+        return result
+
+    def name(self) -> str:
+        fn = self.ast.fn if isinstance(self.ast, FnExpression) else self.ast
+        return fn.name if isinstance(fn, RefExpression) else "function"
+
+    def rest(self):
+        """Infers the arguments the signature didn't ask for"""
+        for i in range(len(self)):
+            self.arg(i)
+
+
+Signature = Callable[[Call], Type]
+signatures: Dict[str, Signature] = {}
+
+ARRAY = {RVT.Array}
+OBJECT = {RVT.Object}
+NUMBERS = {RVT.Number}
+STRINGS = {RVT.String}
+PATTERNS = {RVT.String, RVT.Regex}
+DATES = {RVT.String, RVT.Number}
+ADDABLE = {RVT.Number, RVT.String, RVT.Array}
+COMPARABLE = {RVT.Boolean, RVT.Number, RVT.String}
+
+
+def signature(*names: str):
+    def signature_decorator(fn: Signature) -> Signature:
+        for name in names:
+            signatures[name] = fn
+        return fn
+
+    return signature_decorator
+
+
+def simple(
+    name: str, params: List[Optional[Iterable[RVT]]], result: Type = ANY
+) -> None:
+    """Registers a builtin that evaluates its arguments in the caller's scope"""
+
+    def infer(call: Call) -> Type:
+        for i in range(min(len(call), len(params))):
+            call.arg(i, params[i])
+        return result
+
+    signatures[name] = infer
+
+
+simple("count", [ARRAY], NUMBER)
+simple("sum", [ARRAY], NUMBER)
+simple(
+    "summarize",
+    [ARRAY],
+    object_of(
+        {key: NUMBER for key in ["max", "min", "mean", "median", "variance", "stddev"]}
+    ),
+)
+simple("fromentries", [ARRAY], object_of({}, ANY))
+simple("pick", [ARRAY, OBJECT], object_of({}, ANY))
+simple("omit", [ARRAY, OBJECT], object_of({}, ANY))
+simple("haskey", [STRINGS, OBJECT], BOOLEAN)
+simple("percentile", [NUMBERS, ARRAY], union(NUMBER, NULL))
+simple("getpath", [ARRAY, None])
+simple("setpath", [ARRAY, None, None])
+simple("string", [None], STRING)
+simple("float", [None], NUMBER)
+simple("keys", [None], array_of(STRING))
+simple("values", [None], array_of(ANY))
+simple("entries", [None], array_of(array_of(ANY)))
+simple("!/unary", [None], BOOLEAN)
+simple("-/unary", [NUMBERS], NUMBER)
+for operator in ["-", "*", "/", "%"]:
+    simple(operator, [NUMBERS, NUMBERS], NUMBER)
+simple("==", [None, None], BOOLEAN)
+simple("!=", [None, None], BOOLEAN)
+simple("regex", [STRINGS, STRINGS], REGEX)
+simple("match", [PATTERNS, STRINGS], BOOLEAN)
+simple("=~", [STRINGS, PATTERNS], BOOLEAN)
+simple("replace", [PATTERNS, STRINGS, STRINGS], STRING)
+simple("split", [PATTERNS, STRINGS], array_of(STRING))
+simple("stringjoin", [STRINGS, ARRAY], STRING)
+simple("range", [NUMBERS, NUMBERS, NUMBERS], array_of(NUMBER))
+simple("lower", [STRINGS], STRING)
+simple("upper", [STRINGS], STRING)
+simple("trim", [STRINGS], STRING)
+simple("startswith", [STRINGS, STRINGS], BOOLEAN)
+simple("endswith", [STRINGS, STRINGS], BOOLEAN)
+simple("contains", [STRINGS, STRINGS], BOOLEAN)
+simple("length", [STRINGS], NUMBER)
+simple("format", [STRINGS, {RVT.Array, RVT.Object}], STRING)
+simple("abs", [NUMBERS], NUMBER)
+simple("floor", [NUMBERS], NUMBER)
+simple("ceil", [NUMBERS], NUMBER)
+simple("round", [NUMBERS, NUMBERS], NUMBER)
+simple("clamp", [NUMBERS, NUMBERS, NUMBERS], NUMBER)
+# Results that aren't real numbers or are infinite come out as null
+simple("pow", [NUMBERS, NUMBERS], union(NUMBER, NULL))
+simple("sqrt", [NUMBERS], union(NUMBER, NULL))
+simple("ln", [NUMBERS], union(NUMBER, NULL))
+simple("intdiv", [NUMBERS, NUMBERS], union(NUMBER, NULL))
+simple("parsedate", [DATES], NUMBER)
+simple("datediff", [STRINGS, DATES, DATES], NUMBER)
+simple("dateadd", [STRINGS, NUMBERS, DATES], STRING)
+simple("truncdate", [STRINGS, DATES], STRING)
+
+
+@signature("log")
+def log(call: Call) -> Type:
+    return call.arg(0)
+
+
+@signature("padstart", "padend")
+def pad(call: Call) -> Type:
+    call.arg(0, NUMBERS)
+    if len(call) == 3:
+        call.arg(1, STRINGS)
+    call.arg(-1, STRINGS)
+    return STRING
+
+
+@signature("substring")
+def substring(call: Call) -> Type:
+    call.arg(0, NUMBERS)
+    if len(call) == 3:
+        call.arg(1, NUMBERS)
+    call.arg(-1, STRINGS)
+    return STRING
+
+
+@signature("min", "max")
+def extremum(call: Call) -> Type:
+    if len(call) == 2:
+        call.arg(0, NUMBERS)
+        call.arg(1, NUMBERS)
+    else:
+        call.arg(0, ARRAY)
+    return union(NUMBER, NULL)
+
+
+@signature("formatdate")
+def formatdate(call: Call) -> Type:
+    if len(call) == 2:
+        call.arg(0, STRINGS)
+    call.arg(-1, DATES)
+    return STRING
+
+
+@signature("reverse", "sort", "unique")
+def reorder(call: Call) -> Type:
+    return array_of(call.arg(0, ARRAY).item())
+
+
+@signature("flatten")
+def flatten(call: Call) -> Type:
+    return array_of(call.arg(0, ARRAY).item().item())
+
+
+@signature("countby")
+def countby(call: Call) -> Type:
+    target = call.arg(1, ARRAY)
+    call.arg_in(0, target.item())
+    return object_of({}, NUMBER)
+
+
+@signature("sumby")
+def sumby(call: Call) -> Type:
+    target = call.arg(1, ARRAY)
+    call.arg_in(0, target.item(), NUMBERS)
+    return NUMBER
+
+
+@signature("avgby")
+def avgby(call: Call) -> Type:
+    sumby(call)
+    return union(NUMBER, NULL)
+
+
+@signature("minby", "maxby")
+def minby(call: Call) -> Type:
+    target = call.arg(1, ARRAY)
+    call.arg_in(0, target.item(), COMPARABLE)
+    return union(target.item(), NULL)
+
+
+@signature("withindices")
+def withindices(call: Call) -> Type:
+    return array_of(array_of(union(NUMBER, call.arg(0, ARRAY).item())))
+
+
+@signature("if")
+def if_else(call: Call) -> Type:
+    call.arg(0)
+    return union(call.arg(1), call.arg(2))
+
+
+@signature("cond")
+def cond(call: Call) -> Type:
+    if len(call) % 2 == 0:
+        call.analyzer.report(
+            MistQLRuntimeError,
+            "cond: Expected pairs of conditions and results, followed by a default",
+            call.ast,
+        )
+    results = []
+    for i in range(0, len(call) - 1, 2):
+        call.arg(i)
+        results.append(call.arg(i + 1))
+    return union(*results, call.arg(len(call) - 1))
+
+
+@signature("&&", "||")
+def boolean_operator(call: Call) -> Type:
+    return union(call.arg(0), call.arg(1))
+
+
+@signature("??")
+def coalesce(call: Call) -> Type:
+    left = call.arg(0)
+    if left.kinds is not None and RVT.Null in left.kinds:
+        # The right is only used in place of a null
+        left = Type(
+            left.kinds - {RVT.Null},
+            left.properties,
+            left.extra,
+            left.items,
+            required=left.required,
+        )
+    return union(left, call.arg(1))
+
+
+@signature("+")
+def add(call: Call) -> Type:
+    left, right = call.arg(0), call.arg(1)
+    if left.kinds is None or right.kinds is None:
+        return ANY
+    kinds = left.kinds & right.kinds & ADDABLE
+    if not kinds and left.kinds and right.kinds:
+        call.analyzer.report(
+            MistQLTypeError, f"add: cannot add {left} and {right}", call.ast
+        )
+    if kinds == ARRAY:
+        return array_of(union(left.item(), right.item()))
+    return Type(kinds)
+
+
+@signature("<", "<=", ">", ">=")
+def compare(call: Call) -> Type:
+    left, right = call.arg(0), call.arg(1)
+    if left.cannot_be(COMPARABLE) or right.cannot_be(COMPARABLE):
+        incomparable = left if left.cannot_be(COMPARABLE) else right
+        call.analyzer.report(
+            MistQLTypeError, f"Cannot compare values of type {incomparable}", call.ast
+        )
+    elif left.kinds and right.kinds and left.kinds.isdisjoint(right.kinds):
+        call.analyzer.report(
+            MistQLTypeError, f"Cannot compare {left} and {right}", call.ast
+        )
+    return BOOLEAN
+
+
+def access(call: Call, lenient: bool) -> Type:
+    target = call.arg(0)
+    member = call.args[1] if len(call.args) > 1 else None
+    if not isinstance(member, RefExpression):
+        return ANY
+    # The member is a name rather than an expression to evaluate
+    call.types[1] = ANY
+    if target.cannot_be({RVT.Object, RVT.Null}):
+        if lenient:
+            return NULL
+        call.analyzer.report(
+            MistQLTypeError, f"Cannot access {member.name} on {target}", call.ast
+        )
+        return ANY
+    field = target.field(member.name) or NULL
+    if target.has_field(member.name):
+        return field
+    return union(field, NULL)
+
+
+@signature(".")
+def dot(call: Call) -> Type:
+    return access(call, lenient=False)
+
+
+@signature("?.")
+def lenient_dot(call: Call) -> Type:
+    return access(call, lenient=True)
+
+
+@signature("index")
+def index(call: Call) -> Type:
+    for i in range(len(call) - 1):
+        call.arg(i)
+    target = call.arg(-1)
+    if len(call) == 3:
+        return target
+    if target.kinds == ARRAY:
+        return union(target.item(), NULL)
+    return ANY
+
+
+@signature("map")
+def map(call: Call) -> Type:
+    target = call.arg(1, ARRAY)
+    return array_of(call.arg_in(0, target.item()))
+
+
+@signature("filter", "sortby", "uniqueby")
+def filter(call: Call) -> Type:
+    target = call.arg(1, ARRAY)
+    call.arg_in(0, target.item())
+    return array_of(target.item())
+
+
+@signature("find")
+def find(call: Call) -> Type:
+    target = call.arg(1, ARRAY)
+    call.arg_in(0, target.item())
+    return union(target.item(), NULL)
+
+
+@signature("merge", "deepmerge")
+def merge(call: Call) -> Type:
+    for i in range(len(call)):
+        call.arg(i, OBJECT)
+    return object_of({}, ANY)
+
+
+@signature("any", "all")
+def quantifier(call: Call) -> Type:
+    target = call.arg(-1, ARRAY)
+    if len(call) == 2:
+        call.arg_in(0, target.item())
+    return BOOLEAN
+
+
+@signature("zip")
+def zip_(call: Call) -> Type:
+    targets = [call.arg(i, ARRAY) for i in range(len(call))]
+    return array_of(array_of(union(*[target.item() for target in targets])))
+
+
+@signature("chunk")
+def chunk(call: Call) -> Type:
+    call.arg(0, NUMBERS)
+    return array_of(call.arg(1, ARRAY))
+
+
+@signature("take", "drop")
+def slice(call: Call) -> Type:
+    call.arg(0, NUMBERS)
+    return array_of(call.arg(1, ARRAY).item())
+
+
+@signature("first", "last")
+def end(call: Call) -> Type:
+    return union(call.arg(0, ARRAY).item(), NULL)
+
+
+@signature("groupby")
+def groupby(call: Call) -> Type:
+    target = call.arg(1, ARRAY)
+    call.arg_in(0, target.item())
+    return object_of({}, array_of(target.item()))
+
+
+@signature("sequence")
+def sequence(call: Call) -> Type:
+    target = call.arg(-1, ARRAY)
+    for i in range(len(call) - 1):
+        call.arg_in(i, target.item())
+    return array_of(array_of(target.item()))
+
+
+@signature("reduce")
+def reduce(call: Call) -> Type:
+    call.arg(1)
+    call.arg(2, ARRAY)
+    call.arg_in(0, array_of(ANY))
+    return ANY
+
+
+@signature("apply")
+def apply(call: Call) -> Type:
+    return call.arg_in(0, call.arg(1))
+
+
+def _values(target: Type) -> Type:
+    return union(*target.properties.values(), target.extra or ANY)
+
+
+@signature("mapvalues")
+def mapvalues(call: Call) -> Type:
+    target = call.arg(1, OBJECT)
+    result = call.arg_in(0, _values(target))
+    properties = {key: result for key in target.properties}
+    return object_of(properties, result, target.required)
+
+
+@signature("filtervalues")
+def filtervalues(call: Call) -> Type:
+    target = call.arg(1, OBJECT)
+    call.arg_in(0, _values(target))
+    return object_of({}, _values(target))
+
+
+@signature("mapkeys", "filterkeys")
+def mapkeys(call: Call) -> Type:
+    target = call.arg(1, OBJECT)
+    call.arg_in(0, STRING)
+    return object_of({}, _values(target))
+
+
+class Analyzer:
+    def __init__(self):
+        self.errors: List[MistQLException] = []
+
+    def report(
+        self,
+        kind: Callable[[str], MistQLException],
+        message: str,
+        ast: BaseExpression,
+    ):
+        error = kind(message)
+        error.start = ast.start
+        error.end = ast.end
+        self.errors.append(error)
+
+    def infer(self, ast: BaseExpression, scope: Scope) -> Type:
+        if isinstance(ast, ValueExpression):
+            return from_value(ast.value)
+        elif isinstance(ast, RefExpression):
+            found = lookup(
+                ast.name, scope[:1] if ast.absolute else scope, quoted=ast.quoted
+            )
+            if found is None:
+                self.report(MistQLReferenceError, f"Unknown reference {ast.name}", ast)
+                return ANY
+            return found
+        elif isinstance(ast, FnExpression):
+            return self.infer_call(ast, ast.args, scope)
+        elif isinstance(ast, ArrayExpression):
+            return self.infer_array(ast, scope)
+        elif isinstance(ast, ObjectExpression):
+            return self.infer_object(ast, scope)
+        elif isinstance(ast, PipeExpression):
+            data = self.infer(ast.stages[0], scope)
+            previous = ast.stages[0]
+            for stage in ast.stages[1:]:
+                args = stage.args if isinstance(stage, FnExpression) else []
+                data = self.infer_call(stage, args, scope + [data], data, previous)
+                previous = stage
+            return data
+        elif isinstance(ast, LetExpression):
+            value = self.infer(ast.value, scope)
+            return self.infer(ast.body, scope + [{ast.name: value}])
+        elif isinstance(ast, LambdaExpression):
+            info = FunctionInfo(ast.name, len(ast.params), len(ast.params))
+            bindings = {param: ANY for param in ast.params}
+            bindings[ast.name] = function_of(info)
+            self.infer(ast.body, scope + [bindings])
+            return function_of(info)
+        return ANY
+
+    def infer_spread(self, ast: SpreadExpression, scope: Scope, into: RVT) -> Type:
+        spread = self.infer(ast.value, scope)
+        if spread.cannot_be({into}):
+            self.report(
+                MistQLTypeError, f"Cannot spread {spread} into an {into.value}", ast
+            )
+        return spread
+
+    def infer_array(self, ast: ArrayExpression, scope: Scope) -> Type:
+        items: List[Type] = []
+        for item in ast.items:
+            if isinstance(item, SpreadExpression):
+                items.append(self.infer_spread(item, scope, RVT.Array).item())
+            else:
+                items.append(self.infer(item, scope))
+        return array_of(union(*items))
+
+    def infer_object(self, ast: ObjectExpression, scope: Scope) -> Type:
+        properties: Dict[str, Type] = {}
+        required: Set[str] = set()
+        extra = NEVER
+        for entry in ast.entries:
+            if isinstance(entry, SpreadExpression):
+                spread = self.infer_spread(entry, scope, RVT.Object)
+                # Any earlier property might have been overridden
+                properties, required, extra = {}, set(), ANY
+                if spread.kinds == frozenset({RVT.Object}):
+                    properties.update(spread.properties)
+                    required.update(spread.required)
+                continue
+            key, value = entry
+            if isinstance(key, BaseExpression):
+                self.infer(key, scope)
+                properties, required, extra = {}, set(), ANY
+                self.infer(value, scope)
+            else:
+                properties[key] = self.infer(value, scope)
+                required.add(key)
+        return object_of(properties, extra, required)
+
+    def infer_callee(self, fn: BaseExpression, scope: Scope) -> Type:
+        if not isinstance(fn, RefExpression):
+            callee = self.infer(fn, scope)
+            if callee.cannot_be({RVT.Function}):
+                self.report(MistQLTypeError, f"Cannot call {callee}", fn)
+                return NEVER
+            return callee
+        callee = lookup(
+            fn.name, scope[:1] if fn.absolute else scope, True, fn.quoted
+        )
+        if callee is None:
+            self.report(MistQLReferenceError, f"Unknown function {fn.name}", fn)
+            return NEVER
+        return callee
+
+    def infer_call(
+        self,
+        ast: BaseExpression,
+        args: List[BaseExpression],
+        scope: Scope,
+        piped: Optional[Type] = None,
+        piped_ast: Optional[BaseExpression] = None,
+    ) -> Type:
+        fn = ast.fn if isinstance(ast, FnExpression) else ast
+        call = Call(self, ast, args, scope, piped, piped_ast)
+        info = self.infer_callee(fn, scope).function
+        if info is None:
+            call.rest()
+            return ANY
+        count = len(call)
+        if info.min_args is not None and info.min_args >= 0 and count < info.min_args:
+            self.report(
+                MistQLRuntimeError,
+                f"{info.name} takes at least {info.min_args} arguments",
+                ast,
+            )
+        elif info.max_args is not None and info.max_args >= 0 and count > info.max_args:
+            self.report(
+                MistQLRuntimeError,
+                f"{info.name} takes at most {info.max_args} arguments",
+                ast,
+            )
+        elif info.builtin and info.name in signatures:
+            result = signatures[info.name](call)
+            call.rest()
+            return result
+        call.rest()
+        return ANY
+
+
+def initial_scope(data: Type, extras: Optional[Dict[str, Any]] = None) -> Scope:
+    """The analyzer's counterpart to mistql.stack.build_initial_stack"""
+    functions: Dict[str, Type] = {}
+    for name, (min_args, max_args) in builtin_arities.items():
+        functions[name] = function_of(FunctionInfo(name, min_args, max_args, True))
+    functions["now"] = STRING
+    for name, value in (extras or {}).items():
+        if isinstance(value, RuntimeValue) and value.type != RVT.Function:
+            functions[name] = from_value(value)
+        else:
+            functions[name] = function_of(FunctionInfo(name))
+    dollar = object_of({"@": data, **functions})
+    return [functions, {"$": dollar}, data]
+
+
+def analyze(
+    ast: BaseExpression,
+    schema: Any = None,
+    extras: Optional[Dict[str, Any]] = None,
+) -> List[MistQLException]:
+    """
+    Finds, without running a query, the errors certain to happen when each of
+    its expressions is evaluated.
+
+    :param ast: The parsed query.
+    :param schema: An optional JSON Schema describing the data.
+    :param extras: Functions and values provided alongside the builtins.
+    :return: The errors found, in the order they'd be evaluated.
+    """
+    analyzer = Analyzer()
+    data = DATA if schema is None else from_schema(schema)
+    analyzer.infer(ast, initial_scope(data, extras))
+    return analyzer.errors
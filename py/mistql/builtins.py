@@ -1,3 +1,4 @@
+import math
 import re
 import statistics
 from functools import cmp_to_key
@@ -9,6 +10,7 @@ from mistql.exceptions import (
     OpenAnIssueIfYouGetThisError,
 )
 from mistql.expression import BaseExpression, RefExpression
+from mistql import dates, limits
 from mistql.runtime_value import RuntimeValue, RuntimeValueType, assert_type, assert_int
 from mistql.stack import Stack, add_runtime_value_to_stack
 
@@ -22,6 +24,9 @@ FunctionDefinitionType = Callable[
 
 builtins: Dict[str, FunctionDefinitionType] = {}
 
+# The (min_args, max_args) of each builtin, where a max of -1 is unbounded
+builtin_arities: Dict[str, Tuple[int, int]] = {}
+
 RVT = RuntimeValueType
 
 
@@ -40,6 +45,7 @@ def builtin(name: str, min_args: int, max_args: Union[None, int] = None):
             return fn(arguments, stack, exec)
 
         builtins[name] = wrapped
+        builtin_arities[name] = (min_args, min_args if max_args is None else max_args)
         return wrapped
 
     return builtin_decorator
@@ -78,12 +84,28 @@ def if_else(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
         return exec(arguments[2], stack)
 
 
+@builtin("cond", 3, -1)
+def cond(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    # Conditions are tried in order, and only the chosen result is evaluated
+    if len(arguments) % 2 == 0:
+        raise MistQLRuntimeError(
+            "cond: Expected pairs of conditions and results, followed by a default"
+        )
+    for i in range(0, len(arguments) - 1, 2):
+        if exec(arguments[i], stack):
+            return exec(arguments[i + 1], stack)
+    return exec(arguments[-1], stack)
+
+
 @builtin("+", 2)
 def add(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     left = exec(arguments[0], stack)
     right = exec(arguments[1], stack)
     if left.type != right.type:
         raise MistQLTypeError(f"add: {left} and {right} are not the same type")
+    if left.type in {RVT.String, RVT.Array}:
+        # The size of the result is known before building it
+        limits.check_size(len(left.value) + len(right.value))
     if left.type in {
         RVT.Number,
         RVT.String,
@@ -111,6 +133,8 @@ def multiply(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
 def divide(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     left = assert_type(exec(arguments[0], stack), RVT.Number)
     right = assert_type(exec(arguments[1], stack), RVT.Number)
+    if right.value == 0:
+        raise MistQLRuntimeError("Division by zero")
     return RuntimeValue.of(left.value / right.value)
 
 
@@ -118,6 +142,8 @@ def divide(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
 def mod(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     left = assert_type(exec(arguments[0], stack), RVT.Number)
     right = assert_type(exec(arguments[1], stack), RVT.Number)
+    if right.value == 0:
+        raise MistQLRuntimeError("Modulo by zero")
     return RuntimeValue.of(left.value % right.value)
 
 
@@ -134,9 +160,8 @@ def neq(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
 @builtin("&&", 2)
 def and_fn(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     left = exec(arguments[0], stack)
-    right = exec(arguments[1], stack)
     if left:
-        return right
+        return exec(arguments[1], stack)
     else:
         return left
 
@@ -144,11 +169,18 @@ def and_fn(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
 @builtin("||", 2)
 def or_fn(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     left = exec(arguments[0], stack)
-    right = exec(arguments[1], stack)
     if left:
         return left
     else:
-        return right
+        return exec(arguments[1], stack)
+
+
+@builtin("??", 2)
+def coalesce(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    left = exec(arguments[0], stack)
+    if left.type == RVT.Null:
+        return exec(arguments[1], stack)
+    return left
 
 
 @builtin("count", 1)
@@ -171,6 +203,18 @@ def dot(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     return _index_single(RuntimeValue.of(right.name), left)
 
 
+@builtin("?.", 2)
+def lenient_dot(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    left = exec(arguments[0], stack)
+    right = arguments[1]
+    if not isinstance(right, RefExpression):
+        raise MistQLRuntimeError("dot: RHS of the dot operator is not a ref")
+    # Unlike `.`, anything other than an object gives null rather than an error
+    if left.type != RVT.Object:
+        return RuntimeValue.of(None)
+    return left.access(right.name)
+
+
 @builtin("map", 2)
 def map(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     mutation = arguments[0]
@@ -366,15 +410,19 @@ def regex(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     )
 
 
+def _compare(a: RuntimeValue, b: RuntimeValue) -> int:
+    """Compares values for sorting, where each comparison counts a step"""
+    limits.step()
+    return RuntimeValue.compare(a, b)
+
+
 @builtin("sort", 1)
 def sort(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     arg = assert_type(exec(arguments[0], stack), RVT.Array)
     for entry in arg.value:
         if not entry.comparable():
             raise MistQLRuntimeError("sort: Cannot sort non-comparable values")
-    return RuntimeValue.of(
-        list(sorted(arg.value, key=cmp_to_key(RuntimeValue.compare)))
-    )
+    return RuntimeValue.of(list(sorted(arg.value, key=cmp_to_key(_compare))))
 
 
 @builtin("sortby", 2)
@@ -389,7 +437,7 @@ def sortby(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
         with_key.append((key, item))
 
     def cmp(a: Tuple[RuntimeValue, RuntimeValue], b: Tuple[RuntimeValue, RuntimeValue]):
-        return RuntimeValue.compare(a[0], b[0])
+        return _compare(a[0], b[0])
 
     post_sort = list(sorted(with_key, key=cmp_to_key(cmp)))
     return RuntimeValue.of([value for key, value in post_sort])
@@ -435,6 +483,81 @@ def groupby(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     return RuntimeValue.of(groups)
 
 
+def _keys(arguments: Args, stack: Stack, exec: Exec) -> List[RuntimeValue]:
+    """The contextual expression applied to each item of the target"""
+    target = assert_type(exec(arguments[1], stack), RVT.Array)
+    return [
+        exec(arguments[0], add_runtime_value_to_stack(item, stack))
+        for item in target.value
+    ]
+
+
+@builtin("countby", 2)
+def countby(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    counts: Dict[str, int] = {}
+    for key in _keys(arguments, stack, exec):
+        name = key.to_string()
+        counts[name] = counts.get(name, 0) + 1
+    return RuntimeValue.of(counts)
+
+
+@builtin("sumby", 2)
+def sumby(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    total = 0
+    for key in _keys(arguments, stack, exec):
+        total += assert_type(key, RVT.Number).value
+    return RuntimeValue.of(total)
+
+
+@builtin("avgby", 2)
+def avgby(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    keys = _keys(arguments, stack, exec)
+    if not keys:
+        return RuntimeValue.of(None)
+    total = 0
+    for key in keys:
+        total += assert_type(key, RVT.Number).value
+    return RuntimeValue.of(total / len(keys))
+
+
+def _extremum_by(name: str, sign: int):
+    @builtin(name, 2)
+    def extremum_by(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+        target = assert_type(exec(arguments[1], stack), RVT.Array)
+        best, best_key = RuntimeValue.of(None), None
+        for item, key in zip(target.value, _keys(arguments, stack, exec)):
+            if not key.comparable():
+                raise MistQLRuntimeError(f"{name}: Cannot compare {key.type.value}")
+            # Ties go to the earliest item
+            if best_key is None or sign * RuntimeValue.compare(key, best_key) < 0:
+                best, best_key = item, key
+        return best
+
+    return extremum_by
+
+
+_extremum_by("minby", 1)
+_extremum_by("maxby", -1)
+
+
+@builtin("percentile", 2)
+def percentile(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    p = assert_type(exec(arguments[0], stack), RVT.Number).value
+    if not 0 <= p <= 100:
+        raise MistQLRuntimeError("percentile: Percentile must be from 0 to 100")
+    target = assert_type(exec(arguments[1], stack), RVT.Array)
+    values = sorted(assert_type(item, RVT.Number).value for item in target.value)
+    if not values:
+        return RuntimeValue.of(None)
+    # Interpolates between the closest ranks, which makes the 50th percentile
+    # the median as summarize finds it
+    position = p * (len(values) - 1) / 100
+    lower = math.floor(position)
+    upper = min(lower + 1, len(values) - 1)
+    fraction = position - lower
+    return RuntimeValue.of(values[lower] * (1 - fraction) + values[upper] * fraction)
+
+
 @builtin("withindices", 1)
 def withindices(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     target = assert_type(exec(arguments[0], stack), RVT.Array)
@@ -467,11 +590,141 @@ def fromentries(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     return RuntimeValue.of(res)
 
 
+@builtin("merge", 2, -1)
+def merge(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    res: Dict[str, RuntimeValue] = {}
+    for arg in arguments:
+        res.update(assert_type(exec(arg, stack), RVT.Object).value)
+    return RuntimeValue.of(res)
+
+
+def _deepmerge(left: RuntimeValue, right: RuntimeValue) -> RuntimeValue:
+    if left.type != RVT.Object or right.type != RVT.Object:
+        return right
+    res = dict(left.value)
+    for key, value in right.value.items():
+        res[key] = _deepmerge(res[key], value) if key in res else value
+    return RuntimeValue.of(res)
+
+
+@builtin("deepmerge", 2, -1)
+def deepmerge(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    targets = [assert_type(exec(arg, stack), RVT.Object) for arg in arguments]
+    res = targets[0]
+    for target in targets[1:]:
+        res = _deepmerge(res, target)
+    return res
+
+
+def _key_list(value: RuntimeValue) -> List[str]:
+    keys = assert_type(value, RVT.Array).value
+    return [assert_type(key, RVT.String).value for key in keys]
+
+
+@builtin("pick", 2)
+def pick(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    keys = _key_list(exec(arguments[0], stack))
+    target = assert_type(exec(arguments[1], stack), RVT.Object)
+    return RuntimeValue.of(
+        {key: target.value[key] for key in keys if key in target.value}
+    )
+
+
+@builtin("omit", 2)
+def omit(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    keys = set(_key_list(exec(arguments[0], stack)))
+    target = assert_type(exec(arguments[1], stack), RVT.Object)
+    return RuntimeValue.of(
+        {key: value for key, value in target.value.items() if key not in keys}
+    )
+
+
+@builtin("haskey", 2)
+def haskey(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    key = assert_type(exec(arguments[0], stack), RVT.String).value
+    target = assert_type(exec(arguments[1], stack), RVT.Object)
+    return RuntimeValue.of(key in target.value)
+
+
+def _path(value: RuntimeValue) -> List[Union[str, int]]:
+    """The keys and indices of a path, checking that they are one or the other"""
+    steps: List[Union[str, int]] = []
+    for step in assert_type(value, RVT.Array).value:
+        if step.type == RVT.String:
+            steps.append(step.value)
+        elif step.type == RVT.Number:
+            steps.append(int(assert_int(step).value))
+        else:
+            raise MistQLTypeError(
+                f"Expected path of strings and integers, got {step.type.value}"
+            )
+    return steps
+
+
+def _array_index(items: List[RuntimeValue], index: int) -> Union[int, None]:
+    """The position an index refers to, counting negative ones from the end"""
+    position = index + len(items) if index < 0 else index
+    return position if 0 <= position < len(items) else None
+
+
+@builtin("getpath", 2)
+def getpath(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    path = _path(exec(arguments[0], stack))
+    current = exec(arguments[1], stack)
+    for step in path:
+        if isinstance(step, str) and current.type == RVT.Object:
+            current = current.value.get(step, RuntimeValue.of(None))
+        elif isinstance(step, int) and current.type == RVT.Array:
+            position = _array_index(current.value, step)
+            if position is None:
+                return RuntimeValue.of(None)
+            current = current.value[position]
+        else:
+            return RuntimeValue.of(None)
+    return current
+
+
+def _setpath(
+    target: RuntimeValue, path: List[Union[str, int]], value: RuntimeValue
+) -> RuntimeValue:
+    if not path:
+        return value
+    step, rest = path[0], path[1:]
+    if isinstance(step, str):
+        if target.type == RVT.Null:
+            target = RuntimeValue.of({})
+        if target.type != RVT.Object:
+            raise MistQLRuntimeError(
+                f"setpath: Cannot set {step} on {target.type.value}"
+            )
+        res = dict(target.value)
+        res[step] = _setpath(res.get(step, RuntimeValue.of(None)), rest, value)
+        return RuntimeValue.of(res)
+    if target.type != RVT.Array:
+        raise MistQLRuntimeError(
+            f"setpath: Cannot set index {step} on {target.type.value}"
+        )
+    position = _array_index(target.value, step)
+    if position is None:
+        raise MistQLRuntimeError(f"setpath: Index {step} is out of range")
+    items = list(target.value)
+    items[position] = _setpath(items[position], rest, value)
+    return RuntimeValue.of(items)
+
+
+@builtin("setpath", 3)
+def setpath(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    path = _path(exec(arguments[0], stack))
+    value = exec(arguments[1], stack)
+    return _setpath(exec(arguments[2], stack), path, value)
+
+
 @builtin("match", 2)
 def match(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     pattern = exec(arguments[0], stack)
     target = exec(arguments[1], stack)
     assert_type(pattern, {RVT.String, RVT.Regex})
+    assert_type(target, RVT.String)
     if pattern.type == RVT.Regex:
         return RuntimeValue.of(bool(pattern.value.search(target.value)))
     elif pattern.type == RVT.String:
@@ -504,7 +757,11 @@ def _range(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
         raise OpenAnIssueIfYouGetThisError(
             "Unexpectedly reaching end of function in range call."
         )
-    return RuntimeValue.of(list(range(start, stop, step)))
+    if step == 0:
+        raise MistQLRuntimeError("Range: Step size cannot be 0")
+    # Checked up front, so that a range too large to return is never built
+    limits.check_size(len(range(start, stop, step)))
+    return RuntimeValue.of(list(limits.stepped(range(start, stop, step))))
 
 
 @builtin("replace", 3)
@@ -513,12 +770,25 @@ def replace(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     replacement = exec(arguments[1], stack)
     target = exec(arguments[2], stack)
     assert_type(pattern, {RVT.String, RVT.Regex})
+    assert_type(replacement, RVT.String)
+    assert_type(target, RVT.String)
     if pattern.type == RVT.Regex:
-        if pattern.modifiers["global"]:
-            res = pattern.value.sub(replacement.value, target.value)
-        else:
-            res = pattern.value.sub(replacement.value, target.value, 1)
-        return RuntimeValue.of(res)
+        # Each replacement counts a step, and the result is measured as it's
+        # built, as a global replacement can grow it many times over
+        size = 0
+        last_end = 0
+
+        def expand(match: "re.Match[str]") -> str:
+            nonlocal size, last_end
+            limits.step()
+            expanded = match.expand(replacement.value)
+            size += match.start() - last_end + len(expanded)
+            last_end = match.end()
+            limits.check_size(size)
+            return expanded
+
+        count = 0 if pattern.modifiers["global"] else 1
+        return RuntimeValue.of(pattern.value.sub(expand, target.value, count))
     elif pattern.type == RVT.String:
         return RuntimeValue.of(
             target.value.replace(pattern.value, replacement.value, 1)
@@ -535,23 +805,171 @@ def split(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     if delimiter.type == RVT.String:
         separator = delimiter.value
         if separator == "":
-            return RuntimeValue.of(list(target.value))
-        return RuntimeValue.of(target.value.split(separator))
+            limits.check_size(len(target.value))
+            pieces = list(target.value)
+        else:
+            # The pieces are counted before any are built
+            limits.check_size(target.value.count(separator) + 1)
+            pieces = target.value.split(separator)
     elif delimiter.type == RVT.Regex:
-        return RuntimeValue.of(list(delimiter.value.split(target.value)))
-    raise OpenAnIssueIfYouGetThisError(
-        "Unexpectedly reaching end of function in match call."
-    )
+        pieces = delimiter.value.split(target.value)
+    else:
+        raise OpenAnIssueIfYouGetThisError(
+            "Unexpectedly reaching end of function in match call."
+        )
+    # Converting each piece counts a step, as there can be one per character
+    return RuntimeValue.of([RuntimeValue.of(piece) for piece in limits.stepped(pieces)])
 
 
 @builtin("stringjoin", 2)
 def stringjoin(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     delimiter = assert_type(exec(arguments[0], stack), RVT.String)
     target = assert_type(exec(arguments[1], stack), RVT.Array)
-    arr = [entry.to_string() for entry in target.value]
+    arr = [entry.to_string() for entry in limits.stepped(target.value)]
+    size = len(delimiter.value) * max(len(arr) - 1, 0)
+    for entry in arr:
+        size += len(entry)
+    limits.check_size(size)
     return RuntimeValue.of(delimiter.value.join(arr))
 
 
+# Python's str.strip and friends disagree with other languages about what
+# whitespace is, so this is the Unicode White_Space property, spelled out
+WHITESPACE = (
+    "\t\n\x0b\x0c\r \x85\xa0\u1680\u2000\u2001\u2002\u2003\u2004\u2005"
+    "\u2006\u2007\u2008\u2009\u200a\u2028\u2029\u202f\u205f\u3000"
+)
+
+
+@builtin("lower", 1)
+def lower(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    target = assert_type(exec(arguments[0], stack), RVT.String)
+    return RuntimeValue.of(target.value.lower())
+
+
+@builtin("upper", 1)
+def upper(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    target = assert_type(exec(arguments[0], stack), RVT.String)
+    return RuntimeValue.of(target.value.upper())
+
+
+@builtin("trim", 1)
+def trim(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    target = assert_type(exec(arguments[0], stack), RVT.String)
+    return RuntimeValue.of(target.value.strip(WHITESPACE))
+
+
+@builtin("startswith", 2)
+def startswith(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    prefix = assert_type(exec(arguments[0], stack), RVT.String)
+    target = assert_type(exec(arguments[1], stack), RVT.String)
+    return RuntimeValue.of(target.value.startswith(prefix.value))
+
+
+@builtin("endswith", 2)
+def endswith(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    suffix = assert_type(exec(arguments[0], stack), RVT.String)
+    target = assert_type(exec(arguments[1], stack), RVT.String)
+    return RuntimeValue.of(target.value.endswith(suffix.value))
+
+
+@builtin("contains", 2)
+def contains(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    needle = assert_type(exec(arguments[0], stack), RVT.String)
+    target = assert_type(exec(arguments[1], stack), RVT.String)
+    return RuntimeValue.of(needle.value in target.value)
+
+
+def _padding(arguments: Args, stack: Stack, exec: Exec) -> Tuple[str, str]:
+    """The target of padstart or padend, and the padding it needs"""
+    width = int(assert_int(exec(arguments[0], stack)).value)
+    fill = " "
+    if len(arguments) == 3:
+        fill = assert_type(exec(arguments[1], stack), RVT.String).value
+        if fill == "":
+            raise MistQLRuntimeError("Cannot pad with an empty string")
+    target = assert_type(exec(arguments[-1], stack), RVT.String).value
+    missing = max(width - len(target), 0)
+    limits.check_size(width)
+    return target, (fill * (missing // len(fill) + 1))[:missing]
+
+
+@builtin("padstart", 2, 3)
+def padstart(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    target, padding = _padding(arguments, stack, exec)
+    return RuntimeValue.of(padding + target)
+
+
+@builtin("padend", 2, 3)
+def padend(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    target, padding = _padding(arguments, stack, exec)
+    return RuntimeValue.of(target + padding)
+
+
+@builtin("substring", 2, 3)
+def substring(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    start = int(assert_int(exec(arguments[0], stack)).value)
+    end = None
+    if len(arguments) == 3:
+        end = int(assert_int(exec(arguments[1], stack)).value)
+    target = assert_type(exec(arguments[-1], stack), RVT.String)
+    return RuntimeValue.of(target.value[start:end])
+
+
+@builtin("length", 1)
+def length(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    target = assert_type(exec(arguments[0], stack), RVT.String)
+    return RuntimeValue.of(len(target.value))
+
+
+def _format_field(name: str, values: RuntimeValue, position: int) -> RuntimeValue:
+    if values.type == RVT.Object:
+        if name == "":
+            raise MistQLRuntimeError("format: {} needs an array of values")
+        if name not in values.value:
+            raise MistQLRuntimeError(f"format: No value for {{{name}}}")
+        return values.value[name]
+    if name == "":
+        index = position
+    elif name.isascii() and name.isdigit():
+        index = int(name)
+    else:
+        raise MistQLRuntimeError(f"format: {{{name}}} needs an object of values")
+    if index >= len(values.value):
+        raise MistQLRuntimeError(f"format: No value for {{{name}}}")
+    return values.value[index]
+
+
+@builtin("format", 2)
+def format(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    template = assert_type(exec(arguments[0], stack), RVT.String).value
+    values = assert_type(exec(arguments[1], stack), {RVT.Array, RVT.Object})
+    result: List[str] = []
+    position = 0
+    i = 0
+    while i < len(template):
+        char = template[i]
+        if char in "{}" and template[i + 1 : i + 2] == char:
+            result.append(char)
+            i += 2
+        elif char == "}":
+            raise MistQLRuntimeError("format: Unmatched } in template")
+        elif char == "{":
+            end = template.find("}", i)
+            if end == -1:
+                raise MistQLRuntimeError("format: Unmatched { in template")
+            name = template[i + 1 : end]
+            field = _format_field(name, values, position)
+            if name == "":
+                position += 1
+            result.append(field.to_string())
+            i = end + 1
+        else:
+            result.append(char)
+            i += 1
+    return RuntimeValue.of("".join(result))
+
+
 @builtin("sum", 1)
 def sum(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     target = assert_type(exec(arguments[0], stack), RVT.Array)
@@ -578,6 +996,131 @@ def summarize(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     return RuntimeValue.of(summary)
 
 
+def _number(value: float) -> RuntimeValue:
+    """A math result, with -0 as 0 so that it prints the same everywhere"""
+    return RuntimeValue.of(value + 0.0)
+
+
+def _round_half_away(value: float) -> float:
+    whole = math.floor(abs(value))
+    if abs(value) - whole >= 0.5:
+        whole += 1
+    return math.copysign(whole, value)
+
+
+def _power_of_ten(exponent: int) -> float:
+    # Converted from the exact integer, so that it's the closest double, as
+    # parsing "1e<exponent>" gives in the other implementations
+    return 10**exponent * 1.0
+
+
+def _number_args(arguments: Args, stack: Stack, exec: Exec) -> List[float]:
+    return [assert_type(exec(arg, stack), RVT.Number).value for arg in arguments]
+
+
+@builtin("abs", 1)
+def abs_(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    [value] = _number_args(arguments, stack, exec)
+    return _number(abs(value))
+
+
+@builtin("floor", 1)
+def floor(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    [value] = _number_args(arguments, stack, exec)
+    return _number(math.floor(value))
+
+
+@builtin("ceil", 1)
+def ceil(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    [value] = _number_args(arguments, stack, exec)
+    return _number(math.ceil(value))
+
+
+@builtin("round", 1, 2)
+def round_(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    precision = 0
+    if len(arguments) == 2:
+        precision = int(assert_int(exec(arguments[0], stack)).value)
+        if abs(precision) > 308:
+            raise MistQLRuntimeError("round: Precision must be from -308 to 308")
+    value = assert_type(exec(arguments[-1], stack), RVT.Number).value
+    factor = _power_of_ten(abs(precision))
+    if precision >= 0:
+        scaled = value * factor
+        # Too big to have any digits at this precision
+        if not math.isfinite(scaled):
+            return _number(value)
+        return _number(_round_half_away(scaled) / factor)
+    return _number(_round_half_away(value / factor) * factor)
+
+
+def _extremum(name: str, pick: Callable[[float, float], float]):
+    @builtin(name, 1, 2)
+    def extremum(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+        if len(arguments) == 2:
+            values = _number_args(arguments, stack, exec)
+        else:
+            target = assert_type(exec(arguments[0], stack), RVT.Array)
+            values = [assert_type(item, RVT.Number).value for item in target.value]
+        if not values:
+            return RuntimeValue.of(None)
+        result = values[0]
+        for value in values[1:]:
+            result = pick(result, value)
+        return _number(result)
+
+    return extremum
+
+
+_extremum("min", min)
+_extremum("max", max)
+
+
+@builtin("pow", 2)
+def pow(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    base, exponent = _number_args(arguments, stack, exec)
+    try:
+        return _number(math.pow(base, exponent))
+    except (OverflowError, ValueError):
+        # Results that aren't real numbers or don't fit are null
+        return RuntimeValue.of(None)
+
+
+@builtin("sqrt", 1)
+def sqrt(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    [value] = _number_args(arguments, stack, exec)
+    if value < 0:
+        return RuntimeValue.of(None)
+    return _number(math.sqrt(value))
+
+
+@builtin("ln", 1)
+def ln(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    [value] = _number_args(arguments, stack, exec)
+    if value <= 0:
+        return RuntimeValue.of(None)
+    return _number(math.log(value))
+
+
+@builtin("clamp", 3)
+def clamp(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    low, high, value = _number_args(arguments, stack, exec)
+    if low > high:
+        raise MistQLRuntimeError("clamp: The lower bound is above the upper bound")
+    return _number(min(max(value, low), high))
+
+
+@builtin("intdiv", 2)
+def intdiv(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    dividend, divisor = _number_args(arguments, stack, exec)
+    if divisor == 0:
+        raise MistQLRuntimeError("Division by zero")
+    quotient = dividend / divisor
+    if not math.isfinite(quotient):
+        return RuntimeValue.of(None)
+    return _number(math.floor(quotient))
+
+
 def _sequence_helper(arr: List[List[bool]], start=0) -> List[List[int]]:
     firstArray = arr[0]
     result: List[List[int]] = []
@@ -588,7 +1131,10 @@ def _sequence_helper(arr: List[List[bool]], start=0) -> List[List[int]]:
             else:
                 subResult = _sequence_helper(arr[1:], idx + 1)
                 for i in range(len(subResult)):
+                    # The number of sequences is combinatorial, so each counts
+                    limits.step()
                     result.append([idx] + subResult[i])
+                    limits.check_size(len(result))
     return result
 
 
@@ -614,7 +1160,152 @@ def sequence(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
 @builtin("flatten", 1)
 def flatten(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
     target = assert_type(exec(arguments[0], stack), RVT.Array)
+    entries = [assert_type(entry, RVT.Array).value for entry in target.value]
+    size = 0
+    for entry in entries:
+        size += len(entry)
+    limits.check_size(size)
     result: List[RuntimeValue] = []
-    for entry in target.value:
-        result.extend(assert_type(entry, RVT.Array).value)
+    for entry in entries:
+        result.extend(entry)
     return RuntimeValue.of(result)
+
+
+def _unique_by_key(items: List[RuntimeValue], keys: List[RuntimeValue]):
+    """The items whose keys don't equal the key of any earlier item"""
+    result: List[RuntimeValue] = []
+    seen: List[RuntimeValue] = []
+    for item, key in zip(items, keys):
+        for other in seen:
+            # Each item is compared against every distinct key so far
+            limits.step()
+            if RuntimeValue.eq(key, other):
+                break
+        else:
+            seen.append(key)
+            result.append(item)
+    return RuntimeValue.of(result)
+
+
+@builtin("unique", 1)
+def unique(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    target = assert_type(exec(arguments[0], stack), RVT.Array)
+    return _unique_by_key(target.value, target.value)
+
+
+@builtin("uniqueby", 2)
+def uniqueby(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    target = assert_type(exec(arguments[1], stack), RVT.Array)
+    keys = [
+        exec(arguments[0], add_runtime_value_to_stack(item, stack))
+        for item in target.value
+    ]
+    return _unique_by_key(target.value, keys)
+
+
+@builtin("zip", 2, -1)
+def zip_(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    targets = [assert_type(exec(arg, stack), RVT.Array).value for arg in arguments]
+    return RuntimeValue.of([list(items) for items in zip(*targets)])
+
+
+def _count_argument(name: str, arguments: Args, stack: Stack, exec: Exec) -> int:
+    count = int(assert_int(exec(arguments[0], stack)).value)
+    if count < 0:
+        raise MistQLRuntimeError(f"{name}: Count cannot be negative")
+    return count
+
+
+@builtin("chunk", 2)
+def chunk(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    size = _count_argument("chunk", arguments, stack, exec)
+    if size == 0:
+        raise MistQLRuntimeError("chunk: Size must be at least 1")
+    target = assert_type(exec(arguments[1], stack), RVT.Array)
+    items = target.value
+    return RuntimeValue.of(
+        [items[start : start + size] for start in range(0, len(items), size)]
+    )
+
+
+@builtin("take", 2)
+def take(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    count = _count_argument("take", arguments, stack, exec)
+    target = assert_type(exec(arguments[1], stack), RVT.Array)
+    return RuntimeValue.of(target.value[:count])
+
+
+@builtin("drop", 2)
+def drop(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    count = _count_argument("drop", arguments, stack, exec)
+    target = assert_type(exec(arguments[1], stack), RVT.Array)
+    return RuntimeValue.of(target.value[count:])
+
+
+@builtin("first", 1)
+def first(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    target = assert_type(exec(arguments[0], stack), RVT.Array)
+    return target.value[0] if target.value else RuntimeValue.of(None)
+
+
+@builtin("last", 1)
+def last(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    target = assert_type(exec(arguments[0], stack), RVT.Array)
+    return target.value[-1] if target.value else RuntimeValue.of(None)
+
+
+def _truths(arguments: Args, stack: Stack, exec: Exec):
+    """Whether each item, or the predicate applied to it, is truthy, lazily"""
+    target = assert_type(exec(arguments[-1], stack), RVT.Array)
+    for item in target.value:
+        if len(arguments) == 2:
+            item = exec(arguments[0], add_runtime_value_to_stack(item, stack))
+        yield item.truthy()
+
+
+@builtin("any", 1, 2)
+def any_(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    return RuntimeValue.of(any(_truths(arguments, stack, exec)))
+
+
+@builtin("all", 1, 2)
+def all_(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    return RuntimeValue.of(all(_truths(arguments, stack, exec)))
+
+
+@builtin("parsedate", 1)
+def parsedate(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    return RuntimeValue.of(dates.to_ms(exec(arguments[0], stack)))
+
+
+@builtin("formatdate", 1, 2)
+def formatdate(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    date = dates.to_ms(exec(arguments[-1], stack))
+    if len(arguments) == 1:
+        return RuntimeValue.of(dates.format_iso(date))
+    fmt = assert_type(exec(arguments[0], stack), RVT.String)
+    return RuntimeValue.of(dates.format_date(fmt.value, date))
+
+
+@builtin("datediff", 3)
+def datediff(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    unit = assert_type(exec(arguments[0], stack), RVT.String)
+    start = dates.to_ms(exec(arguments[1], stack))
+    end = dates.to_ms(exec(arguments[2], stack))
+    return RuntimeValue.of(dates.diff(unit.value, start, end))
+
+
+@builtin("dateadd", 3)
+def dateadd(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    unit = assert_type(exec(arguments[0], stack), RVT.String)
+    amount = assert_int(exec(arguments[1], stack))
+    date = dates.to_ms(exec(arguments[2], stack))
+    result = dates.add(unit.value, int(amount.value), date)
+    return RuntimeValue.of(dates.format_iso(result))
+
+
+@builtin("truncdate", 2)
+def truncdate(arguments: Args, stack: Stack, exec: Exec) -> RuntimeValue:
+    unit = assert_type(exec(arguments[0], stack), RVT.String)
+    date = dates.to_ms(exec(arguments[1], stack))
+    return RuntimeValue.of(dates.format_iso(dates.truncate(unit.value, date)))
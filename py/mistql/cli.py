@@ -1,9 +1,11 @@
 #!/usr/bin/env python3
 
-from typing import Union
+from contextlib import contextmanager, nullcontext
+from typing import IO, Callable, ContextManager, Iterator, Union
 import argparse
+import io
 from mistql import __version__
-from mistql import query
+from mistql import query, compile
 import sys
 import json
 import logging
@@ -26,7 +28,24 @@ inputgroup.add_argument(
 )
 
 inputgroup.add_argument(
-    "--file_jsonl", "-fjl", type=str, help="The json-lines file to read the data from."
+    "--file_jsonl",
+    "-fjl",
+    type=str,
+    help="The json-lines file to read the data from. Implies --jsonl",
+)
+
+parser.add_argument(
+    "--jsonl",
+    "-jl",
+    action="store_true",
+    help="Read the data as json-lines, and write one result per line as it's read",
+)
+
+parser.add_argument(
+    "--slurp",
+    "-s",
+    action="store_true",
+    help="Run the query once over an array of every json-lines item",
 )
 
 parser.add_argument(
@@ -38,6 +57,41 @@ parser.add_argument(
 )
 
 
+def open_jsonl_input(args) -> ContextManager[IO[bytes]]:
+    if args.data:
+        return io.BytesIO(args.data.encode("utf-8"))
+    elif args.file or args.file_jsonl:
+        return open(args.file or args.file_jsonl, "rb")
+    # Leave stdin open for the caller
+    return nullcontext(sys.stdin.buffer)
+
+
+@contextmanager
+def open_jsonl_output(args) -> Iterator[Callable[[str], None]]:
+    if args.output:
+        with open(args.output, "wb") as f:
+
+            def write_line(line: str) -> None:
+                f.write(line.encode("utf-8") + b"\n")
+
+            yield write_line
+    else:
+
+        def print_line(line: str) -> None:
+            # Flushed, so that results show up while the input is still read
+            print(line, flush=True)
+
+        yield print_line
+
+
+def stream_jsonl(args) -> None:
+    """Runs the query over each json-lines item, writing results as they come"""
+    compiled = compile(args.query)
+    with open_jsonl_input(args) as f, open_jsonl_output(args) as write_line:
+        for item in json_lines.reader(f):
+            write_line(json.dumps(compiled.run(item), ensure_ascii=False))
+
+
 def main(supplied_args=None):
     if supplied_args is None:
         args = parser.parse_args()
@@ -45,23 +99,29 @@ def main(supplied_args=None):
         args = parser.parse_args(supplied_args)
     raw_data: Union[str, bytes]
 
-    if args.data:
-        raw_data = args.data
-    elif args.file:
-        with open(args.file, "rb") as f:
-            raw_data = f.read()
-
-    elif args.file_jsonl:
-        out = []
-        with open(args.file_jsonl, "rb") as f:
-            for item in json_lines.reader(f):
-                out.append(query(args.query, item))
+    jsonl = args.jsonl or args.file_jsonl is not None
+    if args.slurp and not jsonl:
+        parser.error("--slurp requires json-lines input, from --jsonl or --file_jsonl")
+    if jsonl and not args.slurp:
+        if args.pretty:
+            parser.error("--pretty can't be used with json-lines output")
+        stream_jsonl(args)
+        return
+
+    if jsonl:
+        with open_jsonl_input(args) as f:
+            out = compile(args.query).run(list(json_lines.reader(f)))
     else:
-        raw_data = sys.stdin.buffer.read()
-
-    if not args.file_jsonl:
+        if args.data:
+            raw_data = args.data
+        elif args.file:
+            with open(args.file, "rb") as f:
+                raw_data = f.read()
+        else:
+            raw_data = sys.stdin.buffer.read()
         data = json.loads(raw_data)
         out = query(args.query, data)
+
     if args.output:
         # TODO: Allow alternate output encodings other than utf-8
         out_bytes = json.dumps(
@@ -0,0 +1,245 @@
+"""
+Date math for the date builtins.
+
+Dates are whole milliseconds since the Unix epoch, always in UTC. Calendar
+math is done by hand, rather than with the datetime module, so that every
+implementation parses, formats, and rounds dates exactly the same way.
+"""
+
+import re
+from datetime import datetime, timezone
+from typing import Tuple
+
+from mistql.exceptions import MistQLRuntimeError, MistQLTypeError
+from mistql.runtime_value import RuntimeValue, RuntimeValueType
+
+MS_PER_SECOND = 1000
+MS_PER_MINUTE = 60 * MS_PER_SECOND
+MS_PER_HOUR = 60 * MS_PER_MINUTE
+MS_PER_DAY = 24 * MS_PER_HOUR
+
+# Units with a fixed length, in milliseconds
+fixed_units = {
+    "millisecond": 1,
+    "second": MS_PER_SECOND,
+    "minute": MS_PER_MINUTE,
+    "hour": MS_PER_HOUR,
+    "day": MS_PER_DAY,
+    "week": 7 * MS_PER_DAY,
+}
+
+# Units whose length depends on the calendar, in months
+calendar_units = {"month": 1, "year": 12}
+
+iso_regex = re.compile(
+    r"([0-9]{4})-([0-9]{2})-([0-9]{2})"
+    r"(?:[T ]([0-9]{2}):([0-9]{2})(?::([0-9]{2})(?:\.([0-9]+))?)?"
+    r"(Z|[+-][0-9]{2}:?[0-9]{2})?)?"
+)
+
+weekdays = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday"]
+weekdays += ["Saturday", "Sunday"]
+months = ["January", "February", "March", "April", "May", "June", "July"]
+months += ["August", "September", "October", "November", "December"]
+
+
+def days_from_civil(year: int, month: int, day: int) -> int:
+    """Days since 1970-01-01 of a date in the proleptic Gregorian calendar."""
+    year -= month <= 2
+    era = year // 400
+    year_of_era = year - era * 400
+    day_of_year = (153 * (month + (-3 if month > 2 else 9)) + 2) // 5 + day - 1
+    day_of_era = (
+        year_of_era * 365 + year_of_era // 4 - year_of_era // 100 + day_of_year
+    )
+    return era * 146097 + day_of_era - 719468
+
+
+def civil_from_days(days: int) -> Tuple[int, int, int]:
+    """The year, month, and day of a number of days since 1970-01-01."""
+    days += 719468
+    era = days // 146097
+    day_of_era = days - era * 146097
+    year_of_era = (
+        day_of_era - day_of_era // 1460 + day_of_era // 36524 - day_of_era // 146096
+    ) // 365
+    day_of_year = day_of_era - (
+        365 * year_of_era + year_of_era // 4 - year_of_era // 100
+    )
+    mp = (5 * day_of_year + 2) // 153
+    day = day_of_year - (153 * mp + 2) // 5 + 1
+    month = mp + 3 if mp < 10 else mp - 9
+    return year_of_era + era * 400 + (month <= 2), month, day
+
+
+def days_in_month(year: int, month: int) -> int:
+    if month == 12:
+        return 31
+    return days_from_civil(year, month + 1, 1) - days_from_civil(year, month, 1)
+
+
+MIN_DATE = days_from_civil(0, 1, 1) * MS_PER_DAY
+MAX_DATE = days_from_civil(10000, 1, 1) * MS_PER_DAY - 1
+
+
+def check_range(ms: int) -> int:
+    if not MIN_DATE <= ms <= MAX_DATE:
+        raise MistQLRuntimeError("Date out of range: years must be 0 to 9999")
+    return ms
+
+
+def parse_iso(text: str) -> int:
+    match = iso_regex.fullmatch(text)
+    if match is None:
+        raise MistQLRuntimeError(f"Invalid ISO 8601 date: {text}")
+    year, month, day, hour, minute, second = (
+        int(group or 0) for group in match.groups()[:6]
+    )
+    fraction, offset = match.group(7), match.group(8)
+    if not (
+        1 <= month <= 12
+        and 1 <= day <= days_in_month(year, month)
+        and hour <= 23
+        and minute <= 59
+        and second <= 59
+    ):
+        raise MistQLRuntimeError(f"Invalid ISO 8601 date: {text}")
+    ms = days_from_civil(year, month, day) * MS_PER_DAY
+    ms += hour * MS_PER_HOUR + minute * MS_PER_MINUTE + second * MS_PER_SECOND
+    # Digits past milliseconds are truncated
+    ms += int((fraction or "0")[:3].ljust(3, "0"))
+    if offset and offset != "Z":
+        offset_hours, offset_minutes = int(offset[1:3]), int(offset[-2:])
+        if offset_hours > 23 or offset_minutes > 59:
+            raise MistQLRuntimeError(f"Invalid ISO 8601 date: {text}")
+        sign = 1 if offset[0] == "+" else -1
+        ms -= sign * (offset_hours * MS_PER_HOUR + offset_minutes * MS_PER_MINUTE)
+    return check_range(ms)
+
+
+def to_ms(value: RuntimeValue) -> int:
+    """Reads a date from an ISO 8601 string or epoch milliseconds."""
+    if value.type == RuntimeValueType.String:
+        return parse_iso(value.value)
+    if value.type == RuntimeValueType.Number:
+        if not float(value.value).is_integer():
+            raise MistQLTypeError(f"Expected integer milliseconds, got {value.value}")
+        return check_range(int(value.value))
+    raise MistQLTypeError(f"Expected a date string or number, got {value.type}")
+
+
+def from_datetime(dt: datetime) -> int:
+    """Milliseconds since the epoch of a datetime, taking naive ones as UTC."""
+    if dt.tzinfo is None:
+        dt = dt.replace(tzinfo=timezone.utc)
+    epoch = datetime(1970, 1, 1, tzinfo=timezone.utc)
+    delta = dt - epoch
+    return (delta.days * 86400 + delta.seconds) * MS_PER_SECOND + (
+        delta.microseconds // 1000
+    )
+
+
+def split(ms: int) -> Tuple[int, int, int, int]:
+    """The year, month, day, and milliseconds into the day of a date."""
+    days, time_of_day = divmod(ms, MS_PER_DAY)
+    year, month, day = civil_from_days(days)
+    return year, month, day, time_of_day
+
+
+def format_directive(directive: str, ms: int) -> str:
+    year, month, day, time_of_day = split(ms)
+    days = ms // MS_PER_DAY
+    if directive == "Y":
+        return f"{year:04}"
+    if directive == "m":
+        return f"{month:02}"
+    if directive == "d":
+        return f"{day:02}"
+    if directive == "H":
+        return f"{time_of_day // MS_PER_HOUR:02}"
+    if directive == "M":
+        return f"{time_of_day // MS_PER_MINUTE % 60:02}"
+    if directive == "S":
+        return f"{time_of_day // MS_PER_SECOND % 60:02}"
+    if directive == "L":
+        return f"{time_of_day % MS_PER_SECOND:03}"
+    if directive == "j":
+        return f"{days - days_from_civil(year, 1, 1) + 1:03}"
+    if directive in ("a", "A"):
+        # 1970-01-01 was a Thursday
+        name = weekdays[(days + 3) % 7]
+        return name if directive == "A" else name[:3]
+    if directive in ("b", "B"):
+        name = months[month - 1]
+        return name if directive == "B" else name[:3]
+    if directive == "%":
+        return "%"
+    raise MistQLRuntimeError(f"Unknown date format directive %{directive}")
+
+
+def format_date(fmt: str, ms: int) -> str:
+    result = []
+    i = 0
+    while i < len(fmt):
+        if fmt[i] != "%":
+            result.append(fmt[i])
+            i += 1
+            continue
+        if i + 1 == len(fmt):
+            raise MistQLRuntimeError("Date format ends with a lone %")
+        result.append(format_directive(fmt[i + 1], ms))
+        i += 2
+    return "".join(result)
+
+
+def format_iso(ms: int) -> str:
+    return format_date("%Y-%m-%dT%H:%M:%S.%LZ", check_range(ms))
+
+
+def check_unit(unit: str) -> str:
+    if unit not in fixed_units and unit not in calendar_units:
+        raise MistQLRuntimeError(f"Unknown date unit {unit}")
+    return unit
+
+
+def add_months(ms: int, count: int) -> int:
+    year, month, day, time_of_day = split(ms)
+    new_year, new_month = divmod(year * 12 + month - 1 + count, 12)
+    new_month += 1
+    # Days past the end of the new month are clamped to its last day
+    new_day = min(day, days_in_month(new_year, new_month))
+    return days_from_civil(new_year, new_month, new_day) * MS_PER_DAY + time_of_day
+
+
+def add(unit: str, amount: int, ms: int) -> int:
+    if check_unit(unit) in fixed_units:
+        return check_range(ms + amount * fixed_units[unit])
+    return check_range(add_months(ms, amount * calendar_units[unit]))
+
+
+def diff(unit: str, start: int, end: int) -> int:
+    """The number of whole units from start to end, truncated toward zero."""
+    if check_unit(unit) in fixed_units:
+        whole = abs(end - start) // fixed_units[unit]
+        return whole if end >= start else -whole
+    start_year, start_month = split(start)[:2]
+    end_year, end_month = split(end)[:2]
+    count = (end_year - start_year) * 12 + end_month - start_month
+    if count > 0 and add_months(start, count) > end:
+        count -= 1
+    elif count < 0 and add_months(start, count) < end:
+        count += 1
+    whole = abs(count) // calendar_units[unit]
+    return whole if count >= 0 else -whole
+
+
+def truncate(unit: str, ms: int) -> int:
+    """The start of the unit the date falls in. Weeks start on Monday."""
+    check_unit(unit)
+    if unit == "week":
+        days = ms // MS_PER_DAY
+        return (days - (days + 3) % 7) * MS_PER_DAY
+    if unit in fixed_units:
+        return ms - ms % fixed_units[unit]
+    year, month = split(ms)[:2]
+    return days_from_civil(year, 1 if unit == "year" else month, 1) * MS_PER_DAY
@@ -1,3 +1,30 @@
+from typing import List, Optional
+import re
+
+
+def make_indicator(text: str, position: int, length: int = 1) -> str:
+    """
+    Renders the text with carets on the line below the given position,
+    underlining up to `length` characters without running past the line.
+    """
+    prev_string_lines = re.sub(r"\S", " ", text[:position]).split("\n")
+    lines = text.split("\n")
+    indicator_line = len(prev_string_lines) - 1
+    rest_of_line = len(lines[indicator_line]) - len(prev_string_lines[-1])
+    indicator = prev_string_lines[-1] + "^" * max(1, min(length, rest_of_line))
+    return "\n".join(
+        lines[: indicator_line + 1] + [indicator] + lines[indicator_line + 1 :]
+    )
+
+
+def make_message(
+    message: str, position: int, query: str, end: Optional[int] = None
+) -> str:
+    length = 1 if end is None else end - position
+    indicator = make_indicator(query, position, length)
+    return f"{message} at position {position}\n---\n{indicator}\n---\n"
+
+
 class OpenAnIssueIfYouGetThisError(Exception):
     """Please open an issue if you get this error.
 
@@ -15,9 +42,21 @@ class OpenAnIssueIfYouGetThisError(Exception):
 class MistQLException(Exception):
     """
     Base class for all MistQL exceptions.
+
+    Errors raised while executing a query record the span of the innermost
+    expression that failed. Once the query is attached, the message points
+    at that expression.
     """
 
-    pass
+    start: Optional[int] = None
+    end: Optional[int] = None
+    query: Optional[str] = None
+
+    def __str__(self):
+        message = super().__str__()
+        if self.query is None or self.start is None:
+            return message
+        return make_message(message, self.start, self.query, self.end)
 
 
 class MistQLRuntimeError(MistQLException):
@@ -42,3 +81,38 @@ class MistQLTypeError(MistQLRuntimeError):
     """
 
     pass
+
+
+class MistQLResourceLimitError(MistQLRuntimeError):
+    """
+    Raised when a query exceeds one of the execution limits of its instance.
+    """
+
+    pass
+
+
+class MistQLParseError(MistQLException):
+    """
+    Raised when a query cannot be parsed.
+
+    Line and column are 1-indexed, offset is the 0-indexed position in the query.
+    """
+
+    def __init__(
+        self,
+        message: str,
+        query: str,
+        offset: int,
+        expected: Optional[List[str]] = None,
+    ):
+        self.query = query
+        self.offset = offset
+        self.line = query.count("\n", 0, offset) + 1
+        self.column = offset - (query.rfind("\n", 0, offset) + 1) + 1
+        self.expected = expected or []
+        if len(self.expected) == 1:
+            message = f"{message}, expected {self.expected[0]}"
+        elif len(self.expected) > 1:
+            message = f"{message}, expected one of {', '.join(self.expected)}"
+        self.message = message
+        super().__init__(make_message(message, offset, query))
@@ -1,4 +1,4 @@
-from typing import List, Mapping, Union, Callable
+from typing import Dict, List, Mapping, Optional, Union, Callable
 from mistql.runtime_value import RuntimeValue, RuntimeValueType
 from mistql.expression import (
     Expression,
@@ -8,20 +8,38 @@ from mistql.expression import (
     ArrayExpression,
     ObjectExpression,
     PipeExpression,
+    LetExpression,
+    LambdaExpression,
+    SpreadExpression,
 )
 from mistql.stack import Stack
 from mistql.builtins import FunctionDefinitionType, builtins
 from mistql.stack import (
+    add_bindings_to_stack,
     add_runtime_value_to_stack,
     build_initial_stack,
     find_in_stack,
 )
 from mistql.expression import BaseExpression
-from mistql.exceptions import MistQLTypeError, OpenAnIssueIfYouGetThisError
+from mistql.exceptions import (
+    MistQLException,
+    MistQLResourceLimitError,
+    MistQLRuntimeError,
+    MistQLTypeError,
+    OpenAnIssueIfYouGetThisError,
+)
+from mistql.limits import Budget, Limits, check_size, current_budget, step
 
 from typeguard import typechecked
 
 
+collection_types = (
+    RuntimeValueType.Array,
+    RuntimeValueType.Object,
+    RuntimeValueType.String,
+)
+
+
 @typechecked
 def execute_fncall(head: BaseExpression, arguments: List[BaseExpression], stack: Stack):
     fn = execute(head, stack)
@@ -29,7 +47,17 @@ def execute_fncall(head: BaseExpression, arguments: List[BaseExpression], stack:
         raise MistQLTypeError(f"Tried to call a non-function: {fn}")
     # Not enforced, but definitely should be.
     function_definition: FunctionDefinitionType = fn.value
-    return function_definition(arguments, stack, execute)
+    budget = current_budget.get()
+    if budget is None:
+        return function_definition(arguments, stack, execute)
+    budget.enter()
+    try:
+        result = function_definition(arguments, stack, execute)
+    finally:
+        budget.leave()
+    if result.type in collection_types:
+        budget.check_size(len(result.value))
+    return result
 
 
 @typechecked
@@ -44,14 +72,83 @@ def execute_pipe(stages: List[BaseExpression], stack: Stack) -> RuntimeValue:
             raise OpenAnIssueIfYouGetThisError("Pipe stage is not a function!!")
         args: List[BaseExpression] = stage_ast.args.copy()
         args.append(ValueExpression(data))
-        stage = FnExpression(stage_ast.fn, args)
+        stage = FnExpression(stage_ast.fn, args).with_span(
+            stage_ast.start, stage_ast.end
+        )
         data = execute(stage, new_stack)
 
     return data
 
 
+def execute_spread(ast: SpreadExpression, stack: Stack, into: RuntimeValueType):
+    value = execute(ast.value, stack)
+    if value.type != into:
+        message = f"Cannot spread {value.type.value} into an {into.value}"
+        error = MistQLTypeError(message)
+        # Spreads aren't executed on their own, so point at them here
+        error.start, error.end = ast.start, ast.end
+        raise error
+    return value
+
+
+def execute_array(ast: ArrayExpression, stack: Stack) -> RuntimeValue:
+    items: List[RuntimeValue] = []
+    for item in ast.items:
+        if isinstance(item, SpreadExpression):
+            items.extend(execute_spread(item, stack, RuntimeValueType.Array).value)
+            check_size(len(items))
+        else:
+            items.append(execute(item, stack))
+    return RuntimeValue.of(items)
+
+
+def execute_object(ast: ObjectExpression, stack: Stack) -> RuntimeValue:
+    # Later entries override earlier ones
+    entries: Dict[str, RuntimeValue] = {}
+    for entry in ast.entries:
+        if isinstance(entry, SpreadExpression):
+            entries.update(execute_spread(entry, stack, RuntimeValueType.Object).value)
+            check_size(len(entries))
+            continue
+        key, value = entry
+        if isinstance(key, BaseExpression):
+            key = execute(key, stack).to_string()
+        entries[key] = execute(value, stack)
+    return RuntimeValue.of(entries)
+
+
+def make_function(ast: LambdaExpression, stack: Stack) -> RuntimeValue:
+    """Functions defined in a query close over the stack they're defined in"""
+    arity = len(ast.params)
+
+    def definition(arguments: List[BaseExpression], caller: Stack, exec):
+        if len(arguments) < arity:
+            raise MistQLRuntimeError(f"{ast.name} takes at least {arity} arguments")
+        if len(arguments) > arity:
+            raise MistQLRuntimeError(f"{ast.name} takes at most {arity} arguments")
+        bindings = {ast.name: function}
+        for param, argument in zip(ast.params, arguments):
+            bindings[param] = exec(argument, caller)
+        return execute(ast.body, add_bindings_to_stack(bindings, stack))
+
+    function = RuntimeValue.wrap_function_def(definition)
+    return function
+
+
 @typechecked
 def execute(ast: BaseExpression, stack: Stack) -> RuntimeValue:
+    try:
+        return execute_expression(ast, stack)
+    except MistQLException as e:
+        # The innermost expression with a span is the most precise location
+        if e.start is None and ast.start is not None:
+            e.start = ast.start
+            e.end = ast.end
+        raise
+
+
+def execute_expression(ast: BaseExpression, stack: Stack) -> RuntimeValue:
+    step()
     if not isinstance(ast, BaseExpression):
         raise OpenAnIssueIfYouGetThisError(
             f"Expected to evaluate an expression, got {ast}"
@@ -59,17 +156,20 @@ def execute(ast: BaseExpression, stack: Stack) -> RuntimeValue:
     if isinstance(ast, ValueExpression):
         return ast.value
     elif isinstance(ast, RefExpression):
-        return find_in_stack(stack, ast.name, ast.absolute)
+        return find_in_stack(stack, ast.name, ast.absolute, ast.quoted)
     elif isinstance(ast, FnExpression):
         return execute_fncall(ast.fn, ast.args, stack)
     elif isinstance(ast, ArrayExpression):
-        return RuntimeValue.of([execute(item, stack) for item in ast.items])
+        return execute_array(ast, stack)
     elif isinstance(ast, ObjectExpression):
-        return RuntimeValue.of(
-            {key: execute(value, stack) for key, value in ast.entries.items()}
-        )
+        return execute_object(ast, stack)
     elif isinstance(ast, PipeExpression):
         return execute_pipe(ast.stages, stack)
+    elif isinstance(ast, LetExpression):
+        value = execute(ast.value, stack)
+        return execute(ast.body, add_bindings_to_stack({ast.name: value}, stack))
+    elif isinstance(ast, LambdaExpression):
+        return make_function(ast, stack)
     raise NotImplementedError("execute() not implemented for " + str(ast.type))
 
 
@@ -77,5 +177,15 @@ def execute_outer(
     ast: Expression,
     data: RuntimeValue,
     extras: Mapping[str, Union[Callable, RuntimeValue]],
+    limits: Optional[Limits] = None,
 ) -> RuntimeValue:
-    return execute(ast, build_initial_stack(data, builtins, extras))
+    budget = Budget(limits) if limits and limits.any() else None
+    token = current_budget.set(budget)
+    try:
+        return execute(ast, build_initial_stack(data, builtins, extras))
+    except RecursionError:
+        raise MistQLResourceLimitError(
+            "Exceeded the maximum recursion depth"
+        ) from None
+    finally:
+        current_budget.reset(token)
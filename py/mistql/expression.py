@@ -1,5 +1,5 @@
 from enum import Enum
-from typing import Dict, List, Union, Any
+from typing import List, Optional, Tuple, Union, Any
 from mistql.runtime_value import RuntimeValue
 
 from typeguard import typechecked
@@ -13,15 +13,31 @@ class ExpressionType(Enum):
     Array = "array"
     Object = "object"
     Pipe = "pipe"
+    Let = "let"
+    Lambda = "lambda"
+    Spread = "spread"
 
 
 class BaseExpression:
-    """Represents the MistQL expression, after parsing"""
+    """
+    Represents the MistQL expression, after parsing.
+
+    Start and end are the offsets of the expression within the source query,
+    and are None for expressions that weren't produced by the parser.
+    """
+
+    start: Optional[int] = None
+    end: Optional[int] = None
 
     @typechecked
     def __init__(self, type: ExpressionType):
         self.type = type
 
+    def with_span(self, start: Optional[int], end: Optional[int]):
+        self.start = start
+        self.end = end
+        return self
+
 
 class FnExpression(BaseExpression):
     @typechecked
@@ -33,10 +49,13 @@ class FnExpression(BaseExpression):
 
 class RefExpression(BaseExpression):
     @typechecked
-    def __init__(self, name: str, absolute: bool = False):
+    def __init__(self, name: str, absolute: bool = False, quoted: bool = False):
         super().__init__(ExpressionType.Reference)
         self.name = name
         self.absolute = absolute
+        # Quoted in backticks, so a name like `@` or `@^` is a field rather
+        # than a context
+        self.quoted = quoted
 
 
 class ValueExpression(BaseExpression):
@@ -50,6 +69,15 @@ class ValueExpression(BaseExpression):
         return cls(RuntimeValue.of(value))
 
 
+class SpreadExpression(BaseExpression):
+    """`...value` in an array or object literal, splicing in its items or entries"""
+
+    @typechecked
+    def __init__(self, value: BaseExpression):
+        super().__init__(ExpressionType.Spread)
+        self.value = value
+
+
 class ArrayExpression(BaseExpression):
     @typechecked
     def __init__(self, items: List[BaseExpression]):
@@ -57,9 +85,15 @@ class ArrayExpression(BaseExpression):
         self.items = items
 
 
+# A key and its value, where computed keys are expressions, or a spread
+ObjectEntry = Union[Tuple[Union[str, BaseExpression], BaseExpression], SpreadExpression]
+
+
 class ObjectExpression(BaseExpression):
+    """Entries are evaluated in order, so later keys override earlier ones"""
+
     @typechecked
-    def __init__(self, entries: Dict[str, BaseExpression]):
+    def __init__(self, entries: List[ObjectEntry]):
         super().__init__(ExpressionType.Object)
         self.entries = entries
 
@@ -71,6 +105,28 @@ class PipeExpression(BaseExpression):
         self.stages = stages
 
 
+class LetExpression(BaseExpression):
+    """Binds the value to the name while evaluating the body"""
+
+    @typechecked
+    def __init__(self, name: str, value: BaseExpression, body: BaseExpression):
+        super().__init__(ExpressionType.Let)
+        self.name = name
+        self.value = value
+        self.body = body
+
+
+class LambdaExpression(BaseExpression):
+    """A function defined within a query, named for recursion and errors"""
+
+    @typechecked
+    def __init__(self, name: str, params: List[str], body: BaseExpression):
+        super().__init__(ExpressionType.Lambda)
+        self.name = name
+        self.params = params
+        self.body = body
+
+
 Expression = Union[
     FnExpression,
     RefExpression,
@@ -78,4 +134,7 @@ Expression = Union[
     ArrayExpression,
     ObjectExpression,
     PipeExpression,
+    LetExpression,
+    LambdaExpression,
+    SpreadExpression,
 ]
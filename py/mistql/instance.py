@@ -1,26 +1,121 @@
-from typing import Dict, Union, Callable, Optional, Any
+from datetime import datetime, timezone
+from typing import Dict, List, Union, Callable, Optional, Any
 
+from .analyze import analyze
+from .dates import format_iso, from_datetime
+from .exceptions import MistQLException
 from .execute import execute_outer
+from .expression import BaseExpression
 from .runtime_value import RuntimeValue
 from .gardenwall import input_garden_wall, output_garden_wall
-from .parse import parse
+from .limits import Limits
+from .parse import parse_cached
 
 
 ExtrasDict = Dict[str, Union[RuntimeValue, Callable]]
+Clock = Callable[[], datetime]
 
 
-class MistQLInstance:
+def system_clock() -> datetime:
+    return datetime.now(timezone.utc)
+
+
+class CompiledQuery:
+    """
+    A query that has already been parsed, and can be run repeatedly
+    against different data.
+    """
+
+    query: str
+    ast: BaseExpression
     extras: ExtrasDict
+    limits: Optional[Limits]
+    clock: Clock
 
-    def __init__(self, extras: Optional[ExtrasDict] = None):
-        self.extras = extras or {}
+    def __init__(
+        self,
+        query: str,
+        ast: BaseExpression,
+        extras: ExtrasDict,
+        limits: Optional[Limits] = None,
+        clock: Clock = system_clock,
+    ):
+        self.query = query
+        self.ast = ast
+        self.extras = extras
+        self.limits = limits
+        self.clock = clock
 
-    def query(self, query: str, data: Any):
-        ast = parse(query)
+    def run(self, data: Any):
         data = input_garden_wall(data)
-        result = execute_outer(ast, data, self.extras)
+        # now is read once per run, so it's the same everywhere in a query
+        extras: ExtrasDict = {
+            "now": RuntimeValue.of(format_iso(from_datetime(self.clock())))
+        }
+        extras.update(self.extras)
+        try:
+            result = execute_outer(self.ast, data, extras, self.limits)
+        except MistQLException as e:
+            if e.query is None:
+                e.query = self.query
+            raise
         return_value = output_garden_wall(result)
         return return_value
 
+    def analyze(self, schema: Any = None) -> List[MistQLException]:
+        """
+        Finds, without running this query, the errors certain to happen when
+        each of its expressions is evaluated.
+
+        :param schema: An optional JSON Schema describing the data.
+        """
+        errors = analyze(self.ast, schema, self.extras)
+        for error in errors:
+            error.query = self.query
+        return errors
+
+
+class MistQLInstance:
+    """
+    Runs queries with a set of extra functions, and optionally caps the
+    resources each run may use. Exceeding a cap raises a
+    MistQLResourceLimitError.
+
+    :param extras: Extra functions and values to make available to queries.
+    :param max_steps: The most steps a query may take, counting each
+        expression evaluated and each item a function works through.
+    :param timeout: The most seconds a query may run for.
+    :param max_collection_size: The largest array, object, or string a
+        function may return, with strings measured in code points.
+    :param max_depth: The most deeply function calls may nest.
+    :param now: A function returning the time that queries see as ``now``.
+        Naive datetimes are taken to be in UTC. Defaults to the system clock.
+    """
+
+    extras: ExtrasDict
+    limits: Limits
+    clock: Clock
+
+    def __init__(
+        self,
+        extras: Optional[ExtrasDict] = None,
+        max_steps: Optional[int] = None,
+        timeout: Optional[float] = None,
+        max_collection_size: Optional[int] = None,
+        max_depth: Optional[int] = None,
+        now: Optional[Clock] = None,
+    ):
+        self.extras = extras or {}
+        self.limits = Limits(max_steps, timeout, max_collection_size, max_depth)
+        self.clock = now or system_clock
+
+    def compile(self, query: str) -> CompiledQuery:
+        return CompiledQuery(
+            query, parse_cached(query), self.extras, self.limits, self.clock
+        )
+
+    def query(self, query: str, data: Any):
+        return self.compile(query).run(data)
+
 
 default_instance = MistQLInstance()
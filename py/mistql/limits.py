@@ -0,0 +1,119 @@
+import time
+from contextvars import ContextVar
+from typing import Iterable, Iterator, Optional, TypeVar
+
+from mistql.exceptions import MistQLResourceLimitError
+
+
+class Limits:
+    """
+    Caps on the work a single run of a query may do. Limits left as None
+    aren't enforced.
+
+    :param max_steps: The most steps a query may take. Each expression it
+        evaluates is a step, as is each item a function works through.
+    :param timeout: The most seconds a query may run for.
+    :param max_collection_size: The largest array, object, or string a
+        function may return. Strings are measured in code points.
+    :param max_depth: The most deeply function calls may nest.
+    """
+
+    def __init__(
+        self,
+        max_steps: Optional[int] = None,
+        timeout: Optional[float] = None,
+        max_collection_size: Optional[int] = None,
+        max_depth: Optional[int] = None,
+    ):
+        self.max_steps = max_steps
+        self.timeout = timeout
+        self.max_collection_size = max_collection_size
+        self.max_depth = max_depth
+
+    def any(self) -> bool:
+        return any(
+            limit is not None
+            for limit in (
+                self.max_steps,
+                self.timeout,
+                self.max_collection_size,
+                self.max_depth,
+            )
+        )
+
+
+class Budget:
+    """The resources left to a single run of a query."""
+
+    def __init__(self, limits: Limits):
+        self.limits = limits
+        self.steps = 0
+        self.depth = 0
+        self.deadline = (
+            None if limits.timeout is None else time.monotonic() + limits.timeout
+        )
+
+    def step(self):
+        self.steps += 1
+        max_steps = self.limits.max_steps
+        if max_steps is not None and self.steps > max_steps:
+            raise MistQLResourceLimitError(f"Exceeded the limit of {max_steps} steps")
+        if self.deadline is not None and time.monotonic() > self.deadline:
+            raise MistQLResourceLimitError(
+                f"Exceeded the timeout of {self.limits.timeout} seconds"
+            )
+
+    def enter(self):
+        self.depth += 1
+        max_depth = self.limits.max_depth
+        if max_depth is not None and self.depth > max_depth:
+            raise MistQLResourceLimitError(
+                f"Exceeded the maximum call depth of {max_depth}"
+            )
+
+    def leave(self):
+        self.depth -= 1
+
+    def check_size(self, size: int):
+        max_size = self.limits.max_collection_size
+        if max_size is not None and size > max_size:
+            raise MistQLResourceLimitError(
+                f"Exceeded the maximum collection size of {max_size}"
+            )
+
+
+# The budget of the query running in this context, if it has any limits
+current_budget: ContextVar[Optional[Budget]] = ContextVar(
+    "current_budget", default=None
+)
+
+
+def step():
+    """Counts one step towards the step limit, and checks the deadline."""
+    budget = current_budget.get()
+    if budget is not None:
+        budget.step()
+
+
+T = TypeVar("T")
+
+
+def stepped(items: Iterable[T]) -> Iterator[T]:
+    """
+    Yields each item, counting a step for each, so that loops within
+    functions can't run past the step limit or the deadline.
+    """
+    budget = current_budget.get()
+    if budget is None:
+        yield from items
+        return
+    for item in items:
+        budget.step()
+        yield item
+
+
+def check_size(size: int):
+    """Checks the size of a collection that's about to be returned."""
+    budget = current_budget.get()
+    if budget is not None:
+        budget.check_size(size)
@@ -1,4 +1,3 @@
-from lark import Lark, Tree, Token
 from mistql.expression import (
     RefExpression,
     FnExpression,
@@ -6,136 +5,554 @@ from mistql.expression import (
     ArrayExpression,
     ObjectExpression,
     PipeExpression,
+    LetExpression,
+    LambdaExpression,
+    SpreadExpression,
 )
-from typing import Union, List, Any
+from typing import Any, Callable, Dict, List, Optional, Tuple, TypeVar, Union
+from functools import lru_cache
 import json
+import re
 
-from mistql.expression import BaseExpression
-from mistql.exceptions import OpenAnIssueIfYouGetThisError
-
-
-mistql_parser = Lark.open("grammar.lark", rel_to=__file__, parser="earley")
-
-function_mappings = {
-    "dot": ".",
-    "neg": "-/unary",
-    "not": "!/unary",
-    "gt": ">",
-    "lt": "<",
-    "gte": ">=",
-    "lte": "<=",
-    "eq": "==",
-    "neq": "!=",
-    "match": "=~",
-    "plus": "+",
-    "minus": "-",
-    "mul": "*",
-    "div": "/",
-    "mod": "%",
-    "and": "&&",
-    "or": "||",
+from mistql.expression import BaseExpression, ObjectEntry
+from mistql.exceptions import MistQLParseError
+
+
+# Binary operators, in order of increasing precedence. All are left associative.
+binary_operators: List[List[str]] = [
+    ["??"],
+    ["||"],
+    ["&&"],
+    ["==", "!=", "=~"],
+    [">", "<", ">=", "<="],
+    ["+", "-"],
+    ["*", "/", "%"],
+]
+
+operator_precedence: Dict[str, int] = {
+    operator: precedence
+    for precedence, level in enumerate(binary_operators)
+    for operator in level
 }
 
+unary_operators = {
+    "-": "-/unary",
+    "!": "!/unary",
+}
 
-def process_lark_tree(lark_node: Tree) -> BaseExpression:
-    if lark_node.data == "array":
-        return ArrayExpression([from_lark(child) for child in lark_node.children])
-    elif lark_node.data == "object":
-        obj = {}
-        for child in lark_node.children:
-            if isinstance(child, str):
-                raise OpenAnIssueIfYouGetThisError(
-                    "Got string for child when we didn't expect it."
-                )
-            key = from_lark(child.children[0])
-            if isinstance(key, ValueExpression):
-                key = key.value.value
-            elif isinstance(key, RefExpression):
-                key = key.name
+# How deeply expressions may nest, e.g. in brackets, before parsing fails. Each
+# level recurses through the parser, so deeper input would exceed Python's
+# recursion limit.
+max_nesting = 64
+
+# Longest specials first, so that e.g. "||" isn't lexed as two pipes.
+specials = sorted(
+    list(operator_precedence.keys()) + ["?.", "..."] + list("!.|()[]{}:,="),
+    key=len,
+    reverse=True,
+)
+
+# Whitespace is significant, as it separates function arguments. These tokens
+# swallow whitespace to their left and right, respectively.
+vacuums_left = set(")]}.:|,=") | {"?."} | set(operator_precedence.keys())
+vacuums_right = set("([{.:|,=") | {"?.", "..."} | set(operator_precedence.keys())
+
+keywords = {"true": True, "false": False, "null": None}
+
+number_regex = re.compile(r"(0|[1-9][0-9]*)(\.[0-9]*)?([eE][+-]?[0-9]+)?")
+ref_regex = re.compile(r"[a-zA-Z_][a-zA-Z0-9_]*")
+string_regex = re.compile(r'"(\\.|[^"\\])*"', re.DOTALL)
+whitespace_regex = re.compile(r"\s+")
+
+
+class Token:
+    """
+    A single lexical token. Kind is one of "value", "ref", or "special".
+
+    Quoted refs are written in backticks, and can name any field. They are
+    never keywords.
+    """
+
+    def __init__(
+        self,
+        kind: str,
+        value: Union[str, float, bool, None],
+        pos: int,
+        end: int,
+        quoted: bool = False,
+    ):
+        self.kind = kind
+        self.value = value
+        self.pos = pos
+        self.end = end
+        self.quoted = quoted
+
+    def is_special(self, *values: str) -> bool:
+        return self.kind == "special" and self.value in values
+
+    def __repr__(self):
+        if self.kind == "special" and self.value == " ":
+            return "whitespace"
+        if self.kind == "value":
+            return json.dumps(self.value)
+        if self.quoted:
+            return f"`{self.value}`"
+        return str(self.value)
+
+
+def _is_whitespace(token: Optional[Token]) -> bool:
+    return token is not None and token.is_special(" ")
+
+
+def lex(raw: str) -> List[Token]:
+    tokens: List[Token] = []
+    i = 0
+    while i < len(raw):
+        char = raw[i]
+        if char.isdigit() and char.isascii():
+            match = number_regex.match(raw, i)
+            assert match is not None
+            tokens.append(Token("value", float(match.group()), i, match.end()))
+            i = match.end()
+        elif char.isspace():
+            match = whitespace_regex.match(raw, i)
+            assert match is not None
+            tokens.append(Token("special", " ", i, match.end()))
+            i = match.end()
+        elif ref_regex.match(char):
+            match = ref_regex.match(raw, i)
+            assert match is not None
+            name = match.group()
+            if name in keywords:
+                tokens.append(Token("value", keywords[name], i, match.end()))
             else:
-                raise OpenAnIssueIfYouGetThisError(f"Unknown key type {type(key)}")
-            value = from_lark(child.children[1])
-            obj[key] = value
-        return ObjectExpression(obj)
-    elif lark_node.data == "pipe":
-        return PipeExpression([from_lark(child) for child in lark_node.children])
-    elif lark_node.data == "fncall":
-        return FnExpression(
-            from_lark(lark_node.children[0]),
-            [from_lark(child) for child in lark_node.children[1:]],
+                tokens.append(Token("ref", name, i, match.end()))
+            i = match.end()
+        elif char == "@" or char == "$":
+            # `@^` is the context enclosing `@`, `@^^` the one enclosing that
+            end = i + 1
+            while char == "@" and raw.startswith("^", end):
+                end += 1
+            tokens.append(Token("ref", raw[i:end], i, end))
+            i = end
+        elif char == "`":
+            end = raw.find("`", i + 1)
+            if end == -1:
+                raise MistQLParseError("Unterminated quoted identifier", raw, i, ["`"])
+            tokens.append(Token("ref", raw[i + 1 : end], i, end + 1, quoted=True))
+            i = end + 1
+        elif char == '"':
+            match = string_regex.match(raw, i)
+            if match is None:
+                raise MistQLParseError("Unterminated string literal", raw, i, ['"'])
+            try:
+                value = json.loads(match.group())
+            except json.JSONDecodeError:
+                raise MistQLParseError("Invalid string literal", raw, i)
+            tokens.append(Token("value", value, i, match.end()))
+            i = match.end()
+        else:
+            special = next((s for s in specials if raw.startswith(s, i)), None)
+            if special is None:
+                raise MistQLParseError(f"Unexpected character '{char}'", raw, i)
+            if special in vacuums_left and tokens and _is_whitespace(tokens[-1]):
+                tokens.pop()
+            tokens.append(Token("special", special, i, i + len(special)))
+            i += len(special)
+            if special in vacuums_right:
+                match = whitespace_regex.match(raw, i)
+                if match is not None:
+                    i = match.end()
+
+    # Leading and trailing whitespace is never significant
+    if tokens and _is_whitespace(tokens[0]):
+        tokens.pop(0)
+    if tokens and _is_whitespace(tokens[-1]):
+        tokens.pop()
+    return tokens
+
+
+T = TypeVar("T")
+
+
+class Parser:
+    """
+    A precedence climbing parser over the tokens produced by `lex`.
+
+    Each method corresponds to a level of the grammar in grammar.lark, from
+    the loosest binding (pipes) to the tightest (literals and references).
+    """
+
+    def __init__(self, raw: str, tokens: List[Token]):
+        self.raw = raw
+        self.tokens = tokens
+        self.offset = 0
+        # End of the most recently consumed token, used to close spans
+        self.last_end = 0
+        # Whether a whitespace separated `in` ends the current expression, as
+        # it does in the value of a let-binding
+        self.in_let_value = False
+        # How many expressions the parser is currently inside of
+        self.depth = 0
+
+    def peek(self) -> Optional[Token]:
+        if self.offset < len(self.tokens):
+            return self.tokens[self.offset]
+        return None
+
+    def advance(self) -> None:
+        self.last_end = self.tokens[self.offset].end
+        self.offset += 1
+
+    def start(self) -> int:
+        token = self.peek()
+        return token.pos if token is not None else len(self.raw)
+
+    def spanned(self, expression: BaseExpression, start: int) -> BaseExpression:
+        return expression.with_span(start, self.last_end)
+
+    def peek_special(self, *values: str) -> bool:
+        token = self.peek()
+        return token is not None and token.is_special(*values)
+
+    def unexpected(self, expected: List[str]) -> MistQLParseError:
+        token = self.peek()
+        if token is None:
+            return MistQLParseError("Unexpected EOF", self.raw, len(self.raw), expected)
+        return MistQLParseError(
+            f"Unexpected token {token}", self.raw, token.pos, expected
         )
-    elif lark_node.data in function_mappings:
-        return FnExpression(
-            RefExpression(function_mappings[lark_node.data], absolute=True),
-            [from_lark(child) for child in lark_node.children[:]],
+
+    def expect(self, value: str) -> None:
+        if not self.peek_special(value):
+            raise self.unexpected([value])
+        self.advance()
+
+    def parse_query(self) -> BaseExpression:
+        result = self.parse_piped()
+        if self.peek() is not None:
+            raise self.unexpected(["EOF"])
+        return result
+
+    def peek_ahead(self, distance: int) -> Optional[Token]:
+        if self.offset + distance < len(self.tokens):
+            return self.tokens[self.offset + distance]
+        return None
+
+    def peek_ref(self, distance: int, name: Optional[str] = None) -> bool:
+        token = self.peek_ahead(distance)
+        if token is None or token.kind != "ref" or token.quoted:
+            return False
+        return token.value == name if name else token.value[0] not in "@$"
+
+    def at_let(self) -> bool:
+        # `let` is only a keyword when followed by a binding, so references
+        # named `let` keep working
+        return (
+            self.peek_ref(0, "let")
+            and _is_whitespace(self.peek_ahead(1))
+            and self.peek_ref(2)
+            and self.peek_ahead(3) is not None
+            and self.peek_ahead(3).is_special("=")  # type: ignore
         )
-    elif lark_node.data == "index":
-        # This is gross becase i can't figure out how to get the tree to look
-        # a little more sensible.
-        base, indexing = lark_node.children
-        if isinstance(indexing, str):
-            raise OpenAnIssueIfYouGetThisError(
-                "Got string for child when we didn't expect it."
+
+    def def_params(self) -> Optional[List[str]]:
+        """The parameters of the function defined here, or None if there isn't one"""
+        if not (
+            self.peek_ref(0, "def")
+            and _is_whitespace(self.peek_ahead(1))
+            and self.peek_ref(2)
+        ):
+            return None
+        params: List[str] = []
+        distance = 3
+        while _is_whitespace(self.peek_ahead(distance)) and self.peek_ref(
+            distance + 1
+        ):
+            params.append(str(self.tokens[self.offset + distance + 1].value))
+            distance += 2
+        token = self.peek_ahead(distance)
+        if not params or token is None or not token.is_special("="):
+            return None
+        return params
+
+    def at_let_in(self) -> bool:
+        return (
+            self.in_let_value
+            and self.peek_special(" ")
+            and self.peek_ref(1, "in")
+        )
+
+    def parse_nested(self, parse: Callable[[], T]) -> T:
+        """Parses within brackets, where `in` can't end a let-binding's value"""
+        in_let_value = self.in_let_value
+        self.in_let_value = False
+        try:
+            return parse()
+        finally:
+            self.in_let_value = in_let_value
+
+    def descend(self, parse: Callable[[], T]) -> T:
+        """
+        Parses a subexpression, failing rather than exceeding the recursion
+        limit on deeply nested input.
+        """
+        if self.depth == max_nesting:
+            raise MistQLParseError(
+                "Exceeded the maximum nesting depth", self.raw, self.start()
             )
-        innards = indexing.children[0]
-        if isinstance(innards, str):
-            raise OpenAnIssueIfYouGetThisError(
-                "Got string for child when we didn't expect it."
+        self.depth += 1
+        try:
+            return parse()
+        finally:
+            self.depth -= 1
+
+    def parse_let_value(self) -> BaseExpression:
+        """Parses the value of a binding, along with the `in` that ends it"""
+        in_let_value = self.in_let_value
+        self.in_let_value = True
+        value = self.parse_piped()
+        if not self.at_let_in():
+            raise self.unexpected(["in"])
+        self.in_let_value = in_let_value
+        self.advance()
+        self.advance()
+        if self.peek_special(" "):
+            self.advance()
+        return value
+
+    def parse_let(self) -> BaseExpression:
+        start = self.start()
+        self.advance()
+        self.advance()
+        name = self.tokens[self.offset].value
+        self.advance()
+        self.expect("=")
+        value = self.parse_let_value()
+        body = self.parse_piped()
+        return self.spanned(LetExpression(str(name), value, body), start)
+
+    def parse_def(self, params: List[str]) -> BaseExpression:
+        # `def f x = value in body` is shorthand for binding f to a function
+        start = self.start()
+        self.advance()
+        self.advance()
+        name = str(self.tokens[self.offset].value)
+        for _ in range(1 + 2 * len(params)):
+            self.advance()
+        self.expect("=")
+        value = self.parse_let_value()
+        function = LambdaExpression(name, params, value)
+        function.with_span(start, value.end)
+        body = self.parse_piped()
+        return self.spanned(LetExpression(name, function, body), start)
+
+    def parse_piped(self) -> BaseExpression:
+        return self.descend(self.parse_pipeline)
+
+    def parse_pipeline(self) -> BaseExpression:
+        if self.at_let():
+            return self.parse_let()
+        params = self.def_params()
+        if params is not None:
+            return self.parse_def(params)
+        start = self.start()
+        first, _ = self.parse_fncall()
+        stages = [first]
+        while self.peek_special("|"):
+            self.advance()
+            stage, is_fncall = self.parse_fncall()
+            # Every stage after the first is called with the piped data
+            if not is_fncall:
+                stage = FnExpression(stage, []).with_span(stage.start, stage.end)
+            stages.append(stage)
+        if len(stages) == 1:
+            return first
+        return self.spanned(PipeExpression(stages), start)
+
+    def parse_fncall(self) -> Tuple[BaseExpression, bool]:
+        start = self.start()
+        head = self.parse_binary(0)
+        args: List[BaseExpression] = []
+        while self.peek_special(" ") and not self.at_let_in():
+            self.advance()
+            args.append(self.parse_binary(0))
+        if not args:
+            return head, False
+        return self.spanned(FnExpression(head, args), start), True
+
+    def parse_binary(self, min_precedence: int) -> BaseExpression:
+        start = self.start()
+        left = self.parse_unary()
+        while True:
+            token = self.peek()
+            if token is None or token.kind != "special":
+                break
+            precedence = operator_precedence.get(str(token.value))
+            if precedence is None or precedence < min_precedence:
+                break
+            self.advance()
+            operator = RefExpression(str(token.value), absolute=True)
+            operator.with_span(token.pos, token.end)
+            right = self.parse_binary(precedence + 1)
+            left = self.spanned(FnExpression(operator, [left, right]), start)
+        return left
+
+    def parse_unary(self) -> BaseExpression:
+        token = self.peek()
+        if token is None or token.kind != "special":
+            return self.parse_postfix()
+        if token.value not in unary_operators:
+            return self.parse_postfix()
+        self.advance()
+        operator = RefExpression(unary_operators[str(token.value)], absolute=True)
+        operator.with_span(token.pos, token.end)
+        if self.peek_special(" "):
+            self.advance()
+        operand = self.descend(self.parse_unary)
+        return self.spanned(FnExpression(operator, [operand]), token.pos)
+
+    def parse_postfix(self) -> BaseExpression:
+        start = self.start()
+        base = self.parse_simple()
+        while True:
+            if self.peek_special(".", "?."):
+                # `?.` is the lenient form of `.`, giving null on non-objects
+                operator = str(self.tokens[self.offset].value)
+                self.advance()
+                token = self.peek()
+                if token is None or token.kind != "ref":
+                    raise self.unexpected(["reference"])
+                self.advance()
+                member = self.spanned(RefExpression(str(token.value)), token.pos)
+                accessor = RefExpression(operator, absolute=True)
+                base = self.spanned(FnExpression(accessor, [base, member]), start)
+            elif self.peek_special("["):
+                self.advance()
+                args = self.parse_nested(self.parse_index_innards)
+                args.append(base)
+                base = self.spanned(
+                    FnExpression(RefExpression("index", absolute=True), args), start
+                )
+            else:
+                return base
+
+    def parse_index_innards(self) -> List[BaseExpression]:
+        # Empty slots in an index expression are filled in with nulls, e.g.
+        # `[1:]` has the arguments `1` and `null`
+        args: List[BaseExpression] = []
+        prev_was_colon = True
+        while True:
+            if self.peek_special(":"):
+                self.advance()
+                if prev_was_colon:
+                    args.append(ValueExpression.of(None))
+                prev_was_colon = True
+            elif self.peek_special("]"):
+                self.advance()
+                break
+            elif prev_was_colon:
+                args.append(self.parse_piped())
+                prev_was_colon = False
+            else:
+                raise self.unexpected([":", "]"])
+        if prev_was_colon:
+            args.append(ValueExpression.of(None))
+        return args
+
+    def parse_simple(self) -> BaseExpression:
+        token = self.peek()
+        if token is None:
+            raise self.unexpected(["expression"])
+        if token.kind == "value":
+            self.advance()
+            return self.spanned(ValueExpression.of(token.value), token.pos)
+        elif token.kind == "ref":
+            self.advance()
+            ref = RefExpression(str(token.value), quoted=token.quoted)
+            return self.spanned(ref, token.pos)
+        elif token.is_special("("):
+            self.advance()
+            inner = self.parse_nested(self.parse_piped)
+            self.expect(")")
+            return inner
+        elif token.is_special("["):
+            self.advance()
+            items = self.parse_nested(
+                lambda: self.parse_sequence("]", self.parse_array_item)
             )
-        fnexp_args: List[BaseExpression] = []
-        prev_was_token = True
-        for child in innards.children:
-            if isinstance(child, Token) and child.value == ":":
-                if prev_was_token:
-                    fnexp_args.append(ValueExpression.of(None))
-                prev_was_token = True
-                continue
+            return self.spanned(ArrayExpression(items), token.pos)
+        elif token.is_special("{"):
+            self.advance()
+            entries = self.parse_nested(
+                lambda: self.parse_sequence("}", self.parse_object_entry)
+            )
+            return self.spanned(ObjectExpression(entries), token.pos)
+        raise self.unexpected(["expression"])
+
+    def parse_sequence(self, end: str, parse_item: Callable[[], Any]) -> List[Any]:
+        items: List[Any] = []
+        if self.peek_special(end):
+            self.advance()
+            return items
+        while True:
+            items.append(parse_item())
+            if self.peek_special(","):
+                self.advance()
+            elif self.peek_special(end):
+                self.advance()
+                return items
             else:
-                fnexp_args.append(from_lark(child))
-                prev_was_token = False
-        if prev_was_token:
-            fnexp_args.append(ValueExpression.of(None))
-        fnexp_args.append(from_lark(base))
-        return FnExpression(RefExpression("index", absolute=True), fnexp_args)
-    else:
-        raise OpenAnIssueIfYouGetThisError(
-            f"Unknown lark expression type: {lark_node.data}"
-        )
+                raise self.unexpected([",", end])
+
+    def parse_spread(self) -> BaseExpression:
+        start = self.start()
+        self.advance()
+        return self.spanned(SpreadExpression(self.parse_piped()), start)
+
+    def parse_array_item(self) -> BaseExpression:
+        if self.peek_special("..."):
+            return self.parse_spread()
+        return self.parse_piped()
+
+    def parse_object_entry(self) -> ObjectEntry:
+        token = self.peek()
+        if token is None:
+            raise self.unexpected(["key"])
+        key: Union[str, BaseExpression]
+        if token.is_special("..."):
+            return self.parse_spread()
+        elif token.is_special("("):
+            # Computed keys, e.g. `{(name): 1}`
+            self.advance()
+            key = self.parse_piped()
+            self.expect(")")
+            self.expect(":")
+            return key, self.parse_piped()
+        elif token.kind == "ref" and (token.quoted or token.value[0] not in "@$"):
+            key = str(token.value)
+        elif token.kind == "value" and isinstance(token.value, str):
+            key = token.value
+        elif token.kind == "value" and not isinstance(token.value, float):
+            # Keywords are still valid keys, e.g. `{null: 1}`
+            key = json.dumps(token.value)
+        else:
+            raise self.unexpected(["key"])
+        self.advance()
+        self.expect(":")
+        return key, self.parse_piped()
+
+
+def parse(raw: str) -> BaseExpression:
+    return Parser(raw, lex(raw)).parse_query()
+
+
+# Parsing dominates the cost of running small queries, so parsed ASTs are
+# cached by query string. Expressions are never mutated during execution, so
+# sharing them between calls is safe.
+PARSE_CACHE_SIZE = 1024
 
 
-def process_lark_token(lark_node: Token) -> BaseExpression:
-    if lark_node.type == "NUMBER":
-        return ValueExpression.of(float(lark_node.value))
-    elif lark_node.type == "ESCAPED_STRING":
-        value = json.loads(lark_node.value)
-        return ValueExpression.of(value)
-    elif lark_node.type == "TRUE":
-        return ValueExpression.of(True)
-    elif lark_node.type == "FALSE":
-        return ValueExpression.of(False)
-    elif lark_node.type == "NULL":
-        return ValueExpression.of(None)
-    elif lark_node.type == "CNAME":
-        return RefExpression(lark_node.value)
-    elif lark_node.type == "AT":
-        return RefExpression("@")
-    elif lark_node.type == "DOLLAR":
-        return RefExpression("$")
-    else:
-        raise OpenAnIssueIfYouGetThisError(f"Unknown token type {lark_node.type}")
-
-
-def from_lark(lark_node: Union[Any, str, Tree, Token]):
-    if isinstance(lark_node, Token):
-        return process_lark_token(lark_node)
-    elif isinstance(lark_node, Tree):
-        return process_lark_tree(lark_node)
-    else:
-        raise OpenAnIssueIfYouGetThisError(f"Unknown lark node type: {type(lark_node)}")
-
-
-def parse(raw):
-    # TODO: Translate errors from this function to something that inherits
-    # from MistQLException
-    parsed = mistql_parser.parse(raw)
-    return from_lark(parsed)
+@lru_cache(maxsize=PARSE_CACHE_SIZE)
+def parse_cached(raw: str) -> BaseExpression:
+    return parse(raw)
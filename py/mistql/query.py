@@ -1,6 +1,7 @@
-from typing import Any
+from typing import Any, List
 
-from .instance import default_instance
+from .exceptions import MistQLException
+from .instance import default_instance, CompiledQuery
 
 
 def query(query: str, data: Any) -> Any:
@@ -12,3 +13,25 @@ def query(query: str, data: Any) -> Any:
     :return: The result of the query.
     """
     return default_instance.query(query, data)
+
+
+def compile(query: str) -> CompiledQuery:
+    """
+    Parses a query once, so that it can be run against many pieces of data.
+
+    :param query: The query to compile.
+    :return: A compiled query, which can be executed via its `run` method.
+    """
+    return default_instance.compile(query)
+
+
+def analyze(query: str, schema: Any = None) -> List[MistQLException]:
+    """
+    Finds, without running a query, the errors certain to happen when each of
+    its expressions is evaluated.
+
+    :param query: The query to analyze.
+    :param schema: An optional JSON Schema describing the data.
+    :return: The errors found, each pointing into the query.
+    """
+    return default_instance.compile(query).analyze(schema)
@@ -179,9 +179,11 @@ class RuntimeValue:
         Compare two values
         """
         if a.type != b.type:
-            raise ValueError("Cannot compare MistQL values of different types")
+            raise MistQLTypeError("Cannot compare MistQL values of different types")
         elif not a.comparable():
-            raise ValueError("Cannot compare MistQL values of type " + str(a.type))
+            raise MistQLTypeError(
+                "Cannot compare MistQL values of type " + str(a.type)
+            )
         elif a.type == RuntimeValueType.Boolean:
             return int(a.value) - int(b.value)
         elif a.type == RuntimeValueType.Number:
@@ -258,7 +260,7 @@ class RuntimeValue:
         elif self.type == RuntimeValueType.Object:
             return {key: value.to_python() for key, value in self.value.items()}
         else:
-            raise ValueError(
+            raise MistQLTypeError(
                 "Cannot convert MistQL value type to Python: " + str(self.type)
             )
 
@@ -322,7 +324,9 @@ class RuntimeValue:
                 return "[regex]"
             else:
                 return "[unknown]"
-        raise ValueError("Cannot convert MistQL value to JSON: " + str(self.type))
+        raise MistQLTypeError(
+            "Cannot convert MistQL value to JSON: " + str(self.type)
+        )
 
     def to_string(self) -> str:
         """
@@ -339,7 +343,10 @@ class RuntimeValue:
         if self.type == RuntimeValueType.Number:
             return self.value
         elif self.type == RuntimeValueType.String:
-            return float(self.value)
+            try:
+                return float(self.value)
+            except ValueError:
+                raise MistQLTypeError("Cannot cast string to float: " + self.value)
         elif self.type == RuntimeValueType.Boolean:
             return float(self.value)
         elif self.type == RuntimeValueType.Null:
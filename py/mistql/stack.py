@@ -1,23 +1,51 @@
 from typing import List, Mapping, Callable, Union, Dict
-from mistql.runtime_value import RuntimeValue
+from mistql.runtime_value import RuntimeValue, RuntimeValueType
 from mistql.exceptions import MistQLReferenceError
 from typeguard import typechecked
 
-StackFrame = Dict[str, RuntimeValue]
-Stack = List[StackFrame]
 
+class ValueFrame:
+    """
+    A stack frame binding `@` to a value, and each field of the value to its
+    name. Fields are looked up in the value as needed, rather than copied, so
+    pushing a wide object is as cheap as pushing a number.
+    """
+
+    def __init__(self, value: RuntimeValue):
+        self.value = value
+
+    def __contains__(self, name: str) -> bool:
+        if name == "@":
+            return True
+        # Fields can be named anything, by quoting them in backticks, but can't
+        # shadow the root reference
+        return (
+            self.value.type == RuntimeValueType.Object
+            and name != "$"
+            and name in self.value.value
+        )
 
-def make_stack_entry_from_runtime_value(value: RuntimeValue) -> StackFrame:
-    new_stackframe = {"@": value}
-    for key in value.keys():
-        new_stackframe[key] = value.access(key)
-    return new_stackframe
+    def __getitem__(self, name: str) -> RuntimeValue:
+        if name == "@":
+            return self.value
+        if name not in self:
+            raise KeyError(name)
+        return self.value.value[name]
+
+
+StackFrame = Union[Dict[str, RuntimeValue], ValueFrame]
+Stack = List[StackFrame]
 
 
 def add_runtime_value_to_stack(value: RuntimeValue, stack: Stack):
-    new_stackframe = make_stack_entry_from_runtime_value(value)
     new_stack = stack.copy()
-    new_stack.append(new_stackframe)
+    new_stack.append(ValueFrame(value))
+    return new_stack
+
+
+def add_bindings_to_stack(bindings: Dict[str, RuntimeValue], stack: Stack):
+    new_stack = stack.copy()
+    new_stack.append(bindings)
     return new_stack
 
 
@@ -26,7 +54,7 @@ def build_initial_stack(
     builtins: Mapping[str, Callable],
     extras: Mapping[str, Union[Callable, RuntimeValue]],
 ) -> Stack:
-    functions_frame: StackFrame = {}
+    functions_frame: Dict[str, RuntimeValue] = {}
     for key, builtin in builtins.items():
         functions_frame[key] = RuntimeValue.wrap_function_def(builtin)
     for key, value in extras.items():
@@ -41,14 +69,51 @@ def build_initial_stack(
     return [
         functions_frame,
         {"$": RuntimeValue.of(dollar_var_dict)},
-        make_stack_entry_from_runtime_value(data),
+        ValueFrame(data),
     ]
 
 
+def find_enclosing_context(stack: Stack, name: str) -> RuntimeValue:
+    """
+    The value of `@` some number of contexts out, one for each caret in the
+    name. Every pipeline stage and every item a function evaluates an
+    expression against is a context of its own.
+    """
+    depth = len(name) - 1
+    for frame in reversed(stack):
+        if isinstance(frame, ValueFrame):
+            if depth == 0:
+                return frame.value
+            depth -= 1
+    raise MistQLReferenceError(f"Could not find {name} in stack")
+
+
+def find_context_field(stack: Stack, name: str) -> RuntimeValue:
+    """
+    A field named like a context, as `@` and `@^` can be when quoted in
+    backticks. Only contexts can hold such a name, so only their fields are
+    searched.
+    """
+    for frame in reversed(stack):
+        if (
+            isinstance(frame, ValueFrame)
+            and frame.value.type == RuntimeValueType.Object
+            and name in frame.value.value
+        ):
+            return frame.value.value[name]
+    raise MistQLReferenceError(f"Could not find {name} in stack")
+
+
 @typechecked
-def find_in_stack(stack: Stack, name: str, absolute: bool) -> RuntimeValue:
+def find_in_stack(
+    stack: Stack, name: str, absolute: bool, quoted: bool = False
+) -> RuntimeValue:
     if absolute:
         stack = stack[:1]
+    elif quoted and name.startswith("@"):
+        return find_context_field(stack, name)
+    elif name.startswith("@^"):
+        return find_enclosing_context(stack, name)
     for frame in reversed(stack):
         if name in frame:
             return frame[name]
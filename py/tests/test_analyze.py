@@ -0,0 +1,162 @@
+from mistql import analyze, MistQLInstance
+from mistql.exceptions import MistQLReferenceError, MistQLRuntimeError, MistQLTypeError
+import pytest
+
+schema = {
+    "type": "object",
+    "properties": {
+        "name": {"type": "string"},
+        "total": {"type": "number"},
+        "meta": {"type": "object"},
+        "items": {
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {"price": {"type": "number"}},
+                "additionalProperties": False,
+            },
+        },
+    },
+    "additionalProperties": False,
+}
+
+
+def messages(query, schema=None):
+    return [error.args[0] for error in analyze(query, schema)]
+
+
+@pytest.mark.parametrize(
+    "query",
+    [
+        "items | map price * 2 | sum",
+        "let n = count items in total / n",
+        "def double x = x * 2 in items | map (double price)",
+        "if (count items) name \"none\"",
+        "$.count items",
+        "datediff \"day\" name now",
+    ],
+)
+def test_accepts_valid_queries(query):
+    assert messages(query, schema) == []
+
+
+def test_assumes_any_json_data_without_a_schema():
+    assert messages("@ | filter x > 1 | map x + 1 | count") == []
+    assert messages("missing.field") == []
+
+
+def test_reports_unknown_functions():
+    [error] = analyze("cuont @")
+    assert isinstance(error, MistQLReferenceError)
+    assert error.args[0] == "Unknown function cuont"
+
+
+def test_reports_unknown_references_given_a_closed_schema():
+    assert messages("items | map prise", schema) == ["Unknown reference prise"]
+    assert messages("nmae", schema) == ["Unknown reference nmae"]
+
+
+def test_follows_enclosing_contexts():
+    assert messages("\"a\" | apply (@^ | count)") == [
+        "count: expected array, got string"
+    ]
+    assert messages("[1] | map @^^^") == ["Unknown reference @^^^"]
+
+
+def test_never_resolves_the_root_to_a_field():
+    schema = {
+        "type": "object",
+        "properties": {"$": {"type": "number"}},
+        "required": ["$"],
+    }
+    assert messages("count $.@", schema) == ["count: expected array, got object"]
+    assert messages("$.@.`$` + \"a\"", schema) == [
+        "add: cannot add number and string"
+    ]
+
+
+def test_reports_wrong_arities():
+    [error] = analyze("count 1 2")
+    assert isinstance(error, MistQLRuntimeError)
+    assert error.args[0] == "count takes at most 1 arguments"
+    assert messages("@ | map") == ["map takes at least 2 arguments"]
+    assert messages("def add a b = a + b in add 1") == [
+        "add takes at least 2 arguments"
+    ]
+    assert messages("cond true 1 false 2") == [
+        "cond: Expected pairs of conditions and results, followed by a default"
+    ]
+
+
+def test_infers_types_from_the_schema():
+    [error] = analyze("count meta", schema)
+    assert isinstance(error, MistQLTypeError)
+    assert error.args[0] == "count: expected array, got object"
+    assert messages("name + total", schema) == ["add: cannot add string and number"]
+    assert messages("items | map price + \"$\"", schema) == [
+        "add: cannot add number and string"
+    ]
+    assert messages("total.value", schema) == ["Cannot access value on number"]
+
+
+def test_infers_types_from_literals_and_builtins():
+    assert messages("1 < \"a\"") == ["Cannot compare number and string"]
+    assert messages("keys @ | sum | count") == ["count: expected array, got number"]
+    assert messages("{a: 1} | apply (a + \"b\")") == [
+        "add: cannot add number and string"
+    ]
+    assert messages("formatdate \"%Y\" true") == [
+        "formatdate: expected number or string, got boolean"
+    ]
+    assert messages("length [1]") == ["length: expected string, got array"]
+    assert messages("max \"a\" 1") == ["max: expected number, got string"]
+    assert messages("[1] | first | count") == [
+        "count: expected array, got null or number"
+    ]
+    assert messages("merge {a: 1} [1]") == ["merge: expected object, got array"]
+    assert messages("[{a: \"x\"}] | sumby a") == ["sumby: expected number, got string"]
+    assert messages("(1?.a ?? \"b\") + 1") == ["add: cannot add string and number"]
+    assert messages("cond true 1 false \"a\" null | count") == [
+        "count: expected array, got null or number or string"
+    ]
+    assert messages("{...[1]}") == ["Cannot spread array into an object"]
+    assert messages("{...{a: 1}, b: 2} | apply (a + \"x\")") == [
+        "add: cannot add number and string"
+    ]
+
+
+def test_follows_local_refs_and_unions():
+    schema = {
+        "$defs": {"price": {"type": "number"}},
+        "type": "object",
+        "properties": {
+            "price": {"$ref": "#/$defs/price"},
+            "tag": {"anyOf": [{"type": "string"}, {"type": "null"}]},
+        },
+    }
+    assert messages("price + \"a\"", schema) == ["add: cannot add number and string"]
+    assert messages("tag + 1", schema) == ["add: cannot add null or string and number"]
+
+
+def test_only_lets_required_properties_shadow_bindings():
+    schema = {
+        "type": "array",
+        "items": {"type": "object", "properties": {"x": {"type": "string"}}},
+    }
+    # Items without an x fall back to the binding
+    assert messages("let x = 5 in @ | map x + 1", schema) == []
+    schema["items"]["required"] = ["x"]
+    assert messages("let x = 5 in @ | map x + 1", schema) == [
+        "add: cannot add string and number"
+    ]
+
+
+def test_errors_point_into_the_query():
+    [error] = analyze("1 + (count {})")
+    assert error.start == 11
+    assert "^^" in str(error)
+
+
+def test_uses_instance_extras():
+    mq = MistQLInstance({"double": lambda x: x * 2})
+    assert mq.compile("double 1 | count").analyze() == []
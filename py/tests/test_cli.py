@@ -1,4 +1,9 @@
+import io
+import sys
 import tempfile
+
+import pytest
+
 from mistql.cli import main
 
 
@@ -44,3 +49,51 @@ def test_encoding_utf32():
 
 def test_encoding_ascii():
     enc_helper("ascii", nice_string)
+
+
+def jsonl_helper(args, input_text):
+    input_file = tempfile.NamedTemporaryFile(delete=False)
+    input_file.write(input_text.encode("utf-8"))
+    input_file.close()
+    output_file = tempfile.NamedTemporaryFile(delete=False)
+    output_file.close()
+    main(args + [input_file.name, "--output", output_file.name])
+    with open(output_file.name, "rb") as f:
+        return f.read().decode("utf-8")
+
+
+def test_file_jsonl_writes_one_result_per_line():
+    output = jsonl_helper(
+        ["a", "--file_jsonl"], '{"a": "x"}\n{"a": "y"}\n\n{"a": "z"}\n'
+    )
+    assert output == '"x"\n"y"\n"z"\n'
+
+
+def test_jsonl_reads_from_file():
+    output = jsonl_helper(["{b: a}", "--jsonl", "--file"], '{"a": "x"}\n{"a": "y"}\n')
+    assert output == '{"b": "x"}\n{"b": "y"}\n'
+
+
+def test_slurp_runs_the_query_once_over_every_line():
+    output = jsonl_helper(
+        ["@ | map a", "--slurp", "--file_jsonl"], '{"a": "x"}\n{"a": "y"}\n'
+    )
+    assert output == '["x", "y"]'
+
+
+def test_jsonl_reads_from_stdin():
+    output_file = tempfile.NamedTemporaryFile(delete=False)
+    output_file.close()
+    stdin = sys.stdin
+    sys.stdin = io.TextIOWrapper(io.BytesIO(b'"a"\n"b"\n'))
+    try:
+        main(["@ + @", "--jsonl", "--output", output_file.name])
+    finally:
+        sys.stdin = stdin
+    with open(output_file.name, "rb") as f:
+        assert f.read().decode("utf-8") == '"aa"\n"bb"\n'
+
+
+def test_slurp_requires_jsonl_input():
+    with pytest.raises(SystemExit):
+        main(["@", "--slurp", "--data", "[]"])
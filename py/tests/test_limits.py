@@ -0,0 +1,67 @@
+import pytest
+
+from mistql import MistQLInstance, MistQLResourceLimitError
+
+
+def test_runs_without_limits_by_default():
+    assert MistQLInstance().query("range 10000 | count", None) == 10000
+
+
+def test_limits_steps():
+    mq = MistQLInstance(max_steps=100)
+    assert mq.query("1 + 2", None) == 3
+    with pytest.raises(MistQLResourceLimitError, match="100 steps"):
+        mq.query("range 1000 | map @ + 1 | sum", None)
+
+
+def test_limits_time():
+    mq = MistQLInstance(timeout=0.05)
+    query = "range 40 | sequence true true true true true | count"
+    with pytest.raises(MistQLResourceLimitError, match="timeout"):
+        mq.query(query, None)
+
+
+def test_limits_time_within_functions():
+    mq = MistQLInstance(timeout=0.05)
+    with pytest.raises(MistQLResourceLimitError, match="timeout"):
+        mq.query("range 100000000 | count", None)
+    with pytest.raises(MistQLResourceLimitError, match="timeout"):
+        mq.query("split \"\" @ | count", "a" * 10000000)
+
+
+def test_limits_collection_size():
+    mq = MistQLInstance(max_collection_size=100)
+    assert mq.query("range 100 | count", None) == 100
+    with pytest.raises(MistQLResourceLimitError, match="collection size"):
+        mq.query("range 100000000", None)
+    with pytest.raises(MistQLResourceLimitError, match="collection size"):
+        mq.query("range 20 | sequence @ @ @ @", None)
+    with pytest.raises(MistQLResourceLimitError, match="collection size"):
+        mq.query("let s = @ + @ in let t = s + s in t + t", "a" * 30)
+
+
+def test_limits_call_depth():
+    mq = MistQLInstance(max_depth=20)
+    query = "def down n = if (n > 0) (down (n - 1)) n in down @"
+    assert mq.query(query, 3) == 0
+    with pytest.raises(MistQLResourceLimitError, match="depth of 20"):
+        mq.query(query, 50)
+
+
+def test_reports_running_out_of_stack_as_a_resource_limit():
+    query = "def down n = if (n > 0) (down (n - 1)) n in down @"
+    with pytest.raises(MistQLResourceLimitError, match="recursion depth"):
+        MistQLInstance().query(query, 100000)
+
+
+def test_limits_apply_to_each_run():
+    compiled = MistQLInstance(max_steps=50).compile("range 5 | map @ * 2 | sum")
+    for _ in range(3):
+        assert compiled.run(None) == 20
+
+
+def test_errors_point_into_the_query():
+    mq = MistQLInstance(max_collection_size=10)
+    with pytest.raises(MistQLResourceLimitError) as e:
+        mq.query("[1, 2] | map (range 100)", None)
+    assert e.value.start == 14
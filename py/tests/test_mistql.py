@@ -1,7 +1,11 @@
-from mistql import __version__, query
+from datetime import datetime, timedelta, timezone
+from mistql import __version__, query, compile, MistQLInstance, RuntimeValue
+from mistql.exceptions import MistQLReferenceError, MistQLTypeError
+from mistql.parse import parse_cached
 import toml
 import json
 import os
+import pytest
 
 
 def test_version():
@@ -14,3 +18,60 @@ def test_version():
 
 def test_query_is_callable():
     assert query and callable(query)
+
+
+def test_compiled_query_runs_on_different_data():
+    compiled = compile("@ | filter type == \"click\" | count")
+    assert compiled.run([{"type": "click"}, {"type": "purchase"}]) == 1
+    assert compiled.run([{"type": "click"}, {"type": "click"}]) == 2
+
+
+def test_compiled_query_uses_instance_extras():
+    mq = MistQLInstance({"double": lambda x: x * 2})
+    compiled = mq.compile("double @")
+    assert compiled.run(2) == 4
+    assert compiled.run(5) == 10
+
+
+def test_now_comes_from_the_instance_clock():
+    mq = MistQLInstance(now=lambda: datetime(2021, 3, 4, 5, 6, 7, 890123))
+    assert mq.query("now", None) == "2021-03-04T05:06:07.890Z"
+    assert mq.query('truncdate "month" now', None) == "2021-03-01T00:00:00.000Z"
+    offset = timezone(timedelta(hours=-5))
+    mq = MistQLInstance(now=lambda: datetime(2021, 3, 4, 22, tzinfo=offset))
+    assert mq.query("now", None) == "2021-03-05T03:00:00.000Z"
+
+
+def test_now_can_be_overridden_by_extras():
+    mq = MistQLInstance({"now": RuntimeValue.of("2000-01-01")})
+    assert mq.query('formatdate "%Y" now', None) == "2000"
+
+
+def test_parsed_queries_are_cached():
+    parse_cached.cache_clear()
+    query("count @", [1, 2, 3])
+    query("count @", [1, 2])
+    assert parse_cached.cache_info().hits == 1
+    assert parse_cached.cache_info().misses == 1
+
+
+def test_runtime_errors_point_at_the_failing_expression():
+    with pytest.raises(MistQLTypeError) as exc_info:
+        query('[1, 2] | map (@ + "a")', None)
+    err = exc_info.value
+    assert (err.start, err.end) == (14, 21)
+    assert str(err) == (
+        'add: <mistql 1> and <mistql "a"> are not the same type at position 14\n'
+        "---\n"
+        '[1, 2] | map (@ + "a")\n'
+        "              ^^^^^^^\n"
+        "---\n"
+    )
+
+
+def test_reference_errors_point_at_the_reference():
+    with pytest.raises(MistQLReferenceError) as exc_info:
+        query("[1, 2]\n  | filter missing", None)
+    err = exc_info.value
+    assert (err.start, err.end) == (18, 25)
+    assert "\n  | filter missing\n           ^^^^^^^\n" in str(err)
@@ -0,0 +1,221 @@
+import pytest
+
+from mistql.expression import (
+    ArrayExpression,
+    BaseExpression,
+    FnExpression,
+    LambdaExpression,
+    LetExpression,
+    ObjectExpression,
+    PipeExpression,
+    RefExpression,
+    SpreadExpression,
+    ValueExpression,
+)
+from mistql.exceptions import MistQLParseError
+from mistql.parse import parse
+
+
+def dump(expression: BaseExpression):
+    """Converts an expression into nested tuples, for easy comparison"""
+    if isinstance(expression, FnExpression):
+        return ("fn", dump(expression.fn), [dump(arg) for arg in expression.args])
+    elif isinstance(expression, RefExpression):
+        return ("ref", expression.name, expression.absolute)
+    elif isinstance(expression, ValueExpression):
+        return ("value", expression.value.value)
+    elif isinstance(expression, ArrayExpression):
+        return ("array", [dump(item) for item in expression.items])
+    elif isinstance(expression, ObjectExpression):
+        return ("object", [dump_entry(entry) for entry in expression.entries])
+    elif isinstance(expression, PipeExpression):
+        return ("pipe", [dump(stage) for stage in expression.stages])
+    elif isinstance(expression, LetExpression):
+        return ("let", expression.name, dump(expression.value), dump(expression.body))
+    elif isinstance(expression, LambdaExpression):
+        return ("lambda", expression.name, expression.params, dump(expression.body))
+    elif isinstance(expression, SpreadExpression):
+        return ("spread", dump(expression.value))
+    raise TypeError(expression)
+
+
+def dump_entry(entry):
+    if isinstance(entry, SpreadExpression):
+        return dump(entry)
+    key, value = entry
+    return (key if isinstance(key, str) else dump(key), dump(value))
+
+
+def op(name, *args):
+    return ("fn", ("ref", name, True), list(args))
+
+
+def ref(name):
+    return ("ref", name, False)
+
+
+def value(v):
+    return ("value", v)
+
+
+def test_binary_operator_precedence():
+    assert dump(parse("1 + 2 * 3")) == op("+", value(1), op("*", value(2), value(3)))
+
+
+def test_binary_operators_are_left_associative():
+    assert dump(parse("1 - 2 - 3")) == op("-", op("-", value(1), value(2)), value(3))
+
+
+def test_unary_operators_bind_tighter_than_binary():
+    assert dump(parse("-a + !b")) == op(
+        "+", op("-/unary", ref("a")), op("!/unary", ref("b"))
+    )
+
+
+def test_whitespace_separates_function_arguments():
+    assert dump(parse("map a == 1 @")) == (
+        "fn",
+        ref("map"),
+        [op("==", ref("a"), value(1)), ref("@")],
+    )
+
+
+def test_pipe_stages_are_always_function_calls():
+    assert dump(parse("@ | count | map x")) == (
+        "pipe",
+        [ref("@"), ("fn", ref("count"), []), ("fn", ref("map"), [ref("x")])],
+    )
+
+
+def test_dot_access():
+    assert dump(parse("a . b.c")) == op(".", op(".", ref("a"), ref("b")), ref("c"))
+
+
+def test_lenient_dot_access():
+    assert dump(parse("a?.b ?. c")) == op("?.", op("?.", ref("a"), ref("b")), ref("c"))
+
+
+def test_coalescing_binds_loosest():
+    assert dump(parse("a || b ?? c")) == op(
+        "??", op("||", ref("a"), ref("b")), ref("c")
+    )
+
+
+@pytest.mark.parametrize(
+    "query,args",
+    [
+        ("a[1]", [value(1)]),
+        ("a[1:]", [value(1), value(None)]),
+        ("a[:1]", [value(None), value(1)]),
+        ("a[:]", [value(None), value(None)]),
+    ],
+)
+def test_index_slots_are_filled_with_nulls(query, args):
+    assert dump(parse(query)) == op("index", *args, ref("a"))
+
+
+def test_array_directly_after_value_is_indexing():
+    assert dump(parse("count [4]")) == ("fn", ref("count"), [("array", [value(4)])])
+    assert dump(parse("count[4]")) == op("index", value(4), ref("count"))
+
+
+def test_object_keys():
+    assert dump(parse('{a: 1, "b c": 2, null: 3}')) == (
+        "object",
+        [("a", value(1)), ("b c", value(2)), ("null", value(3))],
+    )
+
+
+def test_computed_keys_and_spreads():
+    assert dump(parse("{(a): 1, ...b, ...c | d}")) == (
+        "object",
+        [
+            (ref("a"), value(1)),
+            ("spread", ref("b")),
+            ("spread", ("pipe", [ref("c"), ("fn", ref("d"), [])])),
+        ],
+    )
+    assert dump(parse("[1, ... a]")) == ("array", [value(1), ("spread", ref("a"))])
+    with pytest.raises(MistQLParseError):
+        parse("{(a) 1}")
+
+
+def test_enclosing_contexts():
+    assert dump(parse("@^^.a")) == op(".", ref("@^^"), ref("a"))
+    assert dump(parse("{`@^`: @^}")) == ("object", [("@^", ref("@^"))])
+    with pytest.raises(MistQLParseError):
+        parse("{@^: 1}")
+
+
+def test_quoted_identifiers():
+    assert dump(parse("`a-b`.`c d`")) == op(".", ref("a-b"), ref("c d"))
+    assert dump(parse("{`a-b`: 1}")) == ("object", [("a-b", value(1))])
+    assert dump(parse("`null`")) == ref("null")
+    with pytest.raises(MistQLParseError):
+        parse("`a-b")
+
+
+def test_let_bindings():
+    assert dump(parse("let x = f 1 in x")) == (
+        "let",
+        "x",
+        ("fn", ("ref", "f", False), [("value", 1.0)]),
+        ("ref", "x", False),
+    )
+
+
+def test_in_only_ends_let_values_outside_brackets():
+    value = dump(parse("let x = (f in) in x").value)
+    assert value == ("fn", ("ref", "f", False), [("ref", "in", False)])
+
+
+def test_function_definitions():
+    assert dump(parse("def f x y = x in f")) == (
+        "let",
+        "f",
+        ("lambda", "f", ["x", "y"], ("ref", "x", False)),
+        ("ref", "f", False),
+    )
+    assert dump(parse("def")) == ("ref", "def", False)
+    with pytest.raises(MistQLParseError):
+        parse("def f = 1 in f")
+
+
+@pytest.mark.parametrize(
+    "query", ["", "1 +", "(1", "[1, 2", '"abc', "{a 1}", "a.1", "1 2)", "#"]
+)
+def test_invalid_queries_raise(query):
+    with pytest.raises(MistQLParseError):
+        parse(query)
+
+
+def test_parse_errors_have_positions():
+    with pytest.raises(MistQLParseError) as exc_info:
+        parse("count [1, 2]\n  | filter @ > 1)")
+    err = exc_info.value
+    assert err.offset == 29
+    assert err.line == 2
+    assert err.column == 17
+    assert err.expected == ["EOF"]
+
+
+def test_parse_errors_render_an_indicator():
+    with pytest.raises(MistQLParseError) as exc_info:
+        parse("1 + + ")
+    assert str(exc_info.value) == (
+        "Unexpected token +, expected expression at position 4\n"
+        "---\n"
+        "1 + + \n"
+        "    ^\n"
+        "---\n"
+    )
+
+
+def test_expressions_record_their_spans():
+    query = "[1, 2] | map (@ + 1)"
+    ast = parse(query)
+    assert (ast.start, ast.end) == (0, len(query))
+    first, second = ast.stages
+    assert query[first.start : first.end] == "[1, 2]"
+    assert query[second.start : second.end] == "map (@ + 1)"
+    assert query[second.args[0].start : second.args[0].end] == "@ + 1"
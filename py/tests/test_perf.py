@@ -0,0 +1,71 @@
+import json
+import statistics
+import time
+from typing import Callable
+
+import mistql
+from mistql import RuntimeValue
+from mistql.parse import parse
+
+
+# The following is scaffolding for performance testing.
+
+
+def time_fn(fn: Callable[[], object], iterations: int = 100, name: str = "") -> float:
+    times = []
+    for _ in range(iterations):
+        start = time.perf_counter()
+        fn()
+        times.append(time.perf_counter() - start)
+    median = statistics.median(times)
+    title = f' for "{name}"' if name else ""
+    print(f"Performance Summary{title} (n={iterations}): median={median:.6f}s")
+    return median
+
+
+# The following is the actual test code.
+
+
+def test_query_parsing_performance_similar_to_native_json_parsing():
+    # Python's json module is implemented in C, so allow a larger ratio than
+    # the JS implementation does.
+    max_ratio = 500
+
+    with open("shared/data/nobel-prizes.json") as f:
+        nobel_prizes = f.read()
+
+    native_time = time_fn(
+        lambda: json.loads(nobel_prizes), iterations=20, name="native json parse"
+    )
+    mistql_time = time_fn(
+        lambda: parse(nobel_prizes), iterations=20, name="mistql parse"
+    )
+    print("Ratio: ", mistql_time / native_time)
+    assert mistql_time < native_time * max_ratio
+
+
+def test_long_query_parsing_is_linear():
+    stage = 'filter (a.b + 3 * c[1:2]) == "x" && !d'
+    short_query = " | ".join([stage] * 10)
+    long_query = " | ".join([stage] * 100)
+
+    short_time = time_fn(lambda: parse(short_query), name="10 stage query")
+    long_time = time_fn(lambda: parse(long_query), name="100 stage query")
+    # 10x the query length should be roughly 10x the time, not 100x
+    assert long_time < short_time * 30
+
+
+def test_pushing_wide_objects_is_as_cheap_as_narrow_ones():
+    def items(width):
+        # Converted up front, as converting input is proportional to its size
+        return RuntimeValue.of(
+            [{f"field{i}": i for i in range(width)} for _ in range(200)]
+        )
+
+    narrow = items(5)
+    wide = items(500)
+    query = mistql.compile("@ | filter field1 > 0 | map field2 | sum")
+
+    narrow_time = time_fn(lambda: query.run(narrow), 20, "5 field objects")
+    wide_time = time_fn(lambda: query.run(wide), 20, "500 field objects")
+    assert wide_time < narrow_time * 3
@@ -1,14 +1,16 @@
 import json
-from typing import Any, List, Optional, Tuple
+import re
+from typing import Any, Dict, List, Optional, Tuple
 
 import pytest
-from mistql import query
+from mistql import MistQLInstance, MistQLException, MistQLParseError
 
 with open("shared/testdata.json", "rb") as f:
     testdata = json.load(f)
 
 
-Case = Tuple[List[Tuple[str, Any, Any]], str, str, str, Optional[str]]
+Assertion = Tuple[str, Any, Any, Any, Dict[str, Any]]
+Case = Tuple[List[Assertion], str, str, str, Optional[str]]
 non_skipped_cases: List[Case] = []
 skipped_cases: List[Case] = []
 
@@ -31,6 +33,7 @@ for block in testdata["data"]:
                             assertion["data"],
                             assertion.get("expected"),
                             assertion.get("throws"),
+                            assertion.get("limits", {}),
                         )
                         for assertion in test["assertions"]
                     ],
@@ -48,9 +51,15 @@ def get_test_id_for_case(case: Case) -> str:
 
 @pytest.mark.parametrize("case", non_skipped_cases, ids=get_test_id_for_case)
 def test_shared(case: Case):
-    for target_query, data, expected, throws in case[0]:
-        if throws:
-            with pytest.raises(Exception):
+    for target_query, data, expected, throws, limits in case[0]:
+        # Limits are named as in JS, e.g. maxCollectionSize
+        kwargs = {re.sub("([A-Z])", r"_\1", k).lower(): v for k, v in limits.items()}
+        query = MistQLInstance(**kwargs).query
+        if throws == "parse":
+            with pytest.raises(MistQLParseError):
+                query(target_query, data)
+        elif throws:
+            with pytest.raises(MistQLException):
                 query(target_query, data)
         else:
             assert query(target_query, data) == expected
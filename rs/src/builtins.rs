@@ -0,0 +1,1697 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, OnceLock};
+
+use regex::RegexBuilder;
+
+use crate::dates;
+use crate::errors::{MistQLError, Result};
+use crate::expression::Expression;
+use crate::runtime_value::{
+    assert_int, assert_type, assert_types, Exec, FunctionDefinition, RegexValue, RuntimeValue,
+    RuntimeValueType as RVT,
+};
+use crate::stack::{add_runtime_value_to_stack, Stack};
+
+pub type Args = [Expression];
+
+type BuiltinFn = fn(&Args, &Stack, Exec) -> Result<RuntimeValue>;
+
+/// Name, minimum arity, maximum arity (`None` for variadic), implementation.
+type BuiltinEntry = (&'static str, usize, Option<usize>, BuiltinFn);
+
+const BUILTINS: &[BuiltinEntry] = &[
+    ("log", 1, Some(1), log),
+    ("reverse", 1, Some(1), reverse),
+    ("-/unary", 1, Some(1), unary_minus),
+    ("!/unary", 1, Some(1), unary_not),
+    ("if", 3, Some(3), if_else),
+    ("cond", 3, None, cond),
+    ("+", 2, Some(2), add),
+    ("-", 2, Some(2), subtract),
+    ("*", 2, Some(2), multiply),
+    ("/", 2, Some(2), divide),
+    ("%", 2, Some(2), modulo),
+    ("==", 2, Some(2), eq),
+    ("!=", 2, Some(2), neq),
+    ("&&", 2, Some(2), and_fn),
+    ("||", 2, Some(2), or_fn),
+    ("??", 2, Some(2), coalesce),
+    ("count", 1, Some(1), count),
+    ("keys", 1, Some(1), keys),
+    (".", 2, Some(2), dot),
+    ("?.", 2, Some(2), lenient_dot),
+    ("map", 2, Some(2), map),
+    ("reduce", 3, Some(3), reduce),
+    ("filter", 2, Some(2), filter),
+    ("mapvalues", 2, Some(2), mapvalues),
+    ("mapkeys", 2, Some(2), mapkeys),
+    ("filtervalues", 2, Some(2), filtervalues),
+    ("filterkeys", 2, Some(2), filterkeys),
+    ("find", 2, Some(2), find),
+    ("apply", 2, Some(2), apply),
+    ("index", 2, Some(3), index),
+    ("string", 1, Some(1), string),
+    ("float", 1, Some(1), float),
+    ("regex", 1, Some(2), regex),
+    ("sort", 1, Some(1), sort),
+    ("sortby", 2, Some(2), sortby),
+    ("<", 2, Some(2), lt),
+    ("<=", 2, Some(2), lte),
+    (">", 2, Some(2), gt),
+    (">=", 2, Some(2), gte),
+    ("values", 1, Some(1), values),
+    ("groupby", 2, Some(2), groupby),
+    ("countby", 2, Some(2), countby),
+    ("sumby", 2, Some(2), sumby),
+    ("avgby", 2, Some(2), avgby),
+    ("minby", 2, Some(2), minby),
+    ("maxby", 2, Some(2), maxby),
+    ("percentile", 2, Some(2), percentile),
+    ("withindices", 1, Some(1), withindices),
+    ("entries", 1, Some(1), entries),
+    ("fromentries", 1, Some(1), fromentries),
+    ("merge", 2, None, merge),
+    ("deepmerge", 2, None, deepmerge),
+    ("pick", 2, Some(2), pick),
+    ("omit", 2, Some(2), omit),
+    ("haskey", 2, Some(2), haskey),
+    ("getpath", 2, Some(2), getpath),
+    ("setpath", 3, Some(3), setpath),
+    ("match", 2, Some(2), match_fn),
+    ("=~", 2, Some(2), match_operator),
+    ("range", 1, Some(3), range),
+    ("replace", 3, Some(3), replace),
+    ("split", 2, Some(2), split),
+    ("stringjoin", 2, Some(2), stringjoin),
+    ("lower", 1, Some(1), lower),
+    ("upper", 1, Some(1), upper),
+    ("trim", 1, Some(1), trim),
+    ("startswith", 2, Some(2), startswith),
+    ("endswith", 2, Some(2), endswith),
+    ("contains", 2, Some(2), contains),
+    ("padstart", 2, Some(3), padstart),
+    ("padend", 2, Some(3), padend),
+    ("substring", 2, Some(3), substring),
+    ("length", 1, Some(1), length),
+    ("format", 2, Some(2), format),
+    ("sum", 1, Some(1), sum),
+    ("summarize", 1, Some(1), summarize),
+    ("abs", 1, Some(1), abs),
+    ("floor", 1, Some(1), floor),
+    ("ceil", 1, Some(1), ceil),
+    ("round", 1, Some(2), round),
+    ("min", 1, Some(2), min),
+    ("max", 1, Some(2), max),
+    ("pow", 2, Some(2), pow),
+    ("sqrt", 1, Some(1), sqrt),
+    ("ln", 1, Some(1), ln),
+    ("clamp", 3, Some(3), clamp),
+    ("intdiv", 2, Some(2), intdiv),
+    ("sequence", 2, None, sequence),
+    ("flatten", 1, Some(1), flatten),
+    ("unique", 1, Some(1), unique),
+    ("uniqueby", 2, Some(2), uniqueby),
+    ("zip", 2, None, zip),
+    ("chunk", 2, Some(2), chunk),
+    ("take", 2, Some(2), take),
+    ("drop", 2, Some(2), drop),
+    ("first", 1, Some(1), first),
+    ("last", 1, Some(1), last),
+    ("any", 1, Some(2), any),
+    ("all", 1, Some(2), all),
+    ("parsedate", 1, Some(1), parsedate),
+    ("formatdate", 1, Some(2), formatdate),
+    ("datediff", 3, Some(3), datediff),
+    ("dateadd", 3, Some(3), dateadd),
+    ("truncdate", 2, Some(2), truncdate),
+];
+
+fn builtin(
+    name: &'static str,
+    min_args: usize,
+    max_args: Option<usize>,
+    f: BuiltinFn,
+) -> FunctionDefinition {
+    Arc::new(move |arguments: &Args, stack: &Stack, exec: Exec| {
+        if arguments.len() < min_args {
+            return Err(MistQLError::Runtime(format!(
+                "{} takes at least {} arguments",
+                name, min_args
+            )));
+        }
+        if let Some(max_args) = max_args {
+            if arguments.len() > max_args {
+                return Err(MistQLError::Runtime(format!(
+                    "{} takes at most {} arguments",
+                    name, max_args
+                )));
+            }
+        }
+        f(arguments, stack, exec)
+    })
+}
+
+/// All builtin functions, keyed by name.
+pub fn builtins() -> &'static HashMap<&'static str, FunctionDefinition> {
+    static BUILTIN_MAP: OnceLock<HashMap<&'static str, FunctionDefinition>> = OnceLock::new();
+    BUILTIN_MAP.get_or_init(|| {
+        BUILTINS
+            .iter()
+            .map(|&(name, min_args, max_args, f)| (name, builtin(name, min_args, max_args, f)))
+            .collect()
+    })
+}
+
+fn log(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let res = exec(&arguments[0], stack)?;
+    eprintln!("{:?}", res);
+    Ok(res)
+}
+
+fn reverse(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let arg = array_items(exec(&arguments[0], stack)?)?;
+    Ok(RuntimeValue::array(arg.iter().rev().cloned().collect()))
+}
+
+fn unary_minus(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let res = number_value(exec(&arguments[0], stack)?)?;
+    Ok(RuntimeValue::number(-res))
+}
+
+fn unary_not(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let res = exec(&arguments[0], stack)?;
+    Ok(RuntimeValue::Boolean(!res.truthy()))
+}
+
+fn if_else(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    if exec(&arguments[0], stack)?.truthy() {
+        exec(&arguments[1], stack)
+    } else {
+        exec(&arguments[2], stack)
+    }
+}
+
+fn cond(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    // Conditions are tried in order, and only the chosen result is evaluated
+    if arguments.len().is_multiple_of(2) {
+        return Err(MistQLError::Runtime(
+            "cond: Expected pairs of conditions and results, followed by a default".to_string(),
+        ));
+    }
+    for pair in arguments[..arguments.len() - 1].chunks(2) {
+        if exec(&pair[0], stack)?.truthy() {
+            return exec(&pair[1], stack);
+        }
+    }
+    exec(&arguments[arguments.len() - 1], stack)
+}
+
+fn add(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let left = exec(&arguments[0], stack)?;
+    let right = exec(&arguments[1], stack)?;
+    match (&left, &right) {
+        (RuntimeValue::Number(a), RuntimeValue::Number(b)) => Ok(RuntimeValue::number(a + b)),
+        (RuntimeValue::String(a), RuntimeValue::String(b)) => {
+            Ok(RuntimeValue::from(format!("{}{}", a, b)))
+        }
+        (RuntimeValue::Array(a), RuntimeValue::Array(b)) => Ok(RuntimeValue::array(
+            a.iter().chain(b.iter()).cloned().collect(),
+        )),
+        _ if left.get_type() != right.get_type() => Err(MistQLError::Type(format!(
+            "add: {:?} and {:?} are not the same type",
+            left, right
+        ))),
+        _ => Err(MistQLError::Type(format!(
+            "add: {} is not supported",
+            left.get_type()
+        ))),
+    }
+}
+
+fn numeric_operands(arguments: &Args, stack: &Stack, exec: Exec) -> Result<(f64, f64)> {
+    let left = number_value(exec(&arguments[0], stack)?)?;
+    let right = number_value(exec(&arguments[1], stack)?)?;
+    Ok((left, right))
+}
+
+fn subtract(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let (left, right) = numeric_operands(arguments, stack, exec)?;
+    Ok(RuntimeValue::number(left - right))
+}
+
+fn multiply(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let (left, right) = numeric_operands(arguments, stack, exec)?;
+    Ok(RuntimeValue::number(left * right))
+}
+
+fn divide(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let (left, right) = numeric_operands(arguments, stack, exec)?;
+    if right == 0.0 {
+        return Err(MistQLError::Runtime("Division by zero".to_string()));
+    }
+    Ok(RuntimeValue::number(left / right))
+}
+
+fn modulo(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let (left, right) = numeric_operands(arguments, stack, exec)?;
+    if right == 0.0 {
+        return Err(MistQLError::Runtime("Modulo by zero".to_string()));
+    }
+    // Floored modulo, as in Python: the result takes the sign of the divisor,
+    // even when it's zero.
+    let remainder = left % right;
+    Ok(RuntimeValue::number(if remainder == 0.0 {
+        0.0_f64.copysign(right)
+    } else if (remainder < 0.0) != (right < 0.0) {
+        remainder + right
+    } else {
+        remainder
+    }))
+}
+
+fn eq(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let left = exec(&arguments[0], stack)?;
+    let right = exec(&arguments[1], stack)?;
+    Ok(RuntimeValue::Boolean(left.equals(&right)))
+}
+
+fn neq(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let left = exec(&arguments[0], stack)?;
+    let right = exec(&arguments[1], stack)?;
+    Ok(RuntimeValue::Boolean(!left.equals(&right)))
+}
+
+fn and_fn(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let left = exec(&arguments[0], stack)?;
+    if left.truthy() {
+        exec(&arguments[1], stack)
+    } else {
+        Ok(left)
+    }
+}
+
+fn or_fn(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let left = exec(&arguments[0], stack)?;
+    if left.truthy() {
+        Ok(left)
+    } else {
+        exec(&arguments[1], stack)
+    }
+}
+
+fn coalesce(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    match exec(&arguments[0], stack)? {
+        RuntimeValue::Null => exec(&arguments[1], stack),
+        left => Ok(left),
+    }
+}
+
+fn count(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let arg = array_items(exec(&arguments[0], stack)?)?;
+    Ok(RuntimeValue::number(arg.len() as f64))
+}
+
+fn keys(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = exec(&arguments[0], stack)?;
+    Ok(RuntimeValue::array(
+        target.keys().into_iter().map(RuntimeValue::from).collect(),
+    ))
+}
+
+fn dot(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let left = exec(&arguments[0], stack)?;
+    match &arguments[1] {
+        Expression::Reference { name, .. } => {
+            index_single(&RuntimeValue::from(name.as_str()), &left)
+        }
+        _ => Err(MistQLError::Runtime(
+            "dot: RHS of the dot operator is not a ref".to_string(),
+        )),
+    }
+}
+
+fn lenient_dot(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let left = exec(&arguments[0], stack)?;
+    match (&arguments[1], &left) {
+        (Expression::Reference { name, .. }, RuntimeValue::Object(_)) => {
+            index_single(&RuntimeValue::from(name.as_str()), &left)
+        }
+        // Unlike `.`, anything other than an object gives null rather than an error
+        (Expression::Reference { .. }, _) => Ok(RuntimeValue::Null),
+        _ => Err(MistQLError::Runtime(
+            "dot: RHS of the dot operator is not a ref".to_string(),
+        )),
+    }
+}
+
+fn map(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mutation = &arguments[0];
+    let operand = array_items(exec(&arguments[1], stack)?)?;
+    let mut out = Vec::with_capacity(operand.len());
+    for item in operand.iter() {
+        out.push(exec(mutation, &add_runtime_value_to_stack(item, stack))?);
+    }
+    Ok(RuntimeValue::array(out))
+}
+
+fn reduce(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mutation = &arguments[0];
+    let initial = exec(&arguments[1], stack)?;
+    let operand = array_items(exec(&arguments[2], stack)?)?;
+    let mut out = initial;
+    for item in operand.iter() {
+        let acc_cur = RuntimeValue::array(vec![out, item.clone()]);
+        out = exec(mutation, &add_runtime_value_to_stack(&acc_cur, stack))?;
+    }
+    Ok(out)
+}
+
+fn filter(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mutation = &arguments[0];
+    let operand = array_items(exec(&arguments[1], stack)?)?;
+    let mut out = Vec::new();
+    for item in operand.iter() {
+        if exec(mutation, &add_runtime_value_to_stack(item, stack))?.truthy() {
+            out.push(item.clone());
+        }
+    }
+    Ok(RuntimeValue::array(out))
+}
+
+fn mapvalues(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mutation = &arguments[0];
+    let operand = object_entries(exec(&arguments[1], stack)?)?;
+    let mut out = BTreeMap::new();
+    for (key, value) in operand.iter() {
+        let res = exec(mutation, &add_runtime_value_to_stack(value, stack))?;
+        out.insert(key.clone(), res);
+    }
+    Ok(RuntimeValue::object(out))
+}
+
+fn mapkeys(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mutation = &arguments[0];
+    let operand = object_entries(exec(&arguments[1], stack)?)?;
+    let mut out = BTreeMap::new();
+    for (key, value) in operand.iter() {
+        let key_value = RuntimeValue::from(key.as_str());
+        let res = exec(mutation, &add_runtime_value_to_stack(&key_value, stack))?;
+        out.insert(res.to_string()?, value.clone());
+    }
+    Ok(RuntimeValue::object(out))
+}
+
+fn filtervalues(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mutation = &arguments[0];
+    let operand = object_entries(exec(&arguments[1], stack)?)?;
+    let mut out = BTreeMap::new();
+    for (key, value) in operand.iter() {
+        if exec(mutation, &add_runtime_value_to_stack(value, stack))?.truthy() {
+            out.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(RuntimeValue::object(out))
+}
+
+fn filterkeys(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mutation = &arguments[0];
+    let operand = object_entries(exec(&arguments[1], stack)?)?;
+    let mut out = BTreeMap::new();
+    for (key, value) in operand.iter() {
+        let key_value = RuntimeValue::from(key.as_str());
+        if exec(mutation, &add_runtime_value_to_stack(&key_value, stack))?.truthy() {
+            out.insert(key.clone(), value.clone());
+        }
+    }
+    Ok(RuntimeValue::object(out))
+}
+
+fn find(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mutation = &arguments[0];
+    let operand = array_items(exec(&arguments[1], stack)?)?;
+    for item in operand.iter() {
+        if exec(mutation, &add_runtime_value_to_stack(item, stack))?.truthy() {
+            return Ok(item.clone());
+        }
+    }
+    Ok(RuntimeValue::Null)
+}
+
+fn apply(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = exec(&arguments[1], stack)?;
+    exec(&arguments[0], &add_runtime_value_to_stack(&target, stack))
+}
+
+/// The length of an indexable value, counting strings by code point.
+fn indexable_len(operand: &RuntimeValue) -> usize {
+    match operand {
+        RuntimeValue::Array(items) => items.len(),
+        RuntimeValue::String(s) => s.chars().count(),
+        _ => 0,
+    }
+}
+
+fn integer_index(index: f64) -> Result<i64> {
+    if index.fract() != 0.0 {
+        return Err(MistQLError::Runtime(
+            "index: Non-integers cannot be used on arrays".to_string(),
+        ));
+    }
+    Ok(index as i64)
+}
+
+fn index_double(
+    index_one: &RuntimeValue,
+    index_two: &RuntimeValue,
+    operand: &RuntimeValue,
+) -> Result<RuntimeValue> {
+    let operand = assert_types(operand.clone(), &[RVT::Array, RVT::String])?;
+    let len = indexable_len(&operand) as i64;
+
+    let bound = |index: &RuntimeValue, default: i64| -> Result<i64> {
+        match index {
+            RuntimeValue::Null => Ok(default),
+            RuntimeValue::Number(n) => integer_index(*n),
+            _ => Err(MistQLError::Runtime(
+                "index: Non-numbers cannot be used on arrays".to_string(),
+            )),
+        }
+    };
+    let mut start = bound(index_one, 0)?;
+    let mut end = bound(index_two, len)?;
+    if start < 0 {
+        start += len;
+    }
+    if end < 0 {
+        end += len;
+    }
+    // Slice semantics: anything still negative counts from the end once more,
+    // then everything is clamped into range.
+    let clamp = |i: i64| -> usize {
+        let i = if i < 0 { (i + len).max(0) } else { i };
+        i.min(len) as usize
+    };
+    let (start, end) = (clamp(start), clamp(end));
+    let end = end.max(start);
+
+    match &operand {
+        RuntimeValue::Array(items) => Ok(RuntimeValue::array(items[start..end].to_vec())),
+        RuntimeValue::String(s) => Ok(RuntimeValue::from(
+            s.chars().skip(start).take(end - start).collect::<String>(),
+        )),
+        _ => Err(MistQLError::OpenAnIssue(
+            "index: Unexpected operand type".to_string(),
+        )),
+    }
+}
+
+fn index_single(index: &RuntimeValue, operand: &RuntimeValue) -> Result<RuntimeValue> {
+    match operand {
+        RuntimeValue::Array(_) | RuntimeValue::String(_) => {
+            let index_num = integer_index(number_value(index.clone())?)?;
+            let len = indexable_len(operand) as i64;
+            let index_num = if index_num < 0 {
+                index_num + len
+            } else {
+                index_num
+            };
+            if index_num < 0 || index_num >= len {
+                return Ok(RuntimeValue::Null);
+            }
+            match operand {
+                RuntimeValue::Array(items) => Ok(items[index_num as usize].clone()),
+                RuntimeValue::String(s) => Ok(s
+                    .chars()
+                    .nth(index_num as usize)
+                    .map_or(RuntimeValue::Null, |c| RuntimeValue::from(c.to_string()))),
+                _ => unreachable!(),
+            }
+        }
+        RuntimeValue::Object(_) => match index {
+            RuntimeValue::String(key) => Ok(operand.access(key)),
+            _ => Err(MistQLError::Type(format!(
+                "Expected {}, got {}",
+                RVT::String,
+                index.get_type()
+            ))),
+        },
+        RuntimeValue::Null => {
+            assert_types(index.clone(), &[RVT::Number, RVT::String])?;
+            Ok(RuntimeValue::Null)
+        }
+        _ => Err(MistQLError::Runtime(format!(
+            "index: Cannot index {}",
+            operand.get_type()
+        ))),
+    }
+}
+
+fn index(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    if arguments.len() == 3 {
+        index_double(
+            &exec(&arguments[0], stack)?,
+            &exec(&arguments[1], stack)?,
+            &exec(&arguments[2], stack)?,
+        )
+    } else {
+        index_single(&exec(&arguments[0], stack)?, &exec(&arguments[1], stack)?)
+    }
+}
+
+fn string(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::from(exec(&arguments[0], stack)?.to_string()?))
+}
+
+fn float(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::number(
+        exec(&arguments[0], stack)?.to_float()?,
+    ))
+}
+
+fn regex(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let pattern = string_value(exec(&arguments[0], stack)?)?;
+    let flags = if arguments.len() == 2 {
+        string_value(exec(&arguments[1], stack)?)?
+    } else {
+        String::new()
+    };
+
+    // Supported flags are /gims/
+    if let Some(flag) = flags.chars().find(|c| !"gims".contains(*c)) {
+        return Err(MistQLError::Runtime(format!(
+            "regex: Invalid flag {}",
+            flag
+        )));
+    }
+    let compiled = RegexBuilder::new(&pattern)
+        .case_insensitive(flags.contains('i'))
+        .multi_line(flags.contains('m'))
+        .dot_matches_new_line(flags.contains('s'))
+        .build()
+        .map_err(|err| MistQLError::Runtime(format!("regex: {}", err)))?;
+
+    let mut normalized_flags: Vec<char> = flags.chars().filter(|c| *c != 'g').collect();
+    normalized_flags.sort_unstable();
+    normalized_flags.dedup();
+
+    Ok(RuntimeValue::Regex(Arc::new(RegexValue {
+        regex: compiled,
+        pattern,
+        flags: normalized_flags.into_iter().collect(),
+        global: flags.contains('g'),
+    })))
+}
+
+/// Sorts values, failing if any pair is incomparable.
+fn sort_values<T>(items: &mut [T], key: impl Fn(&T) -> &RuntimeValue) -> Result<()> {
+    if let Some(first) = items.first() {
+        let first_type = key(first).get_type();
+        for item in items.iter() {
+            let value = key(item);
+            if !value.comparable() {
+                return Err(MistQLError::Runtime(
+                    "sort: Cannot sort non-comparable values".to_string(),
+                ));
+            }
+            if value.get_type() != first_type {
+                return Err(MistQLError::Type(
+                    "Cannot compare MistQL values of different types".to_string(),
+                ));
+            }
+        }
+    }
+    // All values are comparable and of the same type, so comparison can't fail.
+    items.sort_by(|a, b| RuntimeValue::compare(key(a), key(b)).unwrap_or(Ordering::Equal));
+    Ok(())
+}
+
+fn sort(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mut arg = array_items(exec(&arguments[0], stack)?)?.to_vec();
+    sort_values(&mut arg, |item| item)?;
+    Ok(RuntimeValue::array(arg))
+}
+
+fn sortby(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[1], stack)?)?;
+    let mut with_key = Vec::with_capacity(target.len());
+    for item in target.iter() {
+        let key = exec(&arguments[0], &add_runtime_value_to_stack(item, stack))?;
+        with_key.push((key, item.clone()));
+    }
+    sort_values(&mut with_key, |(key, _)| key)?;
+    Ok(RuntimeValue::array(
+        with_key.into_iter().map(|(_, value)| value).collect(),
+    ))
+}
+
+fn compare_operands(arguments: &Args, stack: &Stack, exec: Exec) -> Result<Ordering> {
+    let left = exec(&arguments[0], stack)?;
+    let right = exec(&arguments[1], stack)?;
+    RuntimeValue::compare(&left, &right)
+}
+
+fn lt(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::Boolean(
+        compare_operands(arguments, stack, exec)?.is_lt(),
+    ))
+}
+
+fn lte(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::Boolean(
+        compare_operands(arguments, stack, exec)?.is_le(),
+    ))
+}
+
+fn gt(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::Boolean(
+        compare_operands(arguments, stack, exec)?.is_gt(),
+    ))
+}
+
+fn gte(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    Ok(RuntimeValue::Boolean(
+        compare_operands(arguments, stack, exec)?.is_ge(),
+    ))
+}
+
+fn values(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = exec(&arguments[0], stack)?;
+    let values = target.keys().iter().map(|key| target.access(key)).collect();
+    Ok(RuntimeValue::array(values))
+}
+
+fn groupby(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[1], stack)?)?;
+    let mut groups: BTreeMap<String, Vec<RuntimeValue>> = BTreeMap::new();
+    for item in target.iter() {
+        let key = exec(&arguments[0], &add_runtime_value_to_stack(item, stack))?.to_string()?;
+        groups.entry(key).or_default().push(item.clone());
+    }
+    Ok(RuntimeValue::object(
+        groups
+            .into_iter()
+            .map(|(key, items)| (key, RuntimeValue::array(items)))
+            .collect(),
+    ))
+}
+
+/// The items of the target, each with the contextual expression applied to it.
+fn items_with_keys(
+    arguments: &Args,
+    stack: &Stack,
+    exec: Exec,
+) -> Result<Vec<(RuntimeValue, RuntimeValue)>> {
+    let target = array_items(exec(&arguments[1], stack)?)?;
+    target
+        .iter()
+        .map(|item| {
+            let key = exec(&arguments[0], &add_runtime_value_to_stack(item, stack))?;
+            Ok((item.clone(), key))
+        })
+        .collect()
+}
+
+fn countby(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mut counts: BTreeMap<String, f64> = BTreeMap::new();
+    for (_, key) in items_with_keys(arguments, stack, exec)? {
+        *counts.entry(key.to_string()?).or_default() += 1.0;
+    }
+    Ok(RuntimeValue::object(
+        counts
+            .into_iter()
+            .map(|(key, count)| (key, RuntimeValue::Number(count)))
+            .collect(),
+    ))
+}
+
+fn numbers_by(arguments: &Args, stack: &Stack, exec: Exec) -> Result<Vec<f64>> {
+    items_with_keys(arguments, stack, exec)?
+        .into_iter()
+        .map(|(_, key)| number_value(key))
+        .collect()
+}
+
+fn sumby(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let numbers = numbers_by(arguments, stack, exec)?;
+    Ok(RuntimeValue::number(
+        numbers.iter().fold(0.0, |acc, n| acc + n),
+    ))
+}
+
+fn avgby(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let numbers = numbers_by(arguments, stack, exec)?;
+    if numbers.is_empty() {
+        return Ok(RuntimeValue::Null);
+    }
+    let total = numbers.iter().fold(0.0, |acc, n| acc + n);
+    Ok(RuntimeValue::number(total / numbers.len() as f64))
+}
+
+/// The item with the smallest key, or the largest when `wanted` is `Greater`.
+fn extremum_by(
+    name: &str,
+    wanted: Ordering,
+    arguments: &Args,
+    stack: &Stack,
+    exec: Exec,
+) -> Result<RuntimeValue> {
+    let mut best: Option<(RuntimeValue, RuntimeValue)> = None;
+    for (item, key) in items_with_keys(arguments, stack, exec)? {
+        if !key.comparable() {
+            return Err(MistQLError::Runtime(format!(
+                "{}: Cannot compare {}",
+                name,
+                key.get_type()
+            )));
+        }
+        // Ties go to the earliest item
+        let better = match &best {
+            None => true,
+            Some((_, best_key)) => RuntimeValue::compare(&key, best_key)? == wanted,
+        };
+        if better {
+            best = Some((item, key));
+        }
+    }
+    Ok(best.map_or(RuntimeValue::Null, |(item, _)| item))
+}
+
+fn minby(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    extremum_by("minby", Ordering::Less, arguments, stack, exec)
+}
+
+fn maxby(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    extremum_by("maxby", Ordering::Greater, arguments, stack, exec)
+}
+
+fn percentile(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let p = number_value(exec(&arguments[0], stack)?)?;
+    if !(0.0..=100.0).contains(&p) {
+        return Err(MistQLError::Runtime(
+            "percentile: Percentile must be from 0 to 100".to_string(),
+        ));
+    }
+    let target = array_items(exec(&arguments[1], stack)?)?;
+    let mut values = target
+        .iter()
+        .map(|item| number_value(item.clone()))
+        .collect::<Result<Vec<_>>>()?;
+    if values.is_empty() {
+        return Ok(RuntimeValue::Null);
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    // Interpolates between the closest ranks, which makes the 50th percentile
+    // the median as summarize finds it
+    let position = p * (values.len() - 1) as f64 / 100.0;
+    let lower = position.floor() as usize;
+    let upper = (lower + 1).min(values.len() - 1);
+    let fraction = position - lower as f64;
+    Ok(RuntimeValue::number(
+        values[lower] * (1.0 - fraction) + values[upper] * fraction,
+    ))
+}
+
+fn withindices(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[0], stack)?)?;
+    Ok(RuntimeValue::array(
+        target
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                RuntimeValue::array(vec![RuntimeValue::number(i as f64), item.clone()])
+            })
+            .collect(),
+    ))
+}
+
+fn entries(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = exec(&arguments[0], stack)?;
+    let entries = target
+        .keys()
+        .into_iter()
+        .map(|key| {
+            let value = target.access(&key);
+            RuntimeValue::array(vec![RuntimeValue::from(key), value])
+        })
+        .collect();
+    Ok(RuntimeValue::array(entries))
+}
+
+fn fromentries(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[0], stack)?)?;
+    let mut res = BTreeMap::new();
+    for entry in target.iter() {
+        let entry = array_items(entry.clone())?;
+        let first = entry.first().cloned().unwrap_or(RuntimeValue::Null);
+        let second = entry.get(1).cloned().unwrap_or(RuntimeValue::Null);
+        res.insert(first.to_string()?, second);
+    }
+    Ok(RuntimeValue::object(res))
+}
+
+fn merge(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mut res = BTreeMap::new();
+    for arg in arguments {
+        let source = object_entries(exec(arg, stack)?)?;
+        res.extend(
+            source
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+    }
+    Ok(RuntimeValue::object(res))
+}
+
+fn deepmerge_values(left: RuntimeValue, right: RuntimeValue) -> RuntimeValue {
+    match (left, right) {
+        (RuntimeValue::Object(left), RuntimeValue::Object(right)) => {
+            let mut res = (*left).clone();
+            for (key, value) in right.iter() {
+                let merged = match res.remove(key) {
+                    Some(existing) => deepmerge_values(existing, value.clone()),
+                    None => value.clone(),
+                };
+                res.insert(key.clone(), merged);
+            }
+            RuntimeValue::object(res)
+        }
+        (_, right) => right,
+    }
+}
+
+fn deepmerge(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mut res = assert_type(exec(&arguments[0], stack)?, RVT::Object)?;
+    for arg in &arguments[1..] {
+        res = deepmerge_values(res, assert_type(exec(arg, stack)?, RVT::Object)?);
+    }
+    Ok(res)
+}
+
+/// The keys given to `pick` or `omit`.
+fn key_list(value: RuntimeValue) -> Result<Vec<String>> {
+    array_items(value)?
+        .iter()
+        .map(|key| string_value(key.clone()))
+        .collect()
+}
+
+fn pick(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let keys = key_list(exec(&arguments[0], stack)?)?;
+    let source = object_entries(exec(&arguments[1], stack)?)?;
+    let res = keys
+        .into_iter()
+        .filter_map(|key| source.get(&key).cloned().map(|value| (key, value)))
+        .collect();
+    Ok(RuntimeValue::object(res))
+}
+
+fn omit(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let keys = key_list(exec(&arguments[0], stack)?)?;
+    let source = object_entries(exec(&arguments[1], stack)?)?;
+    let res = source
+        .iter()
+        .filter(|(key, _)| !keys.contains(key))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    Ok(RuntimeValue::object(res))
+}
+
+fn haskey(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let key = string_value(exec(&arguments[0], stack)?)?;
+    let target = object_entries(exec(&arguments[1], stack)?)?;
+    Ok(RuntimeValue::Boolean(target.contains_key(&key)))
+}
+
+/// A key or index in a path given to `getpath` or `setpath`.
+enum PathStep {
+    Key(String),
+    Index(i64),
+}
+
+fn path_steps(value: RuntimeValue) -> Result<Vec<PathStep>> {
+    array_items(value)?
+        .iter()
+        .map(|step| match step {
+            RuntimeValue::String(key) => Ok(PathStep::Key(key.to_string())),
+            RuntimeValue::Number(_) => Ok(PathStep::Index(assert_int(step.clone())?)),
+            other => Err(MistQLError::Type(format!(
+                "Expected path of strings and integers, got {}",
+                other.get_type()
+            ))),
+        })
+        .collect()
+}
+
+/// The position an index refers to, counting negative ones from the end.
+fn array_index(items: &[RuntimeValue], index: i64) -> Option<usize> {
+    let position = if index < 0 {
+        index + items.len() as i64
+    } else {
+        index
+    };
+    if position >= 0 && (position as usize) < items.len() {
+        Some(position as usize)
+    } else {
+        None
+    }
+}
+
+fn getpath(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let path = path_steps(exec(&arguments[0], stack)?)?;
+    let mut current = exec(&arguments[1], stack)?;
+    for step in path {
+        current = match (&step, &current) {
+            (PathStep::Key(key), RuntimeValue::Object(entries)) => {
+                entries.get(key).cloned().unwrap_or(RuntimeValue::Null)
+            }
+            (PathStep::Index(index), RuntimeValue::Array(items)) => {
+                match array_index(items, *index) {
+                    Some(position) => items[position].clone(),
+                    None => return Ok(RuntimeValue::Null),
+                }
+            }
+            _ => return Ok(RuntimeValue::Null),
+        };
+    }
+    Ok(current)
+}
+
+fn setpath_value(
+    target: RuntimeValue,
+    path: &[PathStep],
+    value: RuntimeValue,
+) -> Result<RuntimeValue> {
+    let Some((step, rest)) = path.split_first() else {
+        return Ok(value);
+    };
+    match (step, target) {
+        (PathStep::Key(key), RuntimeValue::Null) => {
+            let inner = setpath_value(RuntimeValue::Null, rest, value)?;
+            Ok(RuntimeValue::object(BTreeMap::from([(key.clone(), inner)])))
+        }
+        (PathStep::Key(key), RuntimeValue::Object(entries)) => {
+            let mut res = (*entries).clone();
+            let existing = res.remove(key).unwrap_or(RuntimeValue::Null);
+            res.insert(key.clone(), setpath_value(existing, rest, value)?);
+            Ok(RuntimeValue::object(res))
+        }
+        (PathStep::Key(key), other) => Err(MistQLError::Runtime(format!(
+            "setpath: Cannot set {} on {}",
+            key,
+            other.get_type()
+        ))),
+        (PathStep::Index(index), RuntimeValue::Array(items)) => {
+            let position = array_index(&items, *index).ok_or_else(|| {
+                MistQLError::Runtime(format!("setpath: Index {} is out of range", index))
+            })?;
+            let mut res = (*items).clone();
+            res[position] = setpath_value(res[position].clone(), rest, value)?;
+            Ok(RuntimeValue::array(res))
+        }
+        (PathStep::Index(index), other) => Err(MistQLError::Runtime(format!(
+            "setpath: Cannot set index {} on {}",
+            index,
+            other.get_type()
+        ))),
+    }
+}
+
+fn setpath(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let path = path_steps(exec(&arguments[0], stack)?)?;
+    let value = exec(&arguments[1], stack)?;
+    setpath_value(exec(&arguments[2], stack)?, &path, value)
+}
+
+fn match_fn(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let pattern = exec(&arguments[0], stack)?;
+    let target = string_value(exec(&arguments[1], stack)?)?;
+    match pattern {
+        RuntimeValue::Regex(pattern) => Ok(RuntimeValue::Boolean(pattern.regex.is_match(&target))),
+        RuntimeValue::String(pattern) => {
+            let compiled = regex::Regex::new(&pattern)
+                .map_err(|err| MistQLError::Runtime(format!("match: {}", err)))?;
+            Ok(RuntimeValue::Boolean(compiled.is_match(&target)))
+        }
+        other => Err(MistQLError::Type(format!(
+            "Expected one of {}, {}, got {}",
+            RVT::String,
+            RVT::Regex,
+            other.get_type()
+        ))),
+    }
+}
+
+fn match_operator(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let reversed: Vec<Expression> = arguments.iter().rev().cloned().collect();
+    match_fn(&reversed, stack, exec)
+}
+
+fn range(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mut start = 0;
+    let mut step = 1;
+    let stop;
+    match arguments.len() {
+        1 => {
+            stop = assert_int(exec(&arguments[0], stack)?)?;
+        }
+        2 => {
+            start = assert_int(exec(&arguments[0], stack)?)?;
+            stop = assert_int(exec(&arguments[1], stack)?)?;
+        }
+        3 => {
+            start = assert_int(exec(&arguments[0], stack)?)?;
+            stop = assert_int(exec(&arguments[1], stack)?)?;
+            step = assert_int(exec(&arguments[2], stack)?)?;
+        }
+        _ => {
+            return Err(MistQLError::OpenAnIssue(
+                "Unexpectedly reaching end of function in range call.".to_string(),
+            ))
+        }
+    }
+    if step == 0 {
+        return Err(MistQLError::Runtime(
+            "range: Step size cannot be 0".to_string(),
+        ));
+    }
+    let mut out = Vec::new();
+    let mut current = start;
+    while (step > 0 && current < stop) || (step < 0 && current > stop) {
+        out.push(RuntimeValue::number(current as f64));
+        current += step;
+    }
+    Ok(RuntimeValue::array(out))
+}
+
+fn replace(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let pattern = exec(&arguments[0], stack)?;
+    let replacement = string_value(exec(&arguments[1], stack)?)?;
+    let target = string_value(exec(&arguments[2], stack)?)?;
+    match pattern {
+        RuntimeValue::Regex(pattern) => {
+            let res = if pattern.global {
+                pattern.regex.replace_all(&target, replacement.as_str())
+            } else {
+                pattern.regex.replace(&target, replacement.as_str())
+            };
+            Ok(RuntimeValue::from(res.into_owned()))
+        }
+        RuntimeValue::String(pattern) => Ok(RuntimeValue::from(target.replacen(
+            &*pattern,
+            &replacement,
+            1,
+        ))),
+        other => Err(MistQLError::Type(format!(
+            "Expected one of {}, {}, got {}",
+            RVT::String,
+            RVT::Regex,
+            other.get_type()
+        ))),
+    }
+}
+
+fn split(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let delimiter = assert_types(exec(&arguments[0], stack)?, &[RVT::String, RVT::Regex])?;
+    let target = string_value(exec(&arguments[1], stack)?)?;
+    let parts: Vec<RuntimeValue> = match delimiter {
+        RuntimeValue::String(separator) if separator.is_empty() => target
+            .chars()
+            .map(|c| RuntimeValue::from(c.to_string()))
+            .collect(),
+        RuntimeValue::String(separator) => {
+            target.split(&*separator).map(RuntimeValue::from).collect()
+        }
+        RuntimeValue::Regex(separator) => separator
+            .regex
+            .split(&target)
+            .map(RuntimeValue::from)
+            .collect(),
+        _ => {
+            return Err(MistQLError::OpenAnIssue(
+                "Unexpectedly reaching end of function in split call.".to_string(),
+            ))
+        }
+    };
+    Ok(RuntimeValue::array(parts))
+}
+
+fn stringjoin(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let delimiter = string_value(exec(&arguments[0], stack)?)?;
+    let target = array_items(exec(&arguments[1], stack)?)?;
+    let arr = target
+        .iter()
+        .map(|entry| entry.to_string())
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RuntimeValue::from(arr.join(&delimiter)))
+}
+
+fn lower(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = string_value(exec(&arguments[0], stack)?)?;
+    Ok(RuntimeValue::from(target.to_lowercase()))
+}
+
+fn upper(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = string_value(exec(&arguments[0], stack)?)?;
+    Ok(RuntimeValue::from(target.to_uppercase()))
+}
+
+fn trim(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = string_value(exec(&arguments[0], stack)?)?;
+    // char::is_whitespace is the Unicode White_Space property, which the other
+    // implementations spell out by hand
+    Ok(RuntimeValue::from(target.trim_matches(char::is_whitespace)))
+}
+
+fn startswith(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let prefix = string_value(exec(&arguments[0], stack)?)?;
+    let target = string_value(exec(&arguments[1], stack)?)?;
+    Ok(RuntimeValue::Boolean(target.starts_with(&prefix)))
+}
+
+fn endswith(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let suffix = string_value(exec(&arguments[0], stack)?)?;
+    let target = string_value(exec(&arguments[1], stack)?)?;
+    Ok(RuntimeValue::Boolean(target.ends_with(&suffix)))
+}
+
+fn contains(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let needle = string_value(exec(&arguments[0], stack)?)?;
+    let target = string_value(exec(&arguments[1], stack)?)?;
+    Ok(RuntimeValue::Boolean(target.contains(&needle)))
+}
+
+/// The target of `padstart` or `padend`, and the padding it needs.
+fn padding(arguments: &Args, stack: &Stack, exec: Exec) -> Result<(String, String)> {
+    let width = assert_int(exec(&arguments[0], stack)?)?;
+    let mut fill = " ".to_string();
+    if arguments.len() == 3 {
+        fill = string_value(exec(&arguments[1], stack)?)?;
+        if fill.is_empty() {
+            return Err(MistQLError::Runtime(
+                "Cannot pad with an empty string".to_string(),
+            ));
+        }
+    }
+    let target = string_value(exec(&arguments[arguments.len() - 1], stack)?)?;
+    let missing = (width - target.chars().count() as i64).max(0) as usize;
+    Ok((target, fill.chars().cycle().take(missing).collect()))
+}
+
+fn padstart(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let (target, padding) = padding(arguments, stack, exec)?;
+    Ok(RuntimeValue::from(padding + &target))
+}
+
+fn padend(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let (target, padding) = padding(arguments, stack, exec)?;
+    Ok(RuntimeValue::from(target + &padding))
+}
+
+fn substring(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let start = assert_int(exec(&arguments[0], stack)?)?;
+    let end = if arguments.len() == 3 {
+        Some(assert_int(exec(&arguments[1], stack)?)?)
+    } else {
+        None
+    };
+    let target = string_value(exec(&arguments[arguments.len() - 1], stack)?)?;
+    let chars: Vec<char> = target.chars().collect();
+    let len = chars.len() as i64;
+    // Slices like Python: negative indices count from the end, and indices
+    // past either end are clamped to it
+    let clamp = |i: i64| if i < 0 { (len + i).max(0) } else { i.min(len) } as usize;
+    let (start, end) = (clamp(start), clamp(end.unwrap_or(len)));
+    if start >= end {
+        return Ok(RuntimeValue::from(""));
+    }
+    Ok(RuntimeValue::from(
+        chars[start..end].iter().collect::<String>(),
+    ))
+}
+
+fn length(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = string_value(exec(&arguments[0], stack)?)?;
+    Ok(RuntimeValue::number(target.chars().count() as f64))
+}
+
+fn format_field(name: &str, values: &RuntimeValue, position: usize) -> Result<RuntimeValue> {
+    let missing = || MistQLError::Runtime(format!("format: No value for {{{}}}", name));
+    match values {
+        RuntimeValue::Object(entries) => {
+            if name.is_empty() {
+                return Err(MistQLError::Runtime(
+                    "format: {} needs an array of values".to_string(),
+                ));
+            }
+            entries.get(name).cloned().ok_or_else(missing)
+        }
+        RuntimeValue::Array(items) => {
+            let index = if name.is_empty() {
+                position
+            } else if name.chars().all(|c| c.is_ascii_digit()) {
+                name.parse().unwrap_or(usize::MAX)
+            } else {
+                return Err(MistQLError::Runtime(format!(
+                    "format: {{{}}} needs an object of values",
+                    name
+                )));
+            };
+            items.get(index).cloned().ok_or_else(missing)
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn format(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let template = string_value(exec(&arguments[0], stack)?)?;
+    let values = assert_types(exec(&arguments[1], stack)?, &[RVT::Array, RVT::Object])?;
+    let mut result = String::new();
+    let mut position = 0;
+    let mut rest = template.as_str();
+    while let Some(c) = rest.chars().next() {
+        if (c == '{' || c == '}') && rest[1..].starts_with(c) {
+            result.push(c);
+            rest = &rest[2..];
+        } else if c == '}' {
+            return Err(MistQLError::Runtime(
+                "format: Unmatched } in template".to_string(),
+            ));
+        } else if c == '{' {
+            let end = rest.find('}').ok_or_else(|| {
+                MistQLError::Runtime("format: Unmatched { in template".to_string())
+            })?;
+            let name = &rest[1..end];
+            result.push_str(&format_field(name, &values, position)?.to_string()?);
+            if name.is_empty() {
+                position += 1;
+            }
+            rest = &rest[end + 1..];
+        } else {
+            result.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    Ok(RuntimeValue::from(result))
+}
+
+fn sum(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[0], stack)?)?;
+    let mut total = 0.0;
+    for entry in target.iter() {
+        total += number_value(entry.clone())?;
+    }
+    Ok(RuntimeValue::number(total))
+}
+
+fn summarize(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[0], stack)?)?;
+    let mut arr = target
+        .iter()
+        .map(|entry| number_value(entry.clone()))
+        .collect::<Result<Vec<_>>>()?;
+    if arr.len() < 2 {
+        return Err(MistQLError::Runtime(
+            "summarize: At least two values are required".to_string(),
+        ));
+    }
+    arr.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+    let n = arr.len() as f64;
+    let mean = arr.iter().sum::<f64>() / n;
+    let midpoint = arr.len() / 2;
+    let median = if arr.len() % 2 == 1 {
+        arr[midpoint]
+    } else {
+        (arr[midpoint - 1] + arr[midpoint]) / 2.0
+    };
+    let variance = arr.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+
+    let mut summary = BTreeMap::new();
+    summary.insert("max".to_string(), RuntimeValue::number(arr[arr.len() - 1]));
+    summary.insert("min".to_string(), RuntimeValue::number(arr[0]));
+    summary.insert("mean".to_string(), RuntimeValue::number(mean));
+    summary.insert("median".to_string(), RuntimeValue::number(median));
+    summary.insert("variance".to_string(), RuntimeValue::number(variance));
+    summary.insert("stddev".to_string(), RuntimeValue::number(variance.sqrt()));
+    Ok(RuntimeValue::object(summary))
+}
+
+/// A math result, with -0 as 0 so that it prints the same everywhere.
+fn number_result(value: f64) -> RuntimeValue {
+    RuntimeValue::number(value + 0.0)
+}
+
+fn number_argument(arguments: &Args, stack: &Stack, exec: Exec) -> Result<f64> {
+    number_value(exec(&arguments[0], stack)?)
+}
+
+fn abs(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    Ok(number_result(
+        number_argument(arguments, stack, exec)?.abs(),
+    ))
+}
+
+fn floor(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    Ok(number_result(
+        number_argument(arguments, stack, exec)?.floor(),
+    ))
+}
+
+fn ceil(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    Ok(number_result(
+        number_argument(arguments, stack, exec)?.ceil(),
+    ))
+}
+
+fn round_half_away(value: f64) -> f64 {
+    let mut whole = value.abs().floor();
+    if value.abs() - whole >= 0.5 {
+        whole += 1.0;
+    }
+    whole.copysign(value)
+}
+
+fn round(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let mut precision = 0;
+    if arguments.len() == 2 {
+        precision = assert_int(exec(&arguments[0], stack)?)?;
+        if precision.abs() > 308 {
+            return Err(MistQLError::Runtime(
+                "round: Precision must be from -308 to 308".to_string(),
+            ));
+        }
+    }
+    let value = number_value(exec(&arguments[arguments.len() - 1], stack)?)?;
+    // Parsed rather than computed, so that it's the closest double everywhere
+    let factor: f64 = format!("1e{}", precision.abs()).parse().unwrap();
+    if precision >= 0 {
+        let scaled = value * factor;
+        // Too big to have any digits at this precision
+        if !scaled.is_finite() {
+            return Ok(number_result(value));
+        }
+        return Ok(number_result(round_half_away(scaled) / factor));
+    }
+    Ok(number_result(round_half_away(value / factor) * factor))
+}
+
+/// The smallest or largest of two numbers or an array of them, per `pick`.
+fn extremum(
+    arguments: &Args,
+    stack: &Stack,
+    exec: Exec,
+    pick: fn(f64, f64) -> f64,
+) -> Result<RuntimeValue> {
+    let values = if arguments.len() == 2 {
+        vec![exec(&arguments[0], stack)?, exec(&arguments[1], stack)?]
+    } else {
+        array_items(exec(&arguments[0], stack)?)?.to_vec()
+    };
+    let numbers = values
+        .into_iter()
+        .map(number_value)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(match numbers.into_iter().reduce(pick) {
+        Some(result) => number_result(result),
+        None => RuntimeValue::Null,
+    })
+}
+
+fn min(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    extremum(arguments, stack, exec, f64::min)
+}
+
+fn max(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    extremum(arguments, stack, exec, f64::max)
+}
+
+fn pow(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let (base, exponent) = numeric_operands(arguments, stack, exec)?;
+    Ok(number_result(base.powf(exponent)))
+}
+
+fn sqrt(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    Ok(number_result(
+        number_argument(arguments, stack, exec)?.sqrt(),
+    ))
+}
+
+fn ln(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    Ok(number_result(number_argument(arguments, stack, exec)?.ln()))
+}
+
+fn clamp(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let low = number_value(exec(&arguments[0], stack)?)?;
+    let high = number_value(exec(&arguments[1], stack)?)?;
+    let value = number_value(exec(&arguments[2], stack)?)?;
+    if low > high {
+        return Err(MistQLError::Runtime(
+            "clamp: The lower bound is above the upper bound".to_string(),
+        ));
+    }
+    Ok(number_result(value.max(low).min(high)))
+}
+
+fn intdiv(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let (dividend, divisor) = numeric_operands(arguments, stack, exec)?;
+    if divisor == 0.0 {
+        return Err(MistQLError::Runtime("Division by zero".to_string()));
+    }
+    Ok(number_result((dividend / divisor).floor()))
+}
+
+fn sequence_helper(arr: &[Vec<bool>], start: usize) -> Vec<Vec<usize>> {
+    let first_array = &arr[0];
+    let mut result = Vec::new();
+    for (idx, &matched) in first_array.iter().enumerate().skip(start) {
+        if matched {
+            if arr.len() == 1 {
+                result.push(vec![idx]);
+            } else {
+                for sub_result in sequence_helper(&arr[1..], idx + 1) {
+                    let mut indices = vec![idx];
+                    indices.extend(sub_result);
+                    result.push(indices);
+                }
+            }
+        }
+    }
+    result
+}
+
+fn sequence(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let (target_arg, predicates) = arguments
+        .split_last()
+        .expect("arity is checked before calling");
+    let target = array_items(exec(target_arg, stack)?)?;
+    let mut bitmasks = Vec::with_capacity(predicates.len());
+    for predicate in predicates {
+        let mut bitmask = Vec::with_capacity(target.len());
+        for item in target.iter() {
+            bitmask.push(exec(predicate, &add_runtime_value_to_stack(item, stack))?.truthy());
+        }
+        bitmasks.push(bitmask);
+    }
+    let result = sequence_helper(&bitmasks, 0)
+        .into_iter()
+        .map(|indices| {
+            RuntimeValue::array(indices.into_iter().map(|idx| target[idx].clone()).collect())
+        })
+        .collect();
+    Ok(RuntimeValue::array(result))
+}
+
+fn flatten(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[0], stack)?)?;
+    let mut result = Vec::new();
+    for entry in target.iter() {
+        result.extend(array_items(entry.clone())?.iter().cloned());
+    }
+    Ok(RuntimeValue::array(result))
+}
+
+/// The items whose keys don't equal the key of any earlier item.
+fn unique_by_key(items: &[RuntimeValue], keys: &[RuntimeValue]) -> RuntimeValue {
+    let mut result = Vec::new();
+    let mut seen: Vec<&RuntimeValue> = Vec::new();
+    for (item, key) in items.iter().zip(keys) {
+        if !seen.iter().any(|other| key.equals(other)) {
+            seen.push(key);
+            result.push(item.clone());
+        }
+    }
+    RuntimeValue::array(result)
+}
+
+fn unique(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[0], stack)?)?;
+    Ok(unique_by_key(&target, &target))
+}
+
+fn uniqueby(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[1], stack)?)?;
+    let keys = target
+        .iter()
+        .map(|item| exec(&arguments[0], &add_runtime_value_to_stack(item, stack)))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(unique_by_key(&target, &keys))
+}
+
+fn zip(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let targets = arguments
+        .iter()
+        .map(|arg| array_items(exec(arg, stack)?))
+        .collect::<Result<Vec<_>>>()?;
+    let length = targets.iter().map(|target| target.len()).min().unwrap_or(0);
+    let result = (0..length)
+        .map(|i| RuntimeValue::array(targets.iter().map(|target| target[i].clone()).collect()))
+        .collect();
+    Ok(RuntimeValue::array(result))
+}
+
+/// The count argument of `take`, `drop`, or `chunk`.
+fn count_argument(name: &str, arguments: &Args, stack: &Stack, exec: Exec) -> Result<usize> {
+    let count = assert_int(exec(&arguments[0], stack)?)?;
+    if count < 0 {
+        return Err(MistQLError::Runtime(format!(
+            "{}: Count cannot be negative",
+            name
+        )));
+    }
+    Ok(count as usize)
+}
+
+fn chunk(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let size = count_argument("chunk", arguments, stack, exec)?;
+    if size == 0 {
+        return Err(MistQLError::Runtime(
+            "chunk: Size must be at least 1".to_string(),
+        ));
+    }
+    let target = array_items(exec(&arguments[1], stack)?)?;
+    let result = target
+        .chunks(size)
+        .map(|items| RuntimeValue::array(items.to_vec()))
+        .collect();
+    Ok(RuntimeValue::array(result))
+}
+
+fn take(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let count = count_argument("take", arguments, stack, exec)?;
+    let target = array_items(exec(&arguments[1], stack)?)?;
+    Ok(RuntimeValue::array(
+        target.iter().take(count).cloned().collect(),
+    ))
+}
+
+fn drop(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let count = count_argument("drop", arguments, stack, exec)?;
+    let target = array_items(exec(&arguments[1], stack)?)?;
+    Ok(RuntimeValue::array(
+        target.iter().skip(count).cloned().collect(),
+    ))
+}
+
+fn first(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[0], stack)?)?;
+    Ok(target.first().cloned().unwrap_or(RuntimeValue::Null))
+}
+
+fn last(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[0], stack)?)?;
+    Ok(target.last().cloned().unwrap_or(RuntimeValue::Null))
+}
+
+/// Whether an item, or the predicate applied to it, is truthy.
+fn item_truth(arguments: &Args, stack: &Stack, exec: Exec, item: &RuntimeValue) -> Result<bool> {
+    if arguments.len() == 2 {
+        return Ok(exec(&arguments[0], &add_runtime_value_to_stack(item, stack))?.truthy());
+    }
+    Ok(item.truthy())
+}
+
+fn any(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[arguments.len() - 1], stack)?)?;
+    for item in target.iter() {
+        if item_truth(arguments, stack, exec, item)? {
+            return Ok(RuntimeValue::Boolean(true));
+        }
+    }
+    Ok(RuntimeValue::Boolean(false))
+}
+
+fn all(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let target = array_items(exec(&arguments[arguments.len() - 1], stack)?)?;
+    for item in target.iter() {
+        if !item_truth(arguments, stack, exec, item)? {
+            return Ok(RuntimeValue::Boolean(false));
+        }
+    }
+    Ok(RuntimeValue::Boolean(true))
+}
+
+fn parsedate(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let date = dates::to_ms(exec(&arguments[0], stack)?)?;
+    Ok(RuntimeValue::number(date as f64))
+}
+
+fn formatdate(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let date = dates::to_ms(exec(&arguments[arguments.len() - 1], stack)?)?;
+    if arguments.len() == 1 {
+        return Ok(RuntimeValue::from(dates::format_iso(date)?));
+    }
+    let format = string_value(exec(&arguments[0], stack)?)?;
+    Ok(RuntimeValue::from(dates::format_date(&format, date)?))
+}
+
+fn datediff(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let unit = string_value(exec(&arguments[0], stack)?)?;
+    let start = dates::to_ms(exec(&arguments[1], stack)?)?;
+    let end = dates::to_ms(exec(&arguments[2], stack)?)?;
+    Ok(RuntimeValue::number(dates::diff(&unit, start, end)? as f64))
+}
+
+fn dateadd(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let unit = string_value(exec(&arguments[0], stack)?)?;
+    let amount = assert_int(exec(&arguments[1], stack)?)?;
+    let date = dates::to_ms(exec(&arguments[2], stack)?)?;
+    let result = dates::add(&unit, amount, date)?;
+    Ok(RuntimeValue::from(dates::format_iso(result)?))
+}
+
+fn truncdate(arguments: &Args, stack: &Stack, exec: Exec) -> Result<RuntimeValue> {
+    let unit = string_value(exec(&arguments[0], stack)?)?;
+    let date = dates::to_ms(exec(&arguments[1], stack)?)?;
+    Ok(RuntimeValue::from(dates::format_iso(dates::truncate(
+        &unit, date,
+    )?)?))
+}
+
+fn array_items(value: RuntimeValue) -> Result<Arc<Vec<RuntimeValue>>> {
+    match assert_type(value, RVT::Array)? {
+        RuntimeValue::Array(items) => Ok(items),
+        _ => unreachable!(),
+    }
+}
+
+fn object_entries(value: RuntimeValue) -> Result<Arc<BTreeMap<String, RuntimeValue>>> {
+    match assert_type(value, RVT::Object)? {
+        RuntimeValue::Object(entries) => Ok(entries),
+        _ => unreachable!(),
+    }
+}
+
+fn number_value(value: RuntimeValue) -> Result<f64> {
+    match assert_type(value, RVT::Number)? {
+        RuntimeValue::Number(n) => Ok(n),
+        _ => unreachable!(),
+    }
+}
+
+fn string_value(value: RuntimeValue) -> Result<String> {
+    match assert_type(value, RVT::String)? {
+        RuntimeValue::String(s) => Ok(s.to_string()),
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,311 @@
+//! Date math for the date builtins.
+//!
+//! Dates are whole milliseconds since the Unix epoch, always in UTC. Calendar
+//! math is done by hand so that every implementation parses, formats, and
+//! rounds dates exactly the same way.
+
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use regex::Regex;
+
+use crate::errors::{MistQLError, Result};
+use crate::runtime_value::RuntimeValue;
+
+const MS_PER_SECOND: i64 = 1000;
+const MS_PER_MINUTE: i64 = 60 * MS_PER_SECOND;
+const MS_PER_HOUR: i64 = 60 * MS_PER_MINUTE;
+const MS_PER_DAY: i64 = 24 * MS_PER_HOUR;
+
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// How long a unit is, either in milliseconds or in calendar months.
+enum Unit {
+    Fixed(i64),
+    Calendar(i64),
+}
+
+fn unit(name: &str) -> Result<Unit> {
+    match name {
+        "millisecond" => Ok(Unit::Fixed(1)),
+        "second" => Ok(Unit::Fixed(MS_PER_SECOND)),
+        "minute" => Ok(Unit::Fixed(MS_PER_MINUTE)),
+        "hour" => Ok(Unit::Fixed(MS_PER_HOUR)),
+        "day" => Ok(Unit::Fixed(MS_PER_DAY)),
+        "week" => Ok(Unit::Fixed(7 * MS_PER_DAY)),
+        "month" => Ok(Unit::Calendar(1)),
+        "year" => Ok(Unit::Calendar(12)),
+        _ => Err(MistQLError::Runtime(format!("Unknown date unit {}", name))),
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The year, month, and day of a number of days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    if month == 12 {
+        return 31;
+    }
+    days_from_civil(year, month + 1, 1) - days_from_civil(year, month, 1)
+}
+
+fn out_of_range() -> MistQLError {
+    MistQLError::Runtime("Date out of range: years must be 0 to 9999".to_string())
+}
+
+fn check_range(ms: i64) -> Result<i64> {
+    let min = days_from_civil(0, 1, 1) * MS_PER_DAY;
+    let max = days_from_civil(10000, 1, 1) * MS_PER_DAY - 1;
+    if ms < min || ms > max {
+        return Err(out_of_range());
+    }
+    Ok(ms)
+}
+
+fn iso_regex() -> &'static Regex {
+    static ISO_REGEX: OnceLock<Regex> = OnceLock::new();
+    ISO_REGEX.get_or_init(|| {
+        Regex::new(concat!(
+            r"^([0-9]{4})-([0-9]{2})-([0-9]{2})",
+            r"(?:[T ]([0-9]{2}):([0-9]{2})(?::([0-9]{2})(?:\.([0-9]+))?)?",
+            r"(Z|[+-][0-9]{2}:?[0-9]{2})?)?$",
+        ))
+        .unwrap()
+    })
+}
+
+fn parse_iso(text: &str) -> Result<i64> {
+    let invalid = || MistQLError::Runtime(format!("Invalid ISO 8601 date: {}", text));
+    let captures = iso_regex().captures(text).ok_or_else(invalid)?;
+    let field = |i: usize| {
+        captures
+            .get(i)
+            .map_or(0, |group| group.as_str().parse::<i64>().unwrap())
+    };
+    let (year, month, day) = (field(1), field(2), field(3));
+    let (hour, minute, second) = (field(4), field(5), field(6));
+    if !((1..=12).contains(&month)
+        && day >= 1
+        && day <= days_in_month(year, month)
+        && hour <= 23
+        && minute <= 59
+        && second <= 59)
+    {
+        return Err(invalid());
+    }
+    let mut ms = days_from_civil(year, month, day) * MS_PER_DAY;
+    ms += hour * MS_PER_HOUR + minute * MS_PER_MINUTE + second * MS_PER_SECOND;
+    // Digits past milliseconds are truncated
+    if let Some(fraction) = captures.get(7) {
+        let digits: String = fraction
+            .as_str()
+            .chars()
+            .chain("00".chars())
+            .take(3)
+            .collect();
+        ms += digits.parse::<i64>().unwrap();
+    }
+    if let Some(offset) = captures.get(8).map(|offset| offset.as_str()) {
+        if offset != "Z" {
+            let offset_hours: i64 = offset[1..3].parse().unwrap();
+            let offset_minutes: i64 = offset[offset.len() - 2..].parse().unwrap();
+            if offset_hours > 23 || offset_minutes > 59 {
+                return Err(invalid());
+            }
+            let sign = if offset.starts_with('+') { 1 } else { -1 };
+            ms -= sign * (offset_hours * MS_PER_HOUR + offset_minutes * MS_PER_MINUTE);
+        }
+    }
+    check_range(ms)
+}
+
+/// Reads a date from an ISO 8601 string or epoch milliseconds.
+pub fn to_ms(value: RuntimeValue) -> Result<i64> {
+    match value {
+        RuntimeValue::String(text) => parse_iso(&text),
+        RuntimeValue::Number(n) if n.fract() != 0.0 => Err(MistQLError::Type(format!(
+            "Expected integer milliseconds, got {}",
+            n
+        ))),
+        // Saturating at the bounds of i64 still leaves the number out of range
+        RuntimeValue::Number(n) => check_range(n as i64),
+        other => Err(MistQLError::Type(format!(
+            "Expected a date string or number, got {}",
+            other.get_type()
+        ))),
+    }
+}
+
+/// Milliseconds since the epoch of a point in time, rounded down.
+pub fn from_system_time(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_millis() as i64,
+        Err(err) => {
+            let before = err.duration();
+            let partial = if before.as_nanos() % 1_000_000 == 0 {
+                0
+            } else {
+                1
+            };
+            -(before.as_millis() as i64) - partial
+        }
+    }
+}
+
+/// The year, month, day, and milliseconds into the day of a date.
+fn split(ms: i64) -> (i64, i64, i64, i64) {
+    let (year, month, day) = civil_from_days(ms.div_euclid(MS_PER_DAY));
+    (year, month, day, ms.rem_euclid(MS_PER_DAY))
+}
+
+fn format_directive(directive: char, ms: i64) -> Result<String> {
+    let (year, month, day, time_of_day) = split(ms);
+    let days = ms.div_euclid(MS_PER_DAY);
+    Ok(match directive {
+        'Y' => format!("{:04}", year),
+        'm' => format!("{:02}", month),
+        'd' => format!("{:02}", day),
+        'H' => format!("{:02}", time_of_day / MS_PER_HOUR),
+        'M' => format!("{:02}", time_of_day / MS_PER_MINUTE % 60),
+        'S' => format!("{:02}", time_of_day / MS_PER_SECOND % 60),
+        'L' => format!("{:03}", time_of_day % MS_PER_SECOND),
+        'j' => format!("{:03}", days - days_from_civil(year, 1, 1) + 1),
+        // 1970-01-01 was a Thursday
+        'A' => WEEKDAYS[(days + 3).rem_euclid(7) as usize].to_string(),
+        'a' => WEEKDAYS[(days + 3).rem_euclid(7) as usize][..3].to_string(),
+        'B' => MONTHS[month as usize - 1].to_string(),
+        'b' => MONTHS[month as usize - 1][..3].to_string(),
+        '%' => "%".to_string(),
+        _ => {
+            return Err(MistQLError::Runtime(format!(
+                "Unknown date format directive %{}",
+                directive
+            )))
+        }
+    })
+}
+
+pub fn format_date(format: &str, ms: i64) -> Result<String> {
+    let mut result = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            result.push(c);
+            continue;
+        }
+        let directive = chars
+            .next()
+            .ok_or_else(|| MistQLError::Runtime("Date format ends with a lone %".to_string()))?;
+        result.push_str(&format_directive(directive, ms)?);
+    }
+    Ok(result)
+}
+
+pub fn format_iso(ms: i64) -> Result<String> {
+    format_date("%Y-%m-%dT%H:%M:%S.%LZ", check_range(ms)?)
+}
+
+fn add_months(ms: i64, count: i64) -> i64 {
+    let (year, month, day, time_of_day) = split(ms);
+    let total = year * 12 + month - 1 + count;
+    let (new_year, new_month) = (total.div_euclid(12), total.rem_euclid(12) + 1);
+    // Days past the end of the new month are clamped to its last day
+    let new_day = day.min(days_in_month(new_year, new_month));
+    days_from_civil(new_year, new_month, new_day) * MS_PER_DAY + time_of_day
+}
+
+pub fn add(unit_name: &str, amount: i64, ms: i64) -> Result<i64> {
+    match unit(unit_name)? {
+        Unit::Fixed(length) => amount
+            .checked_mul(length)
+            .and_then(|delta| ms.checked_add(delta))
+            .ok_or_else(out_of_range)
+            .and_then(check_range),
+        Unit::Calendar(months) => {
+            let count = amount.checked_mul(months).ok_or_else(out_of_range)?;
+            // No in-range date is this many months from another
+            if count.abs() > 12 * 10000 {
+                return Err(out_of_range());
+            }
+            check_range(add_months(ms, count))
+        }
+    }
+}
+
+/// The number of whole units from start to end, truncated toward zero.
+pub fn diff(unit_name: &str, start: i64, end: i64) -> Result<i64> {
+    match unit(unit_name)? {
+        Unit::Fixed(length) => Ok((end - start) / length),
+        Unit::Calendar(months) => {
+            let (start_year, start_month, _, _) = split(start);
+            let (end_year, end_month, _, _) = split(end);
+            let mut count = (end_year - start_year) * 12 + end_month - start_month;
+            if count > 0 && add_months(start, count) > end {
+                count -= 1;
+            } else if count < 0 && add_months(start, count) < end {
+                count += 1;
+            }
+            Ok(count / months)
+        }
+    }
+}
+
+/// The start of the unit the date falls in. Weeks start on Monday.
+pub fn truncate(unit_name: &str, ms: i64) -> Result<i64> {
+    Ok(match unit(unit_name)? {
+        Unit::Fixed(_) if unit_name == "week" => {
+            let days = ms.div_euclid(MS_PER_DAY);
+            (days - (days + 3).rem_euclid(7)) * MS_PER_DAY
+        }
+        Unit::Fixed(length) => ms - ms.rem_euclid(length),
+        Unit::Calendar(months) => {
+            let (year, month, _, _) = split(ms);
+            let month = if months == 12 { 1 } else { month };
+            days_from_civil(year, month, 1) * MS_PER_DAY
+        }
+    })
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+
+const OPEN_AN_ISSUE: &str = "Please open an issue if you get this error.
+
+Issues can be submitted at https://github.com/evinism/mistql/issues/new";
+
+/// All errors raised by MistQL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MistQLError {
+    /// Raised when a query cannot be parsed.
+    Parse(String),
+    /// Raised when the MistQL expression is invalid.
+    Runtime(String),
+    /// Raised when a reference is invalid.
+    Reference(String),
+    /// Raised when a value is of the wrong type.
+    Type(String),
+    /// Raised when a query nests too deeply to run without overflowing the
+    /// stack.
+    ResourceLimit(String),
+    /// Please open an issue if you get this error.
+    OpenAnIssue(String),
+}
+
+impl MistQLError {
+    /// The error message, without any decoration.
+    pub fn message(&self) -> &str {
+        match self {
+            MistQLError::Parse(message)
+            | MistQLError::Runtime(message)
+            | MistQLError::Reference(message)
+            | MistQLError::Type(message)
+            | MistQLError::ResourceLimit(message)
+            | MistQLError::OpenAnIssue(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for MistQLError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MistQLError::OpenAnIssue(message) => write!(f, "{}\n\n{}", message, OPEN_AN_ISSUE),
+            _ => f.write_str(self.message()),
+        }
+    }
+}
+
+impl std::error::Error for MistQLError {}
+
+pub type Result<T> = std::result::Result<T, MistQLError>;
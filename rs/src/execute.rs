@@ -0,0 +1,205 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::builtins::builtins;
+use crate::errors::{MistQLError, Result};
+use crate::expression::{ArrayItem, Expression, ObjectEntry};
+use crate::runtime_value::RuntimeValue;
+use crate::stack::{
+    add_bindings_to_stack, add_runtime_value_to_stack, build_initial_stack, find_in_stack, Stack,
+    StackFrame,
+};
+
+fn execute_fncall(
+    head: &Expression,
+    arguments: &[Expression],
+    stack: &Stack,
+) -> Result<RuntimeValue> {
+    match execute(head, stack)? {
+        RuntimeValue::Function(function_definition) => {
+            function_definition(arguments, stack, execute)
+        }
+        other => Err(MistQLError::Type(format!(
+            "Tried to call a non-function: {:?}",
+            other
+        ))),
+    }
+}
+
+fn execute_pipe(stages: &[Expression], stack: &Stack) -> Result<RuntimeValue> {
+    let (first, remaining) = stages
+        .split_first()
+        .ok_or_else(|| MistQLError::OpenAnIssue("Pipe has no stages!!".to_string()))?;
+    let mut data = execute(first, stack)?;
+
+    for stage_ast in remaining {
+        let new_stack = add_runtime_value_to_stack(&data, stack);
+        let (function, args) = match stage_ast {
+            Expression::Fncall { function, args } => (function, args),
+            _ => {
+                return Err(MistQLError::OpenAnIssue(
+                    "Pipe stage is not a function!!".to_string(),
+                ))
+            }
+        };
+        let mut args = args.clone();
+        args.push(Expression::Value(data));
+        data = execute_fncall(function, &args, &new_stack)?;
+    }
+
+    Ok(data)
+}
+
+struct Lambda {
+    name: String,
+    params: Vec<String>,
+    body: Expression,
+    stack: Stack,
+}
+
+/// Functions defined in a query close over the stack they're defined in.
+fn make_function(lambda: Arc<Lambda>) -> RuntimeValue {
+    RuntimeValue::wrap_function_def(move |arguments, caller, exec| {
+        let arity = lambda.params.len();
+        if arguments.len() < arity {
+            return Err(MistQLError::Runtime(format!(
+                "{} takes at least {} arguments",
+                lambda.name, arity
+            )));
+        }
+        if arguments.len() > arity {
+            return Err(MistQLError::Runtime(format!(
+                "{} takes at most {} arguments",
+                lambda.name, arity
+            )));
+        }
+        // Rebuilt on each call rather than captured, to avoid a reference cycle
+        let mut bindings = StackFrame::from([(lambda.name.clone(), make_function(lambda.clone()))]);
+        for (param, argument) in lambda.params.iter().zip(arguments) {
+            bindings.insert(param.clone(), exec(argument, caller)?);
+        }
+        execute(
+            &lambda.body,
+            &add_bindings_to_stack(bindings, &lambda.stack),
+        )
+    })
+}
+
+fn cannot_spread(value: &RuntimeValue, into: &str) -> MistQLError {
+    MistQLError::Type(format!(
+        "Cannot spread {} into an {}",
+        value.get_type(),
+        into
+    ))
+}
+
+fn execute_array(items: &[ArrayItem], stack: &Stack) -> Result<RuntimeValue> {
+    let mut result = Vec::new();
+    for item in items {
+        match item {
+            ArrayItem::Item(item) => result.push(execute(item, stack)?),
+            ArrayItem::Spread(spread) => match execute(spread, stack)? {
+                RuntimeValue::Array(spread) => result.extend(spread.iter().cloned()),
+                other => return Err(cannot_spread(&other, "array")),
+            },
+        }
+    }
+    Ok(RuntimeValue::array(result))
+}
+
+fn execute_object(entries: &[ObjectEntry], stack: &Stack) -> Result<RuntimeValue> {
+    let mut result = BTreeMap::new();
+    for entry in entries {
+        match entry {
+            ObjectEntry::Field(key, value) => {
+                result.insert(key.clone(), execute(value, stack)?);
+            }
+            ObjectEntry::Computed(key, value) => {
+                let key = execute(key, stack)?.to_string()?;
+                result.insert(key, execute(value, stack)?);
+            }
+            ObjectEntry::Spread(spread) => match execute(spread, stack)? {
+                RuntimeValue::Object(spread) => {
+                    result.extend(spread.iter().map(|(k, v)| (k.clone(), v.clone())))
+                }
+                other => return Err(cannot_spread(&other, "object")),
+            },
+        }
+    }
+    Ok(RuntimeValue::object(result))
+}
+
+/// How deeply expressions may nest while executing, counting each function
+/// call's body as a level below the call. Past this, recursing any further
+/// risks overflowing the stack, which would abort the whole process.
+pub const MAX_DEPTH: usize = 250;
+
+thread_local! {
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts a level of nesting for as long as it's held.
+struct DepthGuard;
+
+impl DepthGuard {
+    fn enter() -> Result<DepthGuard> {
+        let depth = DEPTH.with(|depth| {
+            depth.set(depth.get() + 1);
+            depth.get()
+        });
+        // Created before checking, so that the level is released either way
+        let guard = DepthGuard;
+        if depth > MAX_DEPTH {
+            return Err(MistQLError::ResourceLimit(
+                "Exceeded the maximum recursion depth".to_string(),
+            ));
+        }
+        Ok(guard)
+    }
+}
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+pub fn execute(ast: &Expression, stack: &Stack) -> Result<RuntimeValue> {
+    let _guard = DepthGuard::enter()?;
+    execute_expression(ast, stack)
+}
+
+fn execute_expression(ast: &Expression, stack: &Stack) -> Result<RuntimeValue> {
+    match ast {
+        Expression::Value(value) => Ok(value.clone()),
+        Expression::Reference {
+            name,
+            absolute,
+            quoted,
+        } => find_in_stack(stack, name, *absolute, *quoted),
+        Expression::Fncall { function, args } => execute_fncall(function, args, stack),
+        Expression::Array(items) => execute_array(items, stack),
+        Expression::Object(entries) => execute_object(entries, stack),
+        Expression::Pipe(stages) => execute_pipe(stages, stack),
+        Expression::Let { name, value, body } => {
+            let value = execute(value, stack)?;
+            let bindings = StackFrame::from([(name.clone(), value)]);
+            execute(body, &add_bindings_to_stack(bindings, stack))
+        }
+        Expression::Lambda { name, params, body } => Ok(make_function(Arc::new(Lambda {
+            name: name.clone(),
+            params: params.clone(),
+            body: (**body).clone(),
+            stack: stack.clone(),
+        }))),
+    }
+}
+
+pub fn execute_outer(
+    ast: &Expression,
+    data: &RuntimeValue,
+    extras: &HashMap<String, RuntimeValue>,
+) -> Result<RuntimeValue> {
+    execute(ast, &build_initial_stack(data, builtins(), extras))
+}
@@ -0,0 +1,86 @@
+use crate::runtime_value::RuntimeValue;
+
+/// Represents the MistQL expression, after parsing
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Fncall {
+        function: Box<Expression>,
+        args: Vec<Expression>,
+    },
+    Reference {
+        name: String,
+        /// Absolute references skip the data stack and resolve directly
+        /// against the builtins. Used for operators, so that data can't
+        /// shadow them.
+        absolute: bool,
+        /// Quoted in backticks, so a name like `@` or `@^` is a field rather
+        /// than a context.
+        quoted: bool,
+    },
+    Value(RuntimeValue),
+    Array(Vec<ArrayItem>),
+    /// Entries are evaluated in order, so later keys override earlier ones.
+    Object(Vec<ObjectEntry>),
+    Pipe(Vec<Expression>),
+    /// Binds the value to the name while evaluating the body.
+    Let {
+        name: String,
+        value: Box<Expression>,
+        body: Box<Expression>,
+    },
+    /// A function defined within a query, named for recursion and errors.
+    Lambda {
+        name: String,
+        params: Vec<String>,
+        body: Box<Expression>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArrayItem {
+    Item(Expression),
+    /// `...value`, splicing in the items of an array.
+    Spread(Expression),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectEntry {
+    Field(String, Expression),
+    /// A key that's evaluated and converted to a string, e.g. `{(name): 1}`.
+    Computed(Expression, Expression),
+    /// `...value`, splicing in the entries of an object.
+    Spread(Expression),
+}
+
+impl Expression {
+    pub fn fncall(function: Expression, args: Vec<Expression>) -> Expression {
+        Expression::Fncall {
+            function: Box::new(function),
+            args,
+        }
+    }
+
+    pub fn reference(name: &str) -> Expression {
+        Expression::Reference {
+            name: name.to_string(),
+            absolute: false,
+            quoted: false,
+        }
+    }
+
+    pub fn quoted_reference(name: &str) -> Expression {
+        Expression::Reference {
+            name: name.to_string(),
+            absolute: false,
+            quoted: true,
+        }
+    }
+
+    pub fn absolute_reference(name: &str) -> Expression {
+        Expression::Reference {
+            name: name.to_string(),
+            absolute: true,
+            quoted: false,
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use serde_json::Value;
+
+use crate::errors::Result;
+use crate::runtime_value::RuntimeValue;
+
+pub fn input_garden_wall(data: &Value) -> RuntimeValue {
+    RuntimeValue::of(data)
+}
+
+pub fn output_garden_wall(data: &RuntimeValue) -> Result<Value> {
+    data.to_json_value()
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+use std::time::SystemTime;
+
+use serde_json::Value;
+
+use crate::dates::{format_iso, from_system_time};
+use crate::errors::Result;
+use crate::execute::execute_outer;
+use crate::expression::Expression;
+use crate::gardenwall::{input_garden_wall, output_garden_wall};
+use crate::parse::parse;
+use crate::runtime_value::RuntimeValue;
+
+/// Returns the time that queries see as `now`.
+pub type Clock = Arc<dyn Fn() -> SystemTime + Send + Sync>;
+
+/// A query that has already been parsed, and can be run repeatedly against
+/// different data.
+#[derive(Clone)]
+pub struct CompiledQuery {
+    pub query: String,
+    pub ast: Expression,
+    extras: HashMap<String, RuntimeValue>,
+    now: Option<Clock>,
+}
+
+impl CompiledQuery {
+    pub fn run(&self, data: &Value) -> Result<Value> {
+        let data = input_garden_wall(data);
+        // now is read once per run, so it's the same everywhere in a query
+        let now = self.now.as_ref().map_or_else(SystemTime::now, |now| now());
+        let mut extras = HashMap::new();
+        extras.insert(
+            "now".to_string(),
+            RuntimeValue::from(format_iso(from_system_time(now))?),
+        );
+        extras.extend(self.extras.clone());
+        let result = execute_outer(&self.ast, &data, &extras)?;
+        output_garden_wall(&result)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MistQLInstance {
+    pub extras: HashMap<String, RuntimeValue>,
+    /// The clock queries read `now` from, or the system clock if unset.
+    pub now: Option<Clock>,
+}
+
+impl MistQLInstance {
+    pub fn new(extras: HashMap<String, RuntimeValue>) -> MistQLInstance {
+        MistQLInstance { extras, now: None }
+    }
+
+    /// Replaces the clock queries read `now` from.
+    pub fn with_now(mut self, now: impl Fn() -> SystemTime + Send + Sync + 'static) -> Self {
+        self.now = Some(Arc::new(now));
+        self
+    }
+
+    pub fn compile(&self, query: &str) -> Result<CompiledQuery> {
+        Ok(CompiledQuery {
+            query: query.to_string(),
+            ast: parse(query)?,
+            extras: self.extras.clone(),
+            now: self.now.clone(),
+        })
+    }
+
+    pub fn query(&self, query: &str, data: &Value) -> Result<Value> {
+        self.compile(query)?.run(data)
+    }
+}
+
+pub fn default_instance() -> &'static MistQLInstance {
+    static DEFAULT_INSTANCE: OnceLock<MistQLInstance> = OnceLock::new();
+    DEFAULT_INSTANCE.get_or_init(MistQLInstance::default)
+}
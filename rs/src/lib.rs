@@ -0,0 +1,31 @@
+//! Rust implementation of MistQL, a miniature embeddable query language for
+//! JSON-like structures.
+
+pub mod builtins;
+pub mod dates;
+pub mod errors;
+pub mod execute;
+pub mod expression;
+pub mod gardenwall;
+pub mod instance;
+pub mod parse;
+pub mod runtime_value;
+pub mod stack;
+
+use serde_json::Value;
+
+pub use errors::{MistQLError, Result};
+pub use instance::{CompiledQuery, MistQLInstance};
+pub use runtime_value::RuntimeValue;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Executes a query on a given data.
+pub fn query(query: &str, data: &Value) -> Result<Value> {
+    instance::default_instance().query(query, data)
+}
+
+/// Parses a query once, so that it can be run against many pieces of data.
+pub fn compile(query: &str) -> Result<CompiledQuery> {
+    instance::default_instance().compile(query)
+}
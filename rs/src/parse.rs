@@ -0,0 +1,814 @@
+use crate::errors::{MistQLError, Result};
+use crate::expression::{ArrayItem, Expression, ObjectEntry};
+use crate::runtime_value::RuntimeValue;
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Value(RuntimeValue),
+    Ref(String),
+    /// A name written in backticks, which can be anything and is never a keyword.
+    QuotedRef(String),
+    Special(&'static str),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+// Binary operators in order of increasing precedence.
+const BINARY_OPERATORS: &[&[&str]] = &[
+    &["??"],
+    &["||"],
+    &["&&"],
+    &["==", "!=", "=~"],
+    &[">", "<", ">=", "<="],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+const THREE_CHAR_SPECIALS: &[&str] = &["..."];
+const TWO_CHAR_SPECIALS: &[&str] = &["<=", ">=", "==", "!=", "=~", "&&", "||", "??", "?."];
+const ONE_CHAR_SPECIALS: &[&str] = &[
+    ".", "*", "/", "%", "+", "-", "<", ">", "|", "(", ")", "[", "]", "{", "}", ":", ",", "!", "=",
+];
+
+/// Whether a special token swallows the whitespace to its left and right.
+/// Whitespace that isn't swallowed is significant, as it separates the
+/// arguments of a function call.
+fn vacuums_whitespace(special: &str) -> (bool, bool) {
+    match special {
+        ")" | "}" | "]" => (true, false),
+        "(" | "{" | "[" | "..." => (false, true),
+        "!" => (false, false),
+        _ => (true, true),
+    }
+}
+
+/// How deeply expressions may nest, e.g. in brackets, before parsing fails.
+/// Each level recurses through the parser, so deeper input could overflow
+/// the stack.
+const MAX_NESTING: usize = 64;
+
+fn precedence(special: &str) -> Option<usize> {
+    BINARY_OPERATORS
+        .iter()
+        .position(|level| level.contains(&special))
+}
+
+fn parse_error(message: String, position: usize) -> MistQLError {
+    MistQLError::Parse(format!("{} at position {}", message, position))
+}
+
+fn lex_number(chars: &[char], start: usize) -> usize {
+    let mut i = start;
+    let digits = |i: &mut usize| {
+        while *i < chars.len() && chars[*i].is_ascii_digit() {
+            *i += 1;
+        }
+    };
+    if chars[i] == '0' {
+        i += 1;
+    } else {
+        digits(&mut i);
+    }
+    if i < chars.len() && chars[i] == '.' {
+        i += 1;
+        digits(&mut i);
+    }
+    if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+        let mut j = i + 1;
+        if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+            j += 1;
+        }
+        if j < chars.len() && chars[j].is_ascii_digit() {
+            i = j;
+            digits(&mut i);
+        }
+    }
+    i
+}
+
+fn lex(raw: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = raw.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let position = i;
+        let c = chars[i];
+        if c.is_ascii_digit() {
+            let end = lex_number(&chars, i);
+            let text: String = chars[i..end].iter().collect();
+            let value = text
+                .parse::<f64>()
+                .map_err(|_| parse_error(format!("Invalid number {}", text), position))?;
+            tokens.push(Token {
+                kind: TokenKind::Value(RuntimeValue::Number(value)),
+                position,
+            });
+            i = end;
+        } else if c.is_whitespace() {
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+            tokens.push(Token {
+                kind: TokenKind::Special(" "),
+                position,
+            });
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[position..i].iter().collect();
+            let kind = match name.as_str() {
+                "true" => TokenKind::Value(RuntimeValue::Boolean(true)),
+                "false" => TokenKind::Value(RuntimeValue::Boolean(false)),
+                "null" => TokenKind::Value(RuntimeValue::Null),
+                _ => TokenKind::Ref(name),
+            };
+            tokens.push(Token { kind, position });
+        } else if c == '@' || c == '$' {
+            // `@^` is the context enclosing `@`, `@^^` the one enclosing that
+            i += 1;
+            while c == '@' && i < chars.len() && chars[i] == '^' {
+                i += 1;
+            }
+            tokens.push(Token {
+                kind: TokenKind::Ref(chars[position..i].iter().collect()),
+                position,
+            });
+        } else if c == '`' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|&c| c == '`')
+                .map(|offset| i + 1 + offset)
+                .ok_or_else(|| {
+                    parse_error("Unterminated quoted identifier".to_string(), position)
+                })?;
+            tokens.push(Token {
+                kind: TokenKind::QuotedRef(chars[i + 1..end].iter().collect()),
+                position,
+            });
+            i = end + 1;
+        } else if c == '"' {
+            i += 1;
+            let mut escaped = false;
+            while i < chars.len() && (escaped || chars[i] != '"') {
+                escaped = !escaped && chars[i] == '\\';
+                i += 1;
+            }
+            if i >= chars.len() {
+                return Err(parse_error(
+                    "Unterminated string literal".to_string(),
+                    position,
+                ));
+            }
+            i += 1;
+            let literal: String = chars[position..i].iter().collect();
+            let value: String = serde_json::from_str(&literal)
+                .map_err(|_| parse_error("Invalid string literal".to_string(), position))?;
+            tokens.push(Token {
+                kind: TokenKind::Value(RuntimeValue::from(value)),
+                position,
+            });
+        } else {
+            let three: String = chars[i..(i + 3).min(chars.len())].iter().collect();
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let special = THREE_CHAR_SPECIALS
+                .iter()
+                .find(|s| **s == three)
+                .or_else(|| TWO_CHAR_SPECIALS.iter().find(|s| **s == two))
+                .or_else(|| ONE_CHAR_SPECIALS.iter().find(|s| s.starts_with(c)))
+                .ok_or_else(|| parse_error(format!("Unexpected character '{}'", c), i))?;
+            i += special.chars().count();
+            let (vacuums_left, vacuums_right) = vacuums_whitespace(special);
+            if vacuums_right {
+                while i < chars.len() && chars[i].is_whitespace() {
+                    i += 1;
+                }
+            }
+            if vacuums_left && is_whitespace_token(tokens.last()) {
+                tokens.pop();
+            }
+            tokens.push(Token {
+                kind: TokenKind::Special(special),
+                position,
+            });
+        }
+    }
+
+    // Leading and trailing whitespace is never significant.
+    if is_whitespace_token(tokens.first()) {
+        tokens.remove(0);
+    }
+    if is_whitespace_token(tokens.last()) {
+        tokens.pop();
+    }
+    Ok(tokens)
+}
+
+fn is_whitespace_token(token: Option<&Token>) -> bool {
+    matches!(
+        token,
+        Some(Token {
+            kind: TokenKind::Special(" "),
+            ..
+        })
+    )
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    offset: usize,
+    raw_len: usize,
+    /// Whether a whitespace separated `in` ends the current expression, as
+    /// it does in the value of a let-binding.
+    in_let_value: bool,
+    /// How many expressions the parser is currently inside of.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a TokenKind> {
+        self.tokens.get(self.offset).map(|token| &token.kind)
+    }
+
+    fn peek_special(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(TokenKind::Special(special)) => Some(special),
+            _ => None,
+        }
+    }
+
+    fn peek_ahead(&self, distance: usize) -> Option<&'a TokenKind> {
+        self.tokens
+            .get(self.offset + distance)
+            .map(|token| &token.kind)
+    }
+
+    /// `let` is only a keyword when followed by a binding, so references
+    /// named `let` keep working.
+    fn at_let(&self) -> bool {
+        matches!(self.peek(), Some(TokenKind::Ref(name)) if name == "let")
+            && matches!(self.peek_ahead(1), Some(TokenKind::Special(" ")))
+            && matches!(self.peek_ahead(2), Some(TokenKind::Ref(name)) if !name.starts_with(['@', '$']))
+            && matches!(self.peek_ahead(3), Some(TokenKind::Special("=")))
+    }
+
+    /// The parameters of the function defined here, if there is one.
+    fn def_params(&self) -> Option<Vec<String>> {
+        let is_binding = |kind: Option<&TokenKind>| matches!(kind, Some(TokenKind::Ref(name)) if !name.starts_with(['@', '$']));
+        if !(matches!(self.peek(), Some(TokenKind::Ref(name)) if name == "def")
+            && matches!(self.peek_ahead(1), Some(TokenKind::Special(" ")))
+            && is_binding(self.peek_ahead(2)))
+        {
+            return None;
+        }
+        let mut params = vec![];
+        let mut distance = 3;
+        while matches!(self.peek_ahead(distance), Some(TokenKind::Special(" ")))
+            && is_binding(self.peek_ahead(distance + 1))
+        {
+            if let Some(TokenKind::Ref(param)) = self.peek_ahead(distance + 1) {
+                params.push(param.clone());
+            }
+            distance += 2;
+        }
+        match self.peek_ahead(distance) {
+            Some(TokenKind::Special("=")) if !params.is_empty() => Some(params),
+            _ => None,
+        }
+    }
+
+    fn at_let_in(&self) -> bool {
+        self.in_let_value
+            && self.peek_special() == Some(" ")
+            && matches!(self.peek_ahead(1), Some(TokenKind::Ref(name)) if name == "in")
+    }
+
+    /// Parses within brackets, where `in` can't end a let-binding's value.
+    fn parse_nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let in_let_value = std::mem::replace(&mut self.in_let_value, false);
+        let result = parse(self);
+        self.in_let_value = in_let_value;
+        result
+    }
+
+    /// Parses a subexpression, failing rather than overflowing the stack on
+    /// deeply nested input.
+    fn descend<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth == MAX_NESTING {
+            return Err(parse_error(
+                "Exceeded the maximum nesting depth".to_string(),
+                self.position(),
+            ));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Parses the value of a binding, along with the `in` that ends it.
+    fn parse_let_value(&mut self) -> Result<Expression> {
+        let in_let_value = std::mem::replace(&mut self.in_let_value, true);
+        let value = self.parse_piped()?;
+        if !self.at_let_in() {
+            return Err(self.unexpected());
+        }
+        self.in_let_value = in_let_value;
+        self.offset += 2;
+        if self.peek_special() == Some(" ") {
+            self.offset += 1;
+        }
+        Ok(value)
+    }
+
+    fn parse_let(&mut self) -> Result<Expression> {
+        let name = match self.peek_ahead(2) {
+            Some(TokenKind::Ref(name)) => name.clone(),
+            _ => return Err(self.unexpected()),
+        };
+        self.offset += 3;
+        self.expect("=")?;
+        let value = self.parse_let_value()?;
+        let body = self.parse_piped()?;
+        Ok(Expression::Let {
+            name,
+            value: Box::new(value),
+            body: Box::new(body),
+        })
+    }
+
+    /// `def f x = value in body` is shorthand for binding f to a function.
+    fn parse_def(&mut self, params: Vec<String>) -> Result<Expression> {
+        let name = match self.peek_ahead(2) {
+            Some(TokenKind::Ref(name)) => name.clone(),
+            _ => return Err(self.unexpected()),
+        };
+        self.offset += 3 + 2 * params.len();
+        self.expect("=")?;
+        let value = self.parse_let_value()?;
+        let body = self.parse_piped()?;
+        Ok(Expression::Let {
+            name: name.clone(),
+            value: Box::new(Expression::Lambda {
+                name,
+                params,
+                body: Box::new(value),
+            }),
+            body: Box::new(body),
+        })
+    }
+
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.offset)
+            .map_or(self.raw_len, |token| token.position)
+    }
+
+    fn unexpected(&self) -> MistQLError {
+        match self.tokens.get(self.offset) {
+            Some(token) => parse_error(
+                format!("Unexpected token {}", describe(&token.kind)),
+                token.position,
+            ),
+            None => parse_error("Unexpected EOF".to_string(), self.raw_len),
+        }
+    }
+
+    fn expect(&mut self, special: &str) -> Result<()> {
+        if self.peek_special() == Some(special) {
+            self.offset += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn parse_piped(&mut self) -> Result<Expression> {
+        self.descend(Self::parse_pipeline)
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Expression> {
+        if self.at_let() {
+            return self.parse_let();
+        }
+        if let Some(params) = self.def_params() {
+            return self.parse_def(params);
+        }
+        let (first, _) = self.parse_application()?;
+        let mut stages = vec![first];
+        while self.peek_special() == Some("|") {
+            self.offset += 1;
+            let (stage, is_application) = self.parse_application()?;
+            if is_application {
+                stages.push(stage);
+            } else {
+                stages.push(Expression::fncall(stage, vec![]));
+            }
+        }
+        if stages.len() == 1 {
+            Ok(stages.pop().expect("there is always a first stage"))
+        } else {
+            Ok(Expression::Pipe(stages))
+        }
+    }
+
+    /// Parses whitespace-separated function application, returning whether
+    /// the result was an application.
+    fn parse_application(&mut self) -> Result<(Expression, bool)> {
+        let head = self.parse_binary(0)?;
+        let mut args = Vec::new();
+        while self.peek_special() == Some(" ") && !self.at_let_in() {
+            self.offset += 1;
+            args.push(self.parse_binary(0)?);
+        }
+        if args.is_empty() {
+            Ok((head, false))
+        } else {
+            Ok((Expression::fncall(head, args), true))
+        }
+    }
+
+    fn parse_binary(&mut self, min_precedence: usize) -> Result<Expression> {
+        let mut left = self.parse_unary()?;
+        while let Some(special) = self.peek_special() {
+            let prec = match precedence(special) {
+                Some(prec) if prec >= min_precedence => prec,
+                _ => break,
+            };
+            self.offset += 1;
+            let right = self.parse_binary(prec + 1)?;
+            left = Expression::fncall(Expression::absolute_reference(special), vec![left, right]);
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression> {
+        let name = match self.peek_special() {
+            Some("-") => "-/unary",
+            Some("!") => "!/unary",
+            _ => return self.parse_postfix(),
+        };
+        self.offset += 1;
+        if self.peek_special() == Some(" ") {
+            self.offset += 1;
+        }
+        let operand = self.descend(Self::parse_unary)?;
+        Ok(Expression::fncall(
+            Expression::absolute_reference(name),
+            vec![operand],
+        ))
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expression> {
+        let mut base = self.parse_simple()?;
+        loop {
+            match self.peek_special() {
+                // `?.` is the lenient form of `.`, giving null on non-objects
+                Some(accessor @ ("." | "?.")) => {
+                    self.offset += 1;
+                    let name = match self.peek() {
+                        Some(TokenKind::Ref(name) | TokenKind::QuotedRef(name)) => name.clone(),
+                        _ => return Err(self.unexpected()),
+                    };
+                    self.offset += 1;
+                    base = Expression::fncall(
+                        Expression::absolute_reference(accessor),
+                        vec![base, Expression::reference(&name)],
+                    );
+                }
+                Some("[") => {
+                    self.offset += 1;
+                    let mut args = self.parse_nested(Self::parse_index_innards)?;
+                    args.push(base);
+                    base = Expression::fncall(Expression::absolute_reference("index"), args);
+                }
+                _ => return Ok(base),
+            }
+        }
+    }
+
+    fn parse_index_innards(&mut self) -> Result<Vec<Expression>> {
+        let mut args = Vec::new();
+        let mut prev_was_colon = true;
+        loop {
+            match self.peek_special() {
+                Some(":") => {
+                    self.offset += 1;
+                    if prev_was_colon {
+                        args.push(Expression::Value(RuntimeValue::Null));
+                    }
+                    prev_was_colon = true;
+                }
+                Some("]") => {
+                    self.offset += 1;
+                    break;
+                }
+                _ if prev_was_colon => {
+                    args.push(self.parse_piped()?);
+                    prev_was_colon = false;
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+        if prev_was_colon {
+            args.push(Expression::Value(RuntimeValue::Null));
+        }
+        Ok(args)
+    }
+
+    fn parse_simple(&mut self) -> Result<Expression> {
+        let kind = match self.peek() {
+            Some(kind) => kind,
+            None => return Err(self.unexpected()),
+        };
+        match kind {
+            TokenKind::Value(value) => {
+                self.offset += 1;
+                Ok(Expression::Value(value.clone()))
+            }
+            TokenKind::Ref(name) => {
+                self.offset += 1;
+                Ok(Expression::reference(name))
+            }
+            TokenKind::QuotedRef(name) => {
+                self.offset += 1;
+                Ok(Expression::quoted_reference(name))
+            }
+            TokenKind::Special("(") => {
+                self.offset += 1;
+                let inner = self.parse_nested(Self::parse_piped)?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            TokenKind::Special("[") => {
+                self.offset += 1;
+                self.parse_nested(Self::parse_array_innards)
+            }
+            TokenKind::Special("{") => {
+                self.offset += 1;
+                self.parse_nested(Self::parse_object_innards)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn parse_array_innards(&mut self) -> Result<Expression> {
+        let mut items = Vec::new();
+        if self.peek_special() == Some("]") {
+            self.offset += 1;
+            return Ok(Expression::Array(items));
+        }
+        loop {
+            if self.peek_special() == Some("...") {
+                self.offset += 1;
+                items.push(ArrayItem::Spread(self.parse_piped()?));
+            } else {
+                items.push(ArrayItem::Item(self.parse_piped()?));
+            }
+            match self.peek_special() {
+                Some(",") => self.offset += 1,
+                Some("]") => {
+                    self.offset += 1;
+                    return Ok(Expression::Array(items));
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    fn parse_object_innards(&mut self) -> Result<Expression> {
+        let mut entries = Vec::new();
+        if self.peek_special() == Some("}") {
+            self.offset += 1;
+            return Ok(Expression::Object(entries));
+        }
+        loop {
+            entries.push(self.parse_object_entry()?);
+            match self.peek_special() {
+                Some(",") => self.offset += 1,
+                Some("}") => {
+                    self.offset += 1;
+                    return Ok(Expression::Object(entries));
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+
+    fn parse_object_entry(&mut self) -> Result<ObjectEntry> {
+        match self.peek_special() {
+            Some("...") => {
+                self.offset += 1;
+                Ok(ObjectEntry::Spread(self.parse_piped()?))
+            }
+            Some("(") => {
+                self.offset += 1;
+                let key = self.parse_piped()?;
+                self.expect(")")?;
+                self.expect(":")?;
+                Ok(ObjectEntry::Computed(key, self.parse_piped()?))
+            }
+            _ => {
+                let key = self.parse_object_key()?;
+                self.expect(":")?;
+                Ok(ObjectEntry::Field(key, self.parse_piped()?))
+            }
+        }
+    }
+
+    fn parse_object_key(&mut self) -> Result<String> {
+        let key = match self.peek() {
+            Some(TokenKind::Ref(name)) if !name.starts_with(['@', '$']) => name.clone(),
+            Some(TokenKind::QuotedRef(name)) => name.clone(),
+            Some(TokenKind::Value(RuntimeValue::String(s))) => s.to_string(),
+            // Keywords are valid identifiers in key position.
+            Some(TokenKind::Value(RuntimeValue::Null)) => "null".to_string(),
+            Some(TokenKind::Value(RuntimeValue::Boolean(b))) => b.to_string(),
+            _ => return Err(self.unexpected()),
+        };
+        self.offset += 1;
+        Ok(key)
+    }
+}
+
+fn describe(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::Value(value) => format!("{:?}", value),
+        TokenKind::Ref(name) => name.clone(),
+        TokenKind::QuotedRef(name) => format!("`{}`", name),
+        TokenKind::Special(" ") => "whitespace".to_string(),
+        TokenKind::Special(special) => special.to_string(),
+    }
+}
+
+/// Parses a MistQL query into an expression tree.
+pub fn parse(raw: &str) -> Result<Expression> {
+    let tokens = lex(raw)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        offset: 0,
+        raw_len: raw.chars().count(),
+        in_let_value: false,
+        depth: 0,
+    };
+    let result = parser.parse_piped()?;
+    if parser.offset != tokens.len() {
+        return Err(parse_error(
+            format!(
+                "Unexpected token {}, expected EOF",
+                describe(&tokens[parser.offset].kind)
+            ),
+            parser.position(),
+        ));
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::errors::MistQLError;
+    use crate::expression::{ArrayItem, Expression, ObjectEntry};
+    use crate::runtime_value::RuntimeValue;
+
+    fn num(n: f64) -> Expression {
+        Expression::Value(RuntimeValue::Number(n))
+    }
+
+    #[test]
+    fn parses_precedence() {
+        assert_eq!(
+            parse("1 + 2 * 3").unwrap(),
+            Expression::fncall(
+                Expression::absolute_reference("+"),
+                vec![
+                    num(1.0),
+                    Expression::fncall(
+                        Expression::absolute_reference("*"),
+                        vec![num(2.0), num(3.0)]
+                    )
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn parses_function_application_and_pipes() {
+        assert_eq!(
+            parse("@ | map x | count").unwrap(),
+            Expression::Pipe(vec![
+                Expression::reference("@"),
+                Expression::fncall(
+                    Expression::reference("map"),
+                    vec![Expression::reference("x")]
+                ),
+                Expression::fncall(Expression::reference("count"), vec![]),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_indexing() {
+        assert_eq!(
+            parse("arr[1:]").unwrap(),
+            Expression::fncall(
+                Expression::absolute_reference("index"),
+                vec![
+                    num(1.0),
+                    Expression::Value(RuntimeValue::Null),
+                    Expression::reference("arr")
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn parses_computed_keys_and_spreads() {
+        assert_eq!(
+            parse("{(a): 1, ...b}").unwrap(),
+            Expression::Object(vec![
+                ObjectEntry::Computed(Expression::reference("a"), num(1.0)),
+                ObjectEntry::Spread(Expression::reference("b")),
+            ])
+        );
+        assert_eq!(
+            parse("[1, ... a]").unwrap(),
+            Expression::Array(vec![
+                ArrayItem::Item(num(1.0)),
+                ArrayItem::Spread(Expression::reference("a")),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_enclosing_contexts() {
+        assert_eq!(
+            parse("@^^.a").unwrap(),
+            Expression::fncall(
+                Expression::absolute_reference("."),
+                vec![Expression::reference("@^^"), Expression::reference("a")]
+            )
+        );
+        assert!(parse("let @^ = 1 in @^").is_err());
+        assert_eq!(parse("`@^`").unwrap(), Expression::quoted_reference("@^"));
+    }
+
+    #[test]
+    fn parses_let_bindings() {
+        assert_eq!(
+            parse("let x = f 1 in x").unwrap(),
+            Expression::Let {
+                name: "x".to_string(),
+                value: Box::new(Expression::fncall(
+                    Expression::reference("f"),
+                    vec![num(1.0)]
+                )),
+                body: Box::new(Expression::reference("x")),
+            }
+        );
+        assert_eq!(parse("let").unwrap(), Expression::reference("let"));
+    }
+
+    #[test]
+    fn parses_function_definitions() {
+        assert_eq!(
+            parse("def f x y = x in f").unwrap(),
+            Expression::Let {
+                name: "f".to_string(),
+                value: Box::new(Expression::Lambda {
+                    name: "f".to_string(),
+                    params: vec!["x".to_string(), "y".to_string()],
+                    body: Box::new(Expression::reference("x")),
+                }),
+                body: Box::new(Expression::reference("f")),
+            }
+        );
+        assert_eq!(parse("def").unwrap(), Expression::reference("def"));
+        assert!(parse("def f = 1 in f").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        assert!(parse("").is_err());
+        assert!(parse("1 +").is_err());
+        assert!(parse("(1").is_err());
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("\"abc").is_err());
+    }
+
+    #[test]
+    fn rejects_deeply_nested_input() {
+        let nested = |depth: usize| format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(63)).is_ok());
+        assert!(parse(&nested(64)).is_err());
+        assert!(matches!(parse(&nested(5000)), Err(MistQLError::Parse(_))));
+        assert!(matches!(
+            parse(&format!("{}1", "-".repeat(5000))),
+            Err(MistQLError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,526 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use regex::Regex;
+use serde_json::{Map, Number, Value};
+
+use crate::errors::{MistQLError, Result};
+use crate::expression::Expression;
+use crate::stack::Stack;
+
+/// Enumeration of the different types of runtime values available in MistQL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeValueType {
+    Null,
+    Boolean,
+    Number,
+    String,
+    Object,
+    Array,
+    Function,
+    Regex,
+}
+
+impl fmt::Display for RuntimeValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RuntimeValueType::Null => "null",
+            RuntimeValueType::Boolean => "boolean",
+            RuntimeValueType::Number => "number",
+            RuntimeValueType::String => "string",
+            RuntimeValueType::Object => "object",
+            RuntimeValueType::Array => "array",
+            RuntimeValueType::Function => "function",
+            RuntimeValueType::Regex => "regex",
+        })
+    }
+}
+
+/// The function used by builtins to evaluate their (unevaluated) arguments.
+pub type Exec = fn(&Expression, &Stack) -> Result<RuntimeValue>;
+
+/// The calling convention for all MistQL functions. Functions receive their
+/// arguments unevaluated, alongside the stack to evaluate them against.
+pub type FunctionDefinition =
+    Arc<dyn Fn(&[Expression], &Stack, Exec) -> Result<RuntimeValue> + Send + Sync>;
+
+/// A compiled regex, alongside the flags it was created with.
+#[derive(Debug)]
+pub struct RegexValue {
+    pub regex: Regex,
+    pub pattern: String,
+    /// The non-global flags (`i`, `m`, `s`), sorted and deduplicated.
+    pub flags: String,
+    /// Whether the `g` flag was passed. Only affects `replace`.
+    pub global: bool,
+}
+
+#[derive(Clone)]
+pub enum RuntimeValue {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(Arc<str>),
+    Array(Arc<Vec<RuntimeValue>>),
+    Object(Arc<BTreeMap<String, RuntimeValue>>),
+    Function(FunctionDefinition),
+    Regex(Arc<RegexValue>),
+}
+
+// Number formatting follows the JS `Number.prototype.toString` algorithm, so
+// that numbers print identically in every implementation.
+pub fn format_number(value: f64) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if value < 0.0 {
+        return format!("-{}", format_number(-value));
+    }
+
+    // Rust's exponential formatting yields the shortest digit string that
+    // round-trips, e.g. "1.5e-7", which is exactly what the JS algorithm needs.
+    let exponential = format!("{:e}", value);
+    let (mantissa, exponent) = exponential
+        .split_once('e')
+        .expect("exponential formatting always contains an exponent");
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    let n = exponent.parse::<i32>().expect("exponent is an integer") + 1;
+
+    if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        let (int_part, frac_part) = digits.split_at(n as usize);
+        format!("{}.{}", int_part, frac_part)
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat((-n) as usize), digits)
+    } else {
+        let e = n - 1;
+        let sign = if e < 0 { '-' } else { '+' };
+        if k == 1 {
+            format!("{}e{}{}", digits, sign, e.abs())
+        } else {
+            format!("{}.{}e{}{}", &digits[..1], &digits[1..], sign, e.abs())
+        }
+    }
+}
+
+impl RuntimeValue {
+    /// Convert a JSON value into a MistQL RuntimeValue
+    pub fn of(value: &Value) -> RuntimeValue {
+        match value {
+            Value::Null => RuntimeValue::Null,
+            Value::Bool(b) => RuntimeValue::Boolean(*b),
+            Value::Number(n) => RuntimeValue::number(n.as_f64().unwrap_or(f64::NAN)),
+            Value::String(s) => RuntimeValue::from(s.as_str()),
+            Value::Array(items) => {
+                RuntimeValue::array(items.iter().map(RuntimeValue::of).collect())
+            }
+            Value::Object(entries) => RuntimeValue::object(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), RuntimeValue::of(value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Create a number, casting NaN and the infinities to null.
+    pub fn number(value: f64) -> RuntimeValue {
+        if value.is_finite() {
+            RuntimeValue::Number(value)
+        } else {
+            RuntimeValue::Null
+        }
+    }
+
+    pub fn array(items: Vec<RuntimeValue>) -> RuntimeValue {
+        RuntimeValue::Array(Arc::new(items))
+    }
+
+    pub fn object(entries: BTreeMap<String, RuntimeValue>) -> RuntimeValue {
+        RuntimeValue::Object(Arc::new(entries))
+    }
+
+    /// Create a new function that can be used in MistQL expressions.
+    pub fn wrap_function_def<F>(definition: F) -> RuntimeValue
+    where
+        F: Fn(&[Expression], &Stack, Exec) -> Result<RuntimeValue> + Send + Sync + 'static,
+    {
+        RuntimeValue::Function(Arc::new(definition))
+    }
+
+    /// Create a new function from a Rust function that can be used in MistQL.
+    ///
+    /// Arguments are evaluated and converted to JSON values before being
+    /// passed to `func`. A `max_arity` of `None` allows any number of
+    /// arguments past `min_arity`.
+    pub fn from_rust_fn<F>(min_arity: usize, max_arity: Option<usize>, func: F) -> RuntimeValue
+    where
+        F: Fn(Vec<Value>) -> Result<Value> + Send + Sync + 'static,
+    {
+        RuntimeValue::wrap_function_def(move |args, stack, exec| {
+            if args.len() < min_arity {
+                return Err(MistQLError::Type(format!(
+                    "Function takes no fewer than {} arguments but {} were provided",
+                    min_arity,
+                    args.len()
+                )));
+            }
+            if let Some(max_arity) = max_arity {
+                if args.len() > max_arity {
+                    return Err(MistQLError::Type(format!(
+                        "Function takes no more than {} arguments but {} were provided",
+                        max_arity,
+                        args.len()
+                    )));
+                }
+            }
+            let rust_args = args
+                .iter()
+                .map(|arg| exec(arg, stack)?.to_json_value())
+                .collect::<Result<Vec<_>>>()?;
+            Ok(RuntimeValue::of(&func(rust_args)?))
+        })
+    }
+
+    pub fn get_type(&self) -> RuntimeValueType {
+        match self {
+            RuntimeValue::Null => RuntimeValueType::Null,
+            RuntimeValue::Boolean(_) => RuntimeValueType::Boolean,
+            RuntimeValue::Number(_) => RuntimeValueType::Number,
+            RuntimeValue::String(_) => RuntimeValueType::String,
+            RuntimeValue::Array(_) => RuntimeValueType::Array,
+            RuntimeValue::Object(_) => RuntimeValueType::Object,
+            RuntimeValue::Function(_) => RuntimeValueType::Function,
+            RuntimeValue::Regex(_) => RuntimeValueType::Regex,
+        }
+    }
+
+    /// Deep equality, as used by `==`.
+    pub fn equals(&self, other: &RuntimeValue) -> bool {
+        match (self, other) {
+            (RuntimeValue::Null, RuntimeValue::Null) => true,
+            (RuntimeValue::Boolean(a), RuntimeValue::Boolean(b)) => a == b,
+            (RuntimeValue::Number(a), RuntimeValue::Number(b)) => a == b,
+            (RuntimeValue::String(a), RuntimeValue::String(b)) => a == b,
+            (RuntimeValue::Array(a), RuntimeValue::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.equals(b))
+            }
+            (RuntimeValue::Object(a), RuntimeValue::Object(b)) => {
+                a.len() == b.len()
+                    && a.iter().all(|(key, value)| match b.get(key) {
+                        Some(other) => value.equals(other),
+                        None => false,
+                    })
+            }
+            (RuntimeValue::Regex(a), RuntimeValue::Regex(b)) => {
+                a.pattern == b.pattern && a.flags == b.flags && a.global == b.global
+            }
+            // referential equality
+            (RuntimeValue::Function(a), RuntimeValue::Function(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Compare two values
+    pub fn compare(a: &RuntimeValue, b: &RuntimeValue) -> Result<Ordering> {
+        if a.get_type() != b.get_type() {
+            return Err(MistQLError::Type(
+                "Cannot compare MistQL values of different types".to_string(),
+            ));
+        }
+        match (a, b) {
+            (RuntimeValue::Boolean(a), RuntimeValue::Boolean(b)) => Ok(a.cmp(b)),
+            (RuntimeValue::Number(a), RuntimeValue::Number(b)) => {
+                Ok(a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            }
+            (RuntimeValue::String(a), RuntimeValue::String(b)) => Ok(a.cmp(b)),
+            _ => Err(MistQLError::Type(format!(
+                "Cannot compare MistQL values of type {}",
+                a.get_type()
+            ))),
+        }
+    }
+
+    /// Check if the value is comparable
+    pub fn comparable(&self) -> bool {
+        matches!(
+            self,
+            RuntimeValue::Boolean(_) | RuntimeValue::Number(_) | RuntimeValue::String(_)
+        )
+    }
+
+    /// Convert a MistQL RuntimeValue into a JSON value
+    pub fn to_json_value(&self) -> Result<Value> {
+        match self {
+            RuntimeValue::Null => Ok(Value::Null),
+            RuntimeValue::Boolean(b) => Ok(Value::Bool(*b)),
+            RuntimeValue::Number(n) => Ok(number_to_json(*n)),
+            RuntimeValue::String(s) => Ok(Value::String(s.to_string())),
+            RuntimeValue::Array(items) => Ok(Value::Array(
+                items
+                    .iter()
+                    .map(|item| item.to_json_value())
+                    .collect::<Result<Vec<_>>>()?,
+            )),
+            RuntimeValue::Object(entries) => {
+                let mut map = Map::new();
+                for (key, value) in entries.iter() {
+                    map.insert(key.clone(), value.to_json_value()?);
+                }
+                Ok(Value::Object(map))
+            }
+            _ => Err(MistQLError::Type(format!(
+                "Cannot convert MistQL value type to JSON: {}",
+                self.get_type()
+            ))),
+        }
+    }
+
+    /// Return whether this value is truthy
+    pub fn truthy(&self) -> bool {
+        match self {
+            RuntimeValue::Null => false,
+            RuntimeValue::Boolean(b) => *b,
+            RuntimeValue::Number(n) => *n != 0.0,
+            RuntimeValue::String(s) => !s.is_empty(),
+            RuntimeValue::Array(items) => !items.is_empty(),
+            RuntimeValue::Object(entries) => !entries.is_empty(),
+            RuntimeValue::Function(_) => true,
+            RuntimeValue::Regex(_) => true,
+        }
+    }
+
+    /// Convert this value to a JSON string
+    pub fn to_json(&self, permissive: bool) -> Result<String> {
+        match self {
+            RuntimeValue::Null => Ok("null".to_string()),
+            RuntimeValue::Boolean(b) => Ok(b.to_string()),
+            RuntimeValue::Number(n) => Ok(format_number(*n)),
+            RuntimeValue::String(s) => Ok(quote(s)),
+            RuntimeValue::Array(items) => Ok(format!(
+                "[{}]",
+                items
+                    .iter()
+                    .map(|item| item.to_json(permissive))
+                    .collect::<Result<Vec<_>>>()?
+                    .join(",")
+            )),
+            RuntimeValue::Object(entries) => Ok(format!(
+                "{{{}}}",
+                entries
+                    .iter()
+                    .map(|(key, item)| Ok(format!("{}:{}", quote(key), item.to_json(permissive)?)))
+                    .collect::<Result<Vec<_>>>()?
+                    .join(",")
+            )),
+            RuntimeValue::Function(_) if permissive => Ok("[function]".to_string()),
+            RuntimeValue::Regex(_) if permissive => Ok("[regex]".to_string()),
+            _ => Err(MistQLError::Type(format!(
+                "Cannot convert MistQL value to JSON: {}",
+                self.get_type()
+            ))),
+        }
+    }
+
+    /// Convert this value to a string
+    pub fn to_string(&self) -> Result<String> {
+        match self {
+            RuntimeValue::String(s) => Ok(s.to_string()),
+            RuntimeValue::Number(n) => Ok(format_number(*n)),
+            _ => self.to_json(false),
+        }
+    }
+
+    pub fn to_float(&self) -> Result<f64> {
+        match self {
+            RuntimeValue::Number(n) => Ok(*n),
+            RuntimeValue::String(s) => parse_float(s),
+            RuntimeValue::Boolean(b) => Ok(if *b { 1.0 } else { 0.0 }),
+            RuntimeValue::Null => Ok(0.0),
+            _ => Err(MistQLError::Type(format!(
+                "Cannot convert MistQL value to float: {}",
+                self.get_type()
+            ))),
+        }
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        match self {
+            RuntimeValue::Object(entries) => entries.keys().cloned().collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Access a property of this value
+    pub fn access(&self, key: &str) -> RuntimeValue {
+        match self {
+            RuntimeValue::Object(entries) => {
+                entries.get(key).cloned().unwrap_or(RuntimeValue::Null)
+            }
+            _ => RuntimeValue::Null,
+        }
+    }
+}
+
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).expect("strings always serialize")
+}
+
+fn number_to_json(value: f64) -> Value {
+    // Integral values come back out as integers, so `1` doesn't become `1.0`.
+    if value.fract() == 0.0 && value.abs() < MAX_SAFE_INT {
+        Value::Number(Number::from(value as i64))
+    } else {
+        Number::from_f64(value).map_or(Value::Null, Value::Number)
+    }
+}
+
+const MAX_SAFE_INT: f64 = 9007199254740992.0;
+
+fn parse_float(s: &str) -> Result<f64> {
+    let trimmed = s.trim();
+    let mut chars = trimmed
+        .strip_prefix('-')
+        .unwrap_or(trimmed)
+        .chars()
+        .peekable();
+    let mut valid = match chars.next() {
+        Some('0') => true,
+        Some(c) if c.is_ascii_digit() => {
+            while chars.next_if(|c| c.is_ascii_digit()).is_some() {}
+            true
+        }
+        _ => false,
+    };
+    if valid && chars.next_if_eq(&'.').is_some() {
+        while chars.next_if(|c| c.is_ascii_digit()).is_some() {}
+    }
+    if valid && chars.next_if(|c| *c == 'e' || *c == 'E').is_some() {
+        chars.next_if(|c| *c == '+' || *c == '-');
+        valid = chars.next_if(|c| c.is_ascii_digit()).is_some();
+        while chars.next_if(|c| c.is_ascii_digit()).is_some() {}
+    }
+    if !valid || chars.next().is_some() {
+        return Err(MistQLError::Type(format!(
+            "Cannot cast string to float: {}",
+            s
+        )));
+    }
+    trimmed
+        .parse::<f64>()
+        .map_err(|_| MistQLError::Type(format!("Cannot cast string to float: {}", s)))
+}
+
+impl PartialEq for RuntimeValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.equals(other)
+    }
+}
+
+impl fmt::Debug for RuntimeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_json(true) {
+            Ok(json) => write!(f, "<mistql {}>", json),
+            Err(_) => f.write_str("<mistql [unknown]>"),
+        }
+    }
+}
+
+impl From<&str> for RuntimeValue {
+    fn from(value: &str) -> Self {
+        RuntimeValue::String(Arc::from(value))
+    }
+}
+
+impl From<String> for RuntimeValue {
+    fn from(value: String) -> Self {
+        RuntimeValue::String(Arc::from(value))
+    }
+}
+
+impl From<bool> for RuntimeValue {
+    fn from(value: bool) -> Self {
+        RuntimeValue::Boolean(value)
+    }
+}
+
+impl From<f64> for RuntimeValue {
+    fn from(value: f64) -> Self {
+        RuntimeValue::number(value)
+    }
+}
+
+impl From<Vec<RuntimeValue>> for RuntimeValue {
+    fn from(value: Vec<RuntimeValue>) -> Self {
+        RuntimeValue::array(value)
+    }
+}
+
+pub fn assert_type(value: RuntimeValue, expected_type: RuntimeValueType) -> Result<RuntimeValue> {
+    if value.get_type() != expected_type {
+        return Err(MistQLError::Type(format!(
+            "Expected {}, got {}",
+            expected_type,
+            value.get_type()
+        )));
+    }
+    Ok(value)
+}
+
+pub fn assert_types(
+    value: RuntimeValue,
+    expected_types: &[RuntimeValueType],
+) -> Result<RuntimeValue> {
+    if !expected_types.contains(&value.get_type()) {
+        let expected = expected_types
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(MistQLError::Type(format!(
+            "Expected one of {}, got {}",
+            expected,
+            value.get_type()
+        )));
+    }
+    Ok(value)
+}
+
+pub fn assert_int(value: RuntimeValue) -> Result<i64> {
+    match value {
+        RuntimeValue::Number(n) if n.fract() == 0.0 => Ok(n as i64),
+        RuntimeValue::Number(n) => Err(MistQLError::Type(format!("Expected integer, got {}", n))),
+        other => Err(MistQLError::Type(format!(
+            "Expected {}, got {}",
+            RuntimeValueType::Number,
+            other.get_type()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_number;
+
+    #[test]
+    fn formats_numbers_like_js() {
+        assert_eq!(format_number(1.0), "1");
+        assert_eq!(format_number(-30.5), "-30.5");
+        assert_eq!(format_number(1e21), "1e+21");
+        assert_eq!(format_number(3e20), "300000000000000000000");
+        assert_eq!(format_number(1e-6), "0.000001");
+        assert_eq!(format_number(1e-7), "1e-7");
+        assert_eq!(format_number(4.81429127e-50), "4.81429127e-50");
+        assert_eq!(format_number(4503599627370495.5), "4503599627370495.5");
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use crate::errors::{MistQLError, Result};
+use crate::runtime_value::{FunctionDefinition, RuntimeValue};
+
+pub type StackFrame = HashMap<String, RuntimeValue>;
+pub type Stack = Vec<Arc<StackFrame>>;
+
+pub fn make_stack_entry_from_runtime_value(value: &RuntimeValue) -> StackFrame {
+    let mut new_stackframe = StackFrame::new();
+    if let RuntimeValue::Object(entries) = value {
+        // Fields can be named anything, by quoting them in backticks, but can't
+        // shadow the root reference
+        for (key, item) in entries.iter().filter(|(key, _)| key.as_str() != "$") {
+            new_stackframe.insert(key.clone(), item.clone());
+        }
+    }
+    new_stackframe.insert("@".to_string(), value.clone());
+    new_stackframe
+}
+
+pub fn add_runtime_value_to_stack(value: &RuntimeValue, stack: &Stack) -> Stack {
+    let mut new_stack = stack.clone();
+    new_stack.push(Arc::new(make_stack_entry_from_runtime_value(value)));
+    new_stack
+}
+
+pub fn add_bindings_to_stack(bindings: StackFrame, stack: &Stack) -> Stack {
+    let mut new_stack = stack.clone();
+    new_stack.push(Arc::new(bindings));
+    new_stack
+}
+
+pub fn build_initial_stack(
+    data: &RuntimeValue,
+    builtins: &HashMap<&'static str, FunctionDefinition>,
+    extras: &HashMap<String, RuntimeValue>,
+) -> Stack {
+    let mut functions_frame = StackFrame::new();
+    for (key, builtin) in builtins {
+        functions_frame.insert(key.to_string(), RuntimeValue::Function(builtin.clone()));
+    }
+    for (key, value) in extras {
+        functions_frame.insert(key.clone(), value.clone());
+    }
+
+    let mut dollar_var_dict: BTreeMap<String, RuntimeValue> = functions_frame
+        .iter()
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    dollar_var_dict.insert("@".to_string(), data.clone());
+
+    let mut dollar_frame = StackFrame::new();
+    dollar_frame.insert("$".to_string(), RuntimeValue::object(dollar_var_dict));
+
+    vec![
+        Arc::new(functions_frame),
+        Arc::new(dollar_frame),
+        Arc::new(make_stack_entry_from_runtime_value(data)),
+    ]
+}
+
+/// The value of `@` some number of contexts out, one for each caret in the
+/// name. Every pipeline stage and every item a function evaluates an
+/// expression against is a context of its own.
+fn find_enclosing_context(stack: &Stack, name: &str) -> Result<RuntimeValue> {
+    stack
+        .iter()
+        .rev()
+        .filter_map(|frame| frame.get("@"))
+        .nth(name.len() - 1)
+        .cloned()
+        .ok_or_else(|| MistQLError::Reference(format!("Could not find {} in stack", name)))
+}
+
+/// A field named like a context, as `@` and `@^` can be when quoted in
+/// backticks. Only contexts can hold such a name, so only their fields are
+/// searched.
+fn find_context_field(stack: &Stack, name: &str) -> Result<RuntimeValue> {
+    stack
+        .iter()
+        .rev()
+        .find_map(|frame| match frame.get("@") {
+            Some(RuntimeValue::Object(entries)) => entries.get(name),
+            _ => None,
+        })
+        .cloned()
+        .ok_or_else(|| MistQLError::Reference(format!("Could not find {} in stack", name)))
+}
+
+pub fn find_in_stack(
+    stack: &Stack,
+    name: &str,
+    absolute: bool,
+    quoted: bool,
+) -> Result<RuntimeValue> {
+    if quoted && name.starts_with('@') {
+        return find_context_field(stack, name);
+    }
+    if !absolute && name.starts_with("@^") {
+        return find_enclosing_context(stack, name);
+    }
+    let frames = if absolute { &stack[..1] } else { &stack[..] };
+    for frame in frames.iter().rev() {
+        if let Some(value) = frame.get(name) {
+            return Ok(value.clone());
+        }
+    }
+    Err(MistQLError::Reference(format!(
+        "Could not find {} in stack",
+        name
+    )))
+}
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use mistql::{MistQLInstance, RuntimeValue};
+use serde_json::{json, Value};
+
+fn instance_with(name: &str, function: RuntimeValue) -> MistQLInstance {
+    let mut extras = HashMap::new();
+    extras.insert(name.to_string(), function);
+    MistQLInstance::new(extras)
+}
+
+#[test]
+fn test_basic_custom_function() {
+    let add_one = RuntimeValue::from_rust_fn(1, Some(1), |args| {
+        Ok(json!(args[0].as_f64().unwrap() + 1.0))
+    });
+    let mq = instance_with("add_one", add_one);
+    assert_eq!(mq.query("add_one 1", &Value::Null).unwrap(), json!(2));
+}
+
+#[test]
+fn test_variadic_custom_function() {
+    let add = RuntimeValue::from_rust_fn(0, None, |args| {
+        Ok(json!(args
+            .iter()
+            .map(|arg| arg.as_f64().unwrap())
+            .sum::<f64>()))
+    });
+    let mq = instance_with("add", add);
+    assert_eq!(mq.query("add 1 2 3", &Value::Null).unwrap(), json!(6));
+    assert_eq!(mq.query("add 1 2 3 4", &Value::Null).unwrap(), json!(10));
+}
+
+#[test]
+fn test_bad_arity_raises() {
+    let add = RuntimeValue::from_rust_fn(2, Some(2), |args| {
+        Ok(json!(args[0].as_f64().unwrap() + args[1].as_f64().unwrap()))
+    });
+    let mq = instance_with("add", add);
+    assert_eq!(mq.query("add 1 2", &Value::Null).unwrap(), json!(3));
+    assert!(mq.query("add 1", &Value::Null).is_err());
+    assert!(mq.query("add 1 2 3", &Value::Null).is_err());
+}
+
+#[test]
+fn test_using_runtime_value_construction() {
+    let add = RuntimeValue::wrap_function_def(|args, stack, exec| {
+        match (exec(&args[0], stack)?, exec(&args[1], stack)?) {
+            (RuntimeValue::Number(a), RuntimeValue::Number(b)) => Ok(RuntimeValue::number(a + b)),
+            _ => Ok(RuntimeValue::Null),
+        }
+    });
+    let mq = instance_with("add", add);
+    assert_eq!(mq.query("add 1 2", &Value::Null).unwrap(), json!(3));
+}
@@ -0,0 +1,60 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+#[test]
+fn test_version() {
+    let meta_file_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../meta.json");
+    let meta: Value = serde_json::from_str(&fs::read_to_string(meta_file_path).unwrap()).unwrap();
+    assert_eq!(meta["version"], json!(mistql::VERSION));
+}
+
+#[test]
+fn test_query_is_callable() {
+    assert_eq!(mistql::query("@", &json!(1)).unwrap(), json!(1));
+}
+
+#[test]
+fn test_compiled_query_runs_on_different_data() {
+    let compiled = mistql::compile("@ | filter type == \"click\" | count").unwrap();
+    let clicks = json!([{"type": "click"}, {"type": "click"}]);
+    assert_eq!(
+        compiled
+            .run(&json!([{"type": "click"}, {"type": "purchase"}]))
+            .unwrap(),
+        json!(1)
+    );
+    assert_eq!(compiled.run(&clicks).unwrap(), json!(2));
+}
+
+#[test]
+fn test_now_comes_from_the_instance_clock() {
+    let instance = mistql::MistQLInstance::default()
+        .with_now(|| UNIX_EPOCH + Duration::from_millis(1_614_834_367_890));
+    assert_eq!(
+        instance.query("now", &Value::Null).unwrap(),
+        json!("2021-03-04T05:06:07.890Z")
+    );
+    assert_eq!(
+        instance
+            .query("truncdate \"month\" now", &Value::Null)
+            .unwrap(),
+        json!("2021-03-01T00:00:00.000Z")
+    );
+}
+
+#[test]
+fn test_deep_recursion_is_an_error() {
+    let query = "def down n = if (n > 0) (down (n - 1)) n in down 3000";
+    assert_eq!(
+        mistql::query(query, &Value::Null),
+        Err(mistql::MistQLError::ResourceLimit(
+            "Exceeded the maximum recursion depth".to_string()
+        ))
+    );
+    // The depth is released after an error, so later queries still run
+    let query = "def down n = if (n > 0) (down (n - 1)) n in down 100";
+    assert_eq!(mistql::query(query, &Value::Null).unwrap(), json!(0));
+}
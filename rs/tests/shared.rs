@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::Path;
+
+use mistql::MistQLError;
+use serde_json::Value;
+
+const SELF_LANG_ID: &str = "rs";
+
+struct Assertion {
+    query: String,
+    data: Value,
+    expected: Value,
+    throws: bool,
+    /// Whether the query must fail at parse time, rather than at runtime.
+    throws_parse: bool,
+}
+
+struct Case {
+    id: String,
+    assertions: Vec<Assertion>,
+    skipped: bool,
+}
+
+fn load_cases() -> Vec<Case> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../shared/testdata.json");
+    let raw = fs::read_to_string(path).expect("could not read shared/testdata.json");
+    let testdata: Value = serde_json::from_str(&raw).expect("testdata.json is not valid JSON");
+
+    let mut cases = Vec::new();
+    for block in testdata["data"].as_array().unwrap() {
+        for innerblock in block["cases"].as_array().unwrap() {
+            for test in innerblock["cases"].as_array().unwrap() {
+                let skipped = test["skip"]
+                    .as_array()
+                    .is_some_and(|skip| skip.iter().any(|lang| lang == SELF_LANG_ID));
+                let assertions = test["assertions"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|assertion| Assertion {
+                        query: assertion["query"].as_str().unwrap().to_string(),
+                        data: assertion["data"].clone(),
+                        expected: assertion["expected"].clone(),
+                        throws: assertion
+                            .get("throws")
+                            .is_some_and(|throws| !throws.is_null()),
+                        throws_parse: assertion["throws"] == "parse",
+                    })
+                    .collect();
+                cases.push(Case {
+                    id: format!(
+                        "{}::{}::{}",
+                        block["describe"].as_str().unwrap(),
+                        innerblock["describe"].as_str().unwrap(),
+                        test["it"].as_str().unwrap()
+                    ),
+                    assertions,
+                    skipped,
+                });
+            }
+        }
+    }
+    cases
+}
+
+// Numbers compare by value, so that `1` and `1.0` are considered equal.
+fn json_eq(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| json_eq(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, value)| b.get(key).is_some_and(|other| json_eq(value, other)))
+        }
+        _ => a == b,
+    }
+}
+
+#[test]
+fn test_shared() {
+    let mut failures = Vec::new();
+    for case in load_cases().iter().filter(|case| !case.skipped) {
+        for assertion in &case.assertions {
+            let result = mistql::query(&assertion.query, &assertion.data);
+            let failure = match (&result, assertion.throws) {
+                (Err(MistQLError::Parse(_)), true) => None,
+                (Err(err), true) if assertion.throws_parse => {
+                    Some(format!("expected a parse error, got {}", err))
+                }
+                (Err(_), true) => None,
+                (Ok(actual), true) => Some(format!("expected an error, got {}", actual)),
+                (Ok(actual), false) if json_eq(actual, &assertion.expected) => None,
+                (Ok(actual), false) => {
+                    Some(format!("expected {}, got {}", assertion.expected, actual))
+                }
+                (Err(err), false) => Some(format!("unexpected error: {}", err)),
+            };
+            if let Some(failure) = failure {
+                failures.push(format!("{} `{}`: {}", case.id, assertion.query, failure));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{} shared assertions failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}